@@ -1,25 +1,569 @@
-mod classes;
-use classes::{Game, Piece, Position};
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-pub fn evaluate (game: &Game) -> i32 {
+use crate::aux_func::letter_to_int;
+use crate::game::Game;
+use crate::moves::Move;
+use crate::piece::Piece;
+use crate::tablebase::{tablebase, Wdl};
+
+// scores beyond this are mates, MATE - n being mate in n plies
+pub const MATE: i32 = 100_000;
+const MATE_BOUND: i32 = MATE - 1000;
+// won endgames by the tablebases, worse than any mate but better than any material
+const TB_WIN: i32 = 20_000;
+// captures are followed this many plies past the nominal depth
+const QUIESCENCE_DEPTH: u32 = 6;
+
+// piece square tables from white's point of view, eighth rank first
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+   -50,-40,-30,-30,-30,-30,-40,-50,
+   -40,-20,  0,  0,  0,  0,-20,-40,
+   -30,  0, 10, 15, 15, 10,  0,-30,
+   -30,  5, 15, 20, 20, 15,  5,-30,
+   -30,  0, 15, 20, 20, 15,  0,-30,
+   -30,  5, 10, 15, 15, 10,  5,-30,
+   -40,-20,  0,  5,  5,  0,-20,-40,
+   -50,-40,-30,-30,-30,-30,-40,-50,
+];
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+   -20,-10,-10,-10,-10,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5, 10, 10,  5,  0,-10,
+   -10,  5,  5, 10, 10,  5,  5,-10,
+   -10,  0, 10, 10, 10, 10,  0,-10,
+   -10, 10, 10, 10, 10, 10, 10,-10,
+   -10,  5,  0,  0,  0,  0,  5,-10,
+   -20,-10,-10,-10,-10,-10,-10,-20,
+];
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+   -20,-10,-10, -5, -5,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+    -5,  0,  5,  5,  5,  5,  0, -5,
+     0,  0,  5,  5,  5,  5,  0, -5,
+   -10,  5,  5,  5,  5,  5,  0,-10,
+   -10,  0,  5,  0,  0,  0,  0,-10,
+   -20,-10,-10, -5, -5,-10,-10,-20,
+];
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -20,-30,-30,-40,-40,-30,-30,-20,
+   -10,-20,-20,-20,-20,-20,-20,-10,
+    20, 20,  0,  0,  0,  0, 20, 20,
+    20, 30, 10,  0,  0, 10, 30, 20,
+];
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+   -50,-40,-30,-20,-20,-30,-40,-50,
+   -30,-20,-10,  0,  0,-10,-20,-30,
+   -30,-10, 20, 30, 30, 20,-10,-30,
+   -30,-10, 30, 40, 40, 30,-10,-30,
+   -30,-10, 30, 40, 40, 30,-10,-30,
+   -30,-10, 20, 30, 30, 20,-10,-30,
+   -30,-30,  0,  0,  0,  0,-30,-30,
+   -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+pub fn piece_value(name: &str) -> i32 {
+    match name {
+        "pawn" => 100,
+        "knight" => 320,
+        "bishop" => 330,
+        "rook" => 500,
+        "queen" => 900,
+        _ => 0,
+    }
+}
+
+fn square_bonus(piece: &Piece, endgame: bool) -> i32 {
+    let table: &[i32; 64] = match piece.name.as_str() {
+        "pawn" => &PAWN_TABLE,
+        "knight" => &KNIGHT_TABLE,
+        "bishop" => &BISHOP_TABLE,
+        "rook" => &ROOK_TABLE,
+        "queen" => &QUEEN_TABLE,
+        _ if endgame => &KING_ENDGAME_TABLE,
+        _ => &KING_TABLE,
+    };
+    let column = (letter_to_int(piece.position.column) - 1) as usize;
+    // black reads the table upside down
+    let rank_from_top = if piece.white {
+        8 - piece.position.row
+    } else {
+        piece.position.row - 1
+    } as usize;
+    table[rank_from_top * 8 + column]
+}
+
+/*
+Score for the side to move when the game's variant says it's over, like a mate ply plies deep
+*/
+fn variant_score(game: &Game, ply: i32) -> Option<i32> {
+    let (result, _) = game.variant.result(game)?;
+    Some(match result.as_str() {
+        "1/2-1/2" => 0,
+        result if (result == "1-0") == game.white_to_move => MATE - ply,
+        _ => ply - MATE,
+    })
+}
+
+/*
+Material and piece placement in centipawns, positive when white is better
+*/
+pub fn evaluate(game: &Game) -> i32 {
+    let pieces_left: i32 = game
+        .pieces
+        .values()
+        .filter(|p| p.name != "pawn")
+        .map(|p| piece_value(&p.name))
+        .sum();
+    let endgame = pieces_left <= 1600;
     let mut score = 0;
     for piece in game.pieces.values() {
-        let mut piece_score = 0;
+        let piece_score = piece_value(&piece.name) + square_bonus(piece, endgame);
         if piece.white {
-            piece_score += 1;
+            score += piece_score;
+        } else {
+            score -= piece_score;
+        }
+    }
+    // a piece in hand is worth about as much as one on the board
+    for name in &game.pockets.0 {
+        score += piece_value(name);
+    }
+    for name in &game.pockets.1 {
+        score -= piece_value(name);
+    }
+    score
+}
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Score {
+    Cp(i32),
+    Mate(i32), // moves to mate, negative when getting mated
+}
+impl Score {
+    pub fn from_search(score: i32) -> Score {
+        if score > MATE_BOUND {
+            Score::Mate((MATE - score + 1) / 2)
+        } else if score < -MATE_BOUND {
+            Score::Mate(-(MATE + score + 1) / 2)
+        } else {
+            Score::Cp(score)
+        }
+    }
+    /*
+    Centipawns, with mates pushed far past any material count
+    */
+    pub fn centipawns(&self) -> i32 {
+        match *self {
+            Score::Cp(cp) => cp,
+            Score::Mate(moves) if moves > 0 => MATE - moves,
+            Score::Mate(moves) => -MATE - moves,
+        }
+    }
+    pub fn negate(&self) -> Score {
+        match *self {
+            Score::Cp(cp) => Score::Cp(-cp),
+            Score::Mate(moves) => Score::Mate(-moves),
+        }
+    }
+}
+
+/*
+When to stop thinking. With nothing set the search goes 4 plies deep
+*/
+#[derive(Clone, Default, Debug)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub stop: Option<Arc<AtomicBool>>, // set from elsewhere to stop the search early
+}
+
+#[derive(Clone)]
+pub struct SearchResult {
+    pub best: Move,
+    pub score: Score, // from the point of view of the side to move
+    pub depth: u32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+/*
+Random centipawns, up to amplitude either way, added to every evaluation so the engine
+misjudges positions on purpose
+*/
+pub struct Noise<'a> {
+    pub amplitude: i32,
+    pub rng: &'a mut StdRng,
+}
+
+struct Searcher<'a> {
+    limits: &'a Limits,
+    started: Instant,
+    nodes: u64,
+    stopped: bool,
+    hint: Option<Move>, // best move of the last iteration, tried first at the root
+    root_moves: Option<Vec<Move>>, // the only moves looked at from the root, when set
+    excluded: Vec<Move>, // root moves already taken by better lines
+    noise: Option<Noise<'a>>,
+}
+impl Searcher<'_> {
+    fn noise(&mut self) -> i32 {
+        match &mut self.noise {
+            Some(noise) if noise.amplitude > 0 => {
+                noise.rng.gen_range(-noise.amplitude..=noise.amplitude)
+            }
+            _ => 0,
+        }
+    }
+    fn out_of_budget(&mut self) -> bool {
+        if !self.stopped {
+            self.stopped = self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes)
+                || self
+                    .limits
+                    .movetime
+                    .is_some_and(|time| self.started.elapsed() >= time)
+                || self
+                    .limits
+                    .stop
+                    .as_ref()
+                    .is_some_and(|stop| stop.load(Ordering::Relaxed));
+        }
+        self.stopped
+    }
+    fn negamax(
+        &mut self,
+        game: &Game,
+        depth: u32,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.out_of_budget() {
+            return 0;
+        }
+        if ply > 0 {
+            if let Some(score) = variant_score(game, ply) {
+                return score;
+            }
+        }
+        if ply > 0 && (game.halfmove_clock >= 100 || game.is_draw()) {
+            return 0;
+        }
+        // right after a capture or pawn move the tablebases know how it ends
+        if ply > 0 && game.halfmove_clock == 0 {
+            if let Some(wdl) = tablebase().and_then(|tb| tb.probe_wdl(game).ok()) {
+                return match wdl {
+                    Wdl::Win => TB_WIN - ply,
+                    Wdl::Loss => ply - TB_WIN,
+                    _ => 0,
+                };
+            }
+        }
+        let hint = if ply == 0 { self.hint.as_ref() } else { None };
+        let mut moves = ordered_moves(game, hint);
+        if ply == 0 {
+            if let Some(allowed) = &self.root_moves {
+                moves.retain(|mov| allowed.iter().any(|a| same_move(a, mov)));
+            }
+            moves.retain(|mov| !self.excluded.iter().any(|e| same_move(e, mov)));
+        }
+        if moves.is_empty() {
+            return if game.is_in_check(game.white_to_move) {
+                ply - MATE
+            } else {
+                0
+            };
+        }
+        if depth == 0 {
+            return self.quiesce(game, QUIESCENCE_DEPTH, ply, alpha, beta);
+        }
+        for mov in moves {
+            let mut child: Game = game.clone();
+            // every move here came out of legal_moves
+            let _ = child.play(&mov);
+            let mut line: Vec<Move> = Vec::new();
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, &mut line);
+            if self.stopped {
+                return 0;
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(mov);
+                pv.extend(line);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        alpha
+    }
+    /*
+    Only captures from here on, so the search doesn't stop in the middle of an exchange
+    */
+    fn quiesce(&mut self, game: &Game, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        // atomic captures can blow up a king
+        if let Some(score) = variant_score(game, ply) {
+            return score;
+        }
+        let stand_pat = if game.white_to_move {
+            evaluate(game)
         } else {
-            piece_score -= 1;
-        }
-        piece_score += match &piece.name[..] {
-            "pawn" => 1,
-            "knight" => 3,
-            "bishop" => 3,
-            "rook" => 5,
-            "queen" => 9,
-            "king" => 1000,
+            -evaluate(game)
+        } + self.noise();
+        if depth == 0 || stand_pat >= beta || self.out_of_budget() {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        for mov in ordered_moves(game, None) {
+            if capture_value(game, &mov) == 0 && mov.promotion.is_none() {
+                // ordered_moves puts captures first, so there are none left
+                break;
+            }
+            let mut child: Game = game.clone();
+            let _ = child.play(&mov);
+            let score = -self.quiesce(&child, depth - 1, ply + 1, -beta, -alpha);
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
+
+/*
+Value of what mov takes, 0 for a quiet move
+*/
+fn capture_value(game: &Game, mov: &Move) -> i32 {
+    match game.pieces.get(&(mov.to.column, mov.to.row)) {
+        Some(victim) if victim.white != game.white_to_move => piece_value(&victim.name),
+        // castling in Chess960 lands on the king's own rook
+        Some(_) => 0,
+        None => match game.pieces.get(&(mov.from.column, mov.from.row)) {
+            // en passant
+            Some(piece) if piece.name == "pawn" && mov.from.column != mov.to.column => 100,
             _ => 0,
+        },
+    }
+}
+
+pub fn same_move(a: &Move, b: &Move) -> bool {
+    a.from.equals(&b.from) && a.to.equals(&b.to) && a.promotion == b.promotion && a.drop == b.drop
+}
+
+/*
+Legal moves with the most promising first: the hint, then captures of the biggest piece by the
+smallest one, then promotions
+*/
+fn ordered_moves(game: &Game, hint: Option<&Move>) -> Vec<Move> {
+    let mut moves: Vec<(i32, Move)> = game
+        .legal_moves()
+        .into_iter()
+        .map(|mov| {
+            let mut order = 0;
+            if hint.is_some_and(|h| same_move(h, &mov)) {
+                order = 1_000_000;
+            }
+            let victim = capture_value(game, &mov);
+            if victim > 0 {
+                let attacker = game
+                    .pieces
+                    .get(&(mov.from.column, mov.from.row))
+                    .map_or(0, |p| piece_value(&p.name));
+                order += 10_000 + victim * 10 - attacker / 10;
+            }
+            if let Some(name) = &mov.promotion {
+                order += 5_000 + piece_value(name);
+            }
+            (order, mov)
+        })
+        .collect();
+    // keep the generation order for ties so searches are repeatable
+    moves.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.to_string().cmp(&b.1.to_string()))
+    });
+    moves.into_iter().map(|(_, mov)| mov).collect()
+}
+
+/*
+Alpha-beta search with iterative deepening, None when there's no legal move.
+The result is the deepest search that finished inside the limits
+*/
+pub fn search(game: &Game, limits: &Limits) -> Option<SearchResult> {
+    search_lines(game, limits, 1, None).into_iter().next()
+}
+
+/*
+The best lines moves, best first, each searched with the moves of the lines before it left
+out. Endgames the tablebases have are won (or lost) by their fastest (or slowest) move, the
+other lines following in the tables' order, and drawn ones only searched among the moves that
+keep the draw
+*/
+pub fn search_lines(
+    game: &Game,
+    limits: &Limits,
+    lines: usize,
+    noise: Option<Noise>,
+) -> Vec<SearchResult> {
+    deepen(game, limits, lines, noise, &mut |_| {})
+}
+
+/*
+Like search_lines without noise, but every depth's lines are handed to report as soon as
+they're done
+*/
+pub fn analyse(
+    game: &Game,
+    limits: &Limits,
+    lines: usize,
+    report: &mut dyn FnMut(&[SearchResult]),
+) -> Vec<SearchResult> {
+    deepen(game, limits, lines, None, report)
+}
+
+fn deepen(
+    game: &Game,
+    limits: &Limits,
+    lines: usize,
+    noise: Option<Noise>,
+    report: &mut dyn FnMut(&[SearchResult]),
+) -> Vec<SearchResult> {
+    let mut root_moves: Vec<Move> = ordered_moves(game, None);
+    let mut allowed: Option<Vec<Move>> = None;
+    if let Some(rated) = tablebase().and_then(|tb| tb.rate_moves(game).ok()) {
+        let Some(best) = rated.first().cloned() else {
+            return Vec::new();
         };
-        score += piece_score;
+        // won or lost, the tables rate every move and there's nothing left to search
+        if matches!(best.wdl, Wdl::Win | Wdl::Loss) {
+            let results: Vec<SearchResult> = rated
+                .into_iter()
+                .take(lines.max(1))
+                .map(|m| {
+                    let score = match m.wdl {
+                        Wdl::Win => TB_WIN - m.dtz,
+                        Wdl::Loss => -TB_WIN - m.dtz,
+                        _ => 0,
+                    };
+                    SearchResult {
+                        pv: vec![m.mov.clone()],
+                        best: m.mov,
+                        score: Score::from_search(score),
+                        depth: 1,
+                        nodes: 0,
+                    }
+                })
+                .collect();
+            report(&results);
+            return results;
+        }
+        root_moves = rated
+            .into_iter()
+            .filter(|m| m.wdl == best.wdl)
+            .map(|m| m.mov)
+            .collect();
+        allowed = Some(root_moves.clone());
     }
-    score
-}
\ No newline at end of file
+    let Some(first) = root_moves.first().cloned() else {
+        return Vec::new();
+    };
+    let lines = lines.clamp(1, root_moves.len());
+    let max_depth = match (limits.depth, limits.nodes, limits.movetime) {
+        (Some(depth), _, _) => depth.max(1),
+        (None, None, None) => 4,
+        _ => 64,
+    };
+    let mut searcher = Searcher {
+        limits,
+        started: Instant::now(),
+        nodes: 0,
+        stopped: false,
+        hint: None,
+        root_moves: allowed,
+        excluded: Vec::new(),
+        noise,
+    };
+    let mut results: Vec<SearchResult> = Vec::new();
+    'deepening: for depth in 1..=max_depth {
+        let mut found: Vec<SearchResult> = Vec::new();
+        searcher.excluded.clear();
+        for line in 0..lines {
+            searcher.hint = results.get(line).map(|r| r.best.clone());
+            let mut pv: Vec<Move> = Vec::new();
+            let score = searcher.negamax(game, depth, 0, -MATE - 1, MATE + 1, &mut pv);
+            if searcher.stopped || pv.is_empty() {
+                break 'deepening;
+            }
+            searcher.excluded.push(pv[0].clone());
+            found.push(SearchResult {
+                best: pv[0].clone(),
+                score: Score::from_search(score),
+                depth,
+                nodes: 0,
+                pv,
+            });
+        }
+        results = found;
+        for result in results.iter_mut() {
+            result.nodes = searcher.nodes;
+        }
+        report(&results);
+        // nothing deeper changes forced mates
+        if results.iter().all(|r| matches!(r.score, Score::Mate(_))) {
+            break;
+        }
+    }
+    if results.is_empty() {
+        results.push(SearchResult {
+            best: first,
+            score: Score::Cp(0),
+            depth: 0,
+            nodes: searcher.nodes,
+            pv: Vec::new(),
+        });
+    }
+    results
+}
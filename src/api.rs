@@ -0,0 +1,595 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chess::ai::{Score, SearchResult};
+use chess::computer::Thought;
+use chess::editor::Draft;
+use chess::fen::POCKET_ORDER;
+use chess::game::{Game, Termination};
+use chess::moves::Move;
+use chess::notation::{san, san_moves_from};
+use chess::position::Position;
+use chess::review::{Judgement, PlayerReview, Review};
+use chess::tablebase::{RatedMove, Wdl};
+
+use crate::rating::category;
+use crate::storage::StoredGame;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Color {
+    White,
+    Black,
+}
+impl Color {
+    pub fn from_bool(white: bool) -> Color {
+        if white {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ongoing,
+    Checkmate,
+    Stalemate,
+    InsufficientMaterial,
+    Timeout,
+    Resignation,
+    Abandoned,
+    Tablebase,
+    KingOfTheHill,
+    ThreeChecks,
+    Explosion,
+}
+impl Status {
+    pub fn from_termination(termination: Termination) -> Status {
+        match termination {
+            Termination::Checkmate => Status::Checkmate,
+            Termination::Stalemate => Status::Stalemate,
+            Termination::InsufficientMaterial => Status::InsufficientMaterial,
+            Termination::Timeout => Status::Timeout,
+            Termination::Resignation => Status::Resignation,
+            Termination::Abandoned => Status::Abandoned,
+            Termination::Tablebase => Status::Tablebase,
+            Termination::KingOfTheHill => Status::KingOfTheHill,
+            Termination::ThreeChecks => Status::ThreeChecks,
+            Termination::Explosion => Status::Explosion,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PieceView {
+    pub color: Color,
+    pub name: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MoveView {
+    pub from: String,
+    pub to: String,
+    pub promotion: Option<String>,
+    pub drop: Option<String>, // the piece put on to, from and to are the same then
+}
+impl MoveView {
+    pub fn from_move(mov: &Move) -> MoveView {
+        MoveView {
+            from: mov.from.to_string(),
+            to: mov.to.to_string(),
+            promotion: mov.promotion.clone(),
+            drop: mov.drop.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TargetView {
+    pub to: String,
+    pub promotions: Vec<String>, // empty unless the pawn has to pick a piece
+}
+
+#[derive(Serialize, Debug)]
+pub struct LegalMoves {
+    pub from: String,
+    pub moves: Vec<TargetView>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayersView {
+    pub white: Option<String>,
+    pub black: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClockView {
+    pub white_ms: u64,
+    pub black_ms: u64,
+    pub increment_ms: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChecksView {
+    pub white: u32, // checks given by white
+    pub black: u32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PocketsView {
+    pub white: Vec<String>, // biggest pieces first
+    pub black: Vec<String>,
+}
+impl PocketsView {
+    pub fn from_game(game: &Game) -> PocketsView {
+        let sorted = |pocket: &Vec<String>| {
+            let mut pocket = pocket.clone();
+            pocket.sort_by_key(|name| POCKET_ORDER.iter().position(|p| p == name));
+            pocket
+        };
+        PocketsView {
+            white: sorted(&game.pockets.0),
+            black: sorted(&game.pockets.1),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct BoardState {
+    pub id: u64,
+    pub board: BTreeMap<String, PieceView>, // "E2" -> white pawn
+    pub to_move: Color,
+    pub status: Status,
+    pub result: Option<String>,
+    pub check: bool,
+    pub legal_moves: BTreeMap<String, Vec<TargetView>>, // "E2" -> E3 and E4
+    pub legal_drops: BTreeMap<String, Vec<String>>,     // "knight" -> squares it can go to
+    pub last_move: Option<MoveView>,
+    pub players: PlayersView,
+    pub spectators: usize,
+    pub rated: bool,
+    pub category: String, // which rating the game counts for
+    pub clock: Option<ClockView>,
+    pub chess960: bool,
+    pub variant: String,
+    pub checks: Option<ChecksView>,   // three-check only
+    pub pockets: Option<PocketsView>, // crazyhouse only
+    pub start_fen: Option<String>,    // when it didn't start from the usual position
+}
+
+/*
+The board for a player who just got a seat, with the token that proves it's them when moving,
+resigning or resetting
+*/
+#[derive(Serialize, Debug)]
+pub struct SeatedState {
+    pub token: String,
+    #[serde(flatten)]
+    pub state: BoardState,
+}
+
+fn seat(name: &str) -> Option<String> {
+    if name == "none" {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/*
+Legal moves of the piece on from, grouped by target square so promotions show up as one choice
+*/
+pub fn legal_moves(game: &Game, from: &Position) -> LegalMoves {
+    let mut moves: Vec<TargetView> = Vec::new();
+    for mov in game.legal_moves_from(from) {
+        let to: String = mov.to.to_string();
+        let target: &mut TargetView = match moves.iter().position(|t| t.to == to) {
+            Some(i) => &mut moves[i],
+            None => {
+                moves.push(TargetView {
+                    to,
+                    promotions: Vec::new(),
+                });
+                moves.last_mut().unwrap()
+            }
+        };
+        if let Some(name) = mov.promotion {
+            target.promotions.push(name);
+        }
+    }
+    LegalMoves {
+        from: from.to_string(),
+        moves,
+    }
+}
+
+/*
+Everything a client needs to draw the game, taken from the live game and its stored record
+*/
+pub fn board_state(game: &Game, record: &StoredGame) -> BoardState {
+    let mut board: BTreeMap<String, PieceView> = BTreeMap::new();
+    let mut legal_moves: BTreeMap<String, Vec<TargetView>> = BTreeMap::new();
+    for piece in game.pieces.values() {
+        board.insert(
+            piece.position.to_string(),
+            PieceView {
+                color: Color::from_bool(piece.white),
+                name: piece.name.clone(),
+            },
+        );
+        if piece.white != game.white_to_move || record.result.is_some() {
+            continue;
+        }
+        let targets: LegalMoves = self::legal_moves(game, &piece.position);
+        if !targets.moves.is_empty() {
+            legal_moves.insert(targets.from, targets.moves);
+        }
+    }
+
+    let mut legal_drops: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    if record.result.is_none() {
+        for mov in game.legal_drops() {
+            if let Some(name) = mov.drop {
+                legal_drops
+                    .entry(name)
+                    .or_default()
+                    .push(mov.to.to_string());
+            }
+        }
+    }
+
+    let status: Status = match (record.result.as_deref(), record.termination) {
+        (None, _) => Status::Ongoing,
+        (Some(_), Some(termination)) => Status::from_termination(termination),
+        // games stored before terminations were kept
+        (Some("*"), None) => Status::Abandoned,
+        (Some(_), None) => match game.is_mate() {
+            1 => Status::Checkmate,
+            2 => Status::Stalemate,
+            _ if game.is_draw() => Status::InsufficientMaterial,
+            _ => Status::Timeout,
+        },
+    };
+
+    BoardState {
+        id: record.id,
+        board,
+        to_move: Color::from_bool(game.white_to_move),
+        status,
+        result: record.result.clone(),
+        check: game.is_in_check(game.white_to_move),
+        legal_moves,
+        legal_drops,
+        last_move: game.history.last().map(MoveView::from_move),
+        players: PlayersView {
+            white: seat(&record.white),
+            black: seat(&record.black),
+        },
+        spectators: 0,
+        rated: record.rated,
+        category: category(record.clock.as_ref()).to_string(),
+        clock: record.clock.as_ref().map(|clock| {
+            let (white_ms, black_ms) = clock.remaining(game.white_to_move);
+            ClockView {
+                white_ms,
+                black_ms,
+                increment_ms: clock.increment_ms,
+            }
+        }),
+        chess960: game.chess960,
+        variant: game.variant.name().to_string(),
+        checks: (game.variant.name() == "three_check").then_some(ChecksView {
+            white: game.checks.0,
+            black: game.checks.1,
+        }),
+        pockets: game
+            .variant
+            .has_drops()
+            .then(|| PocketsView::from_game(game)),
+        start_fen: record.start_fen.clone(),
+    }
+}
+
+/*
+A position in the board editor, with what keeps it from being played when something does
+*/
+#[derive(Serialize, Debug)]
+pub struct DraftView {
+    pub id: u64,
+    pub fen: String,
+    pub board: BTreeMap<String, PieceView>,
+    pub to_move: Color,
+    pub castling: String, // "KQkq" style, empty when nobody can castle
+    pub en_passant: Option<String>,
+    pub playable: bool,
+    pub problem: Option<String>, // why it isn't playable
+}
+impl DraftView {
+    pub fn from_draft(draft: &Draft) -> DraftView {
+        let problem = draft.game().err().map(String::from);
+        DraftView {
+            id: draft.id,
+            fen: draft.fen(),
+            board: draft
+                .pieces
+                .values()
+                .map(|piece| {
+                    (
+                        piece.position.to_string(),
+                        PieceView {
+                            color: Color::from_bool(piece.white),
+                            name: piece.name.clone(),
+                        },
+                    )
+                })
+                .collect(),
+            to_move: Color::from_bool(draft.white_to_move),
+            castling: draft.castling.clone(),
+            en_passant: draft.en_passant.as_ref().map(|s| s.to_string()),
+            playable: problem.is_none(),
+            problem,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct EngineView {
+    pub name: String,
+    pub builtin: bool,
+}
+
+/*
+An engine's answer for a position, the line in SAN from the position it was asked about
+*/
+#[derive(Serialize, Debug)]
+pub struct EngineMoveView {
+    pub engine: String,
+    pub best: String,
+    pub san: Option<String>,
+    pub score: Option<Score>,
+    pub depth: Option<u32>,
+    pub pv: Vec<String>,
+}
+impl EngineMoveView {
+    pub fn new(engine: &str, game: &Game, thought: &Thought) -> EngineMoveView {
+        let pv: Vec<String> = thought.pv.iter().map(|m| m.to_string()).collect();
+        EngineMoveView {
+            engine: engine.to_string(),
+            best: thought.best.to_string(),
+            san: san(game, &thought.best),
+            score: thought.score,
+            depth: thought.depth,
+            pv: san_moves_from(game, &pv),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TablebaseMoveView {
+    #[serde(rename = "move")]
+    pub mov: String,
+    pub san: Option<String>,
+    pub wdl: Wdl, // for the side making the move
+    pub dtz: i32,
+}
+
+/*
+What the tablebases say about a position: its result and DTZ for the side to move, and every
+move with the best first
+*/
+#[derive(Serialize, Debug)]
+pub struct TablebaseView {
+    pub fen: String,
+    pub wdl: Wdl,
+    pub dtz: i32,
+    pub best: Option<String>,
+    pub moves: Vec<TablebaseMoveView>,
+}
+impl TablebaseView {
+    pub fn new(game: &Game, wdl: Wdl, dtz: i32, moves: &[RatedMove]) -> TablebaseView {
+        TablebaseView {
+            fen: game.to_fen(),
+            wdl,
+            dtz,
+            best: moves.first().map(|m| m.mov.to_string()),
+            moves: moves
+                .iter()
+                .map(|m| TablebaseMoveView {
+                    mov: m.mov.to_string(),
+                    san: san(game, &m.mov),
+                    wdl: m.wdl,
+                    dtz: m.dtz,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct LineView {
+    pub multipv: usize, // 1 for the best line
+    pub score: Score,   // from the point of view of the side to move
+    pub depth: u32,
+    #[serde(rename = "move")]
+    pub mov: String,
+    pub san: Option<String>,
+    pub pv: Vec<String>, // in SAN
+}
+
+/*
+The best lines of a position at the depth the search got to, best first
+*/
+#[derive(Serialize, Debug)]
+pub struct AnalysisView {
+    pub fen: String,
+    pub depth: u32,
+    pub nodes: u64,
+    pub lines: Vec<LineView>,
+}
+impl AnalysisView {
+    pub fn new(game: &Game, results: &[SearchResult]) -> AnalysisView {
+        AnalysisView {
+            fen: game.to_fen(),
+            depth: results.iter().map(|r| r.depth).min().unwrap_or(0),
+            nodes: results.first().map_or(0, |r| r.nodes),
+            lines: results
+                .iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    let pv: Vec<String> = r.pv.iter().map(|m| m.to_string()).collect();
+                    LineView {
+                        multipv: i + 1,
+                        score: r.score,
+                        depth: r.depth,
+                        mov: r.best.to_string(),
+                        san: san(game, &r.best),
+                        pv: san_moves_from(game, &pv),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReviewedMoveView {
+    pub ply: usize,
+    #[serde(rename = "move")]
+    pub mov: String,
+    pub san: String,
+    pub judgement: Judgement,
+    pub loss: i32,            // centipawns given away compared to the engine's move
+    pub eval: Option<Score>,  // after the move, from white's point of view
+    pub best: Option<String>, // the engine's move in SAN, when it's another one
+    pub line: Vec<String>,
+}
+
+/*
+A finished game gone over by the engine: how well each side played and what it thought of
+every move
+*/
+#[derive(Serialize, Debug)]
+pub struct ReviewView {
+    pub id: u64,
+    pub white: PlayerReview,
+    pub black: PlayerReview,
+    pub moves: Vec<ReviewedMoveView>,
+}
+impl ReviewView {
+    pub fn new(id: u64, review: &Review) -> ReviewView {
+        ReviewView {
+            id,
+            white: review.white.clone(),
+            black: review.black.clone(),
+            moves: review
+                .moves
+                .iter()
+                .zip(&review.positions)
+                .enumerate()
+                .map(|(i, (reviewed, before))| {
+                    let line = reviewed.better_line(before);
+                    ReviewedMoveView {
+                        ply: i + 1,
+                        mov: reviewed.mov.to_string(),
+                        san: reviewed.san.clone(),
+                        judgement: reviewed.judgement,
+                        loss: reviewed.loss,
+                        eval: reviewed.eval,
+                        best: line.first().cloned(),
+                        line,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/*
+Error sent back to clients as {"error": {"code": ..., "message": ...}}.
+The code is meant for programs, the message for people
+*/
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+}
+impl ApiError {
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            code,
+            message: message.into(),
+        }
+    }
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError {
+            status: StatusCode::CONFLICT,
+            code,
+            message: message.into(),
+        }
+    }
+    pub fn forbidden(code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError {
+            status: StatusCode::FORBIDDEN,
+            code,
+            message: message.into(),
+        }
+    }
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError {
+            status: StatusCode::NOT_FOUND,
+            code,
+            message: message.into(),
+        }
+    }
+    pub fn unavailable(code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            code,
+            message: message.into(),
+        }
+    }
+    pub fn internal(message: impl Into<String>) -> ApiError {
+        ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            code: "internal_error",
+            message: message.into(),
+        }
+    }
+}
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+#[derive(Serialize)]
+struct ErrorDetail<'a> {
+    code: &'a str,
+    message: &'a str,
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status).json(ErrorBody {
+            error: ErrorDetail {
+                code: self.code,
+                message: &self.message,
+            },
+        })
+    }
+}
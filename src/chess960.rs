@@ -0,0 +1,153 @@
+use rand::Rng;
+
+use crate::error::Error;
+use crate::game::Game;
+
+// starting positions, numbered 0 to 959 the way Scharnagl did
+pub const POSITIONS: u32 = 960;
+
+// where the two knights go among the five squares bishops and queen leave
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/*
+White's back rank in starting position index, from a to h. 518 is the usual one
+*/
+pub fn back_rank(index: u32) -> [char; 8] {
+    let mut rank = [' '; 8];
+    let empty = |rank: &[char; 8]| (0..8).filter(|i| rank[*i] == ' ').collect::<Vec<usize>>();
+    let n = (index % POSITIONS) as usize;
+    // one bishop on the light squares b, d, f, h and one on the dark a, c, e, g
+    rank[2 * (n % 4) + 1] = 'B';
+    let n = n / 4;
+    rank[2 * (n % 4)] = 'B';
+    let n = n / 4;
+    rank[empty(&rank)[n % 6]] = 'Q';
+    let (first, second) = KNIGHTS[n / 6];
+    let free = empty(&rank);
+    rank[free[first]] = 'N';
+    rank[free[second]] = 'N';
+    // the king always ends up between the rooks
+    for (square, piece) in empty(&rank).into_iter().zip(['R', 'K', 'R']) {
+        rank[square] = piece;
+    }
+    rank
+}
+
+/*
+FEN of starting position index, with the castling rooks named by their files
+*/
+pub fn start_fen(index: u32) -> String {
+    let rank = back_rank(index);
+    let white: String = rank.iter().collect();
+    let rooks: String = (0..8)
+        .rev()
+        .filter(|i| rank[*i] == 'R')
+        .map(|i| (b'A' + i as u8) as char)
+        .collect();
+    format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w {}{} - 0 1",
+        white.to_lowercase(),
+        white,
+        rooks,
+        rooks.to_lowercase()
+    )
+}
+
+/*
+Starting position index, or a random one without an index
+*/
+pub fn start(index: Option<u32>) -> Result<Game, Error> {
+    let index = index.unwrap_or_else(|| rand::thread_rng().gen_range(0..POSITIONS));
+    if index >= POSITIONS {
+        return Err(Error::Setup(format!(
+            "{} is not a Chess960 position, they go from 0 to {}",
+            index,
+            POSITIONS - 1
+        )));
+    }
+    Game::from_fen(&start_fen(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::START_FEN;
+    use crate::notation::parse_san;
+
+    fn piece_on(game: &Game, square: (char, i32)) -> Option<(&str, bool)> {
+        game.pieces.get(&square).map(|p| (p.name.as_str(), p.white))
+    }
+
+    #[test]
+    fn start_positions() {
+        // the usual setup is number 518, and still gets its rooks named
+        let usual = start_fen(518);
+        assert_eq!(
+            usual,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1"
+        );
+        assert_eq!(back_rank(0).iter().collect::<String>(), "BBQNNRKR");
+        assert!(start(Some(POSITIONS)).is_err());
+        for index in [0, 518, 959] {
+            let game = start(Some(index)).unwrap();
+            assert!(game.chess960);
+            assert_eq!(game.to_fen(), start_fen(index));
+        }
+    }
+
+    #[test]
+    fn x_fen_and_shredder_fen_read_the_same() {
+        // X-FEN's KQkq stand for the outermost rooks, which Shredder-FEN names by file
+        let x_fen = "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w KQkq - 0 1";
+        let shredder = "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 0 1";
+        let game = Game::from_fen(x_fen).unwrap();
+        assert!(game.chess960);
+        assert_eq!(game.castling_files, ('E', 'G'));
+        assert_eq!(game.to_fen(), shredder);
+        assert_eq!(Game::from_fen(shredder).unwrap().to_fen(), shredder);
+        // with two rooks on one side the inner one needs its file, K means the outer one
+        let inner = Game::from_fen("1k4rr/8/8/8/8/8/8/1K4RR w Gg - 0 1").unwrap();
+        assert_eq!(inner.castling_files.1, 'G');
+        assert_eq!(inner.to_fen(), "1k4rr/8/8/8/8/8/8/1K4RR w Gg - 0 1");
+        let outer = Game::from_fen("1k4rr/8/8/8/8/8/8/1K4RR w Kk - 0 1").unwrap();
+        assert_eq!(outer.to_fen(), "1k4rr/8/8/8/8/8/8/1K4RR w Hh - 0 1");
+        // and normal chess keeps writing KQkq
+        assert_eq!(Game::from_fen(START_FEN).unwrap().to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn castling_with_the_king_next_to_its_rook() {
+        let fen = "1r4kr/8/8/8/8/8/8/1R4KR w HBhb - 0 1";
+        // short: the king stays where it is and the rook jumps over to f1
+        let mut game = Game::from_fen(fen).unwrap();
+        let castle = parse_san(&game, "O-O").unwrap();
+        game.play(&castle).unwrap();
+        assert_eq!(piece_on(&game, ('G', 1)), Some(("king", true)));
+        assert_eq!(piece_on(&game, ('F', 1)), Some(("rook", true)));
+        assert_eq!(piece_on(&game, ('H', 1)), None);
+        assert_eq!(game.to_fen(), "1r4kr/8/8/8/8/8/8/1R3RK1 b hb - 1 1");
+        // long: king to c1 and rook to d1, passing each other
+        let mut game = Game::from_fen(fen).unwrap();
+        let castle = parse_san(&game, "O-O-O").unwrap();
+        game.play(&castle).unwrap();
+        assert_eq!(piece_on(&game, ('C', 1)), Some(("king", true)));
+        assert_eq!(piece_on(&game, ('D', 1)), Some(("rook", true)));
+        assert_eq!(piece_on(&game, ('B', 1)), None);
+        assert_eq!(piece_on(&game, ('G', 1)), None);
+        // no castling through an attacked square: the rook on d8 covers the king's way to c1
+        let game = Game::from_fen("3r2k1/8/8/8/8/8/8/1R4KR w HB - 0 1").unwrap();
+        assert!(parse_san(&game, "O-O-O").is_none());
+        assert!(parse_san(&game, "O-O").is_some());
+    }
+}
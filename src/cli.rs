@@ -0,0 +1,371 @@
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chess::ai::{analyse, search, Limits, Score};
+use chess::book;
+use chess::fen::START_FEN;
+use chess::game::Game;
+use chess::notation::{self, san_moves_from, PgnGame};
+use chess::perft::{divide, perft};
+use chess::review::{annotated_pgn, review};
+use chess::tablebase;
+use chess::uci::uci_move;
+use chess::variant::{self, VARIANTS};
+
+use crate::api::AnalysisView;
+use crate::engine_match;
+use crate::terminal::{self, Opponent};
+use crate::xboard;
+
+/*
+Everything the binary does, the HTTP server being what it does without a command
+*/
+#[derive(Parser, Debug)]
+#[command(name = "chess", version, about = "Chess server, engine and tools")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Runs the HTTP server and the web interface
+    Serve {
+        /// Settings file, chess.toml when it's there
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Overrides server.host, 127.0.0.1 by default
+        #[arg(long)]
+        host: Option<String>,
+        /// Overrides server.port, 8080 by default
+        #[arg(long)]
+        port: Option<u16>,
+        /// Where the web interface's files are, ./ui by default
+        #[arg(long)]
+        static_dir: Option<PathBuf>,
+    },
+    /// Plays a game on the terminal
+    Play {
+        /// Plays against the built-in engine instead of someone else
+        #[arg(long)]
+        vs_engine: bool,
+        /// Takes black against the engine
+        #[arg(long, requires = "vs_engine")]
+        black: bool,
+        /// How deep the engine looks
+        #[arg(long, default_value_t = 4)]
+        depth: u32,
+    },
+    /// Counts the move sequences from a position, to check move generation
+    Perft {
+        /// The position, or "startpos"
+        fen: String,
+        depth: u32,
+        /// Counts for every first move separately
+        #[arg(long)]
+        divide: bool,
+        /// The rules to count by, like "atomic" or "crazyhouse"
+        #[arg(long, default_value = "standard")]
+        variant: String,
+    },
+    /// Reviews the games of a PGN file, or analyses a position given as FEN
+    Analyse {
+        /// A PGN file, or a FEN
+        input: String,
+        /// How deep the engine looks, 3 for games and 6 for a position by default
+        #[arg(long)]
+        depth: Option<u32>,
+        /// How many lines to show for a position
+        #[arg(long, default_value_t = 3)]
+        lines: usize,
+    },
+    /// Turns the games of a PGN file into JSON
+    Convert {
+        input: PathBuf,
+        /// Where the JSON goes, standard output when left out
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Prints the built-in engine's move for a position
+    Bestmove {
+        /// The position, or "startpos"
+        fen: String,
+        #[arg(long, default_value_t = 6)]
+        depth: u32,
+        /// Stops after this long instead, in milliseconds
+        #[arg(long)]
+        movetime: Option<u64>,
+    },
+    /// Plays an engine match from a config (see README)
+    Match { config: PathBuf },
+    /// Speaks the xboard protocol, for chess GUIs
+    Xboard {
+        /// A Polyglot book to play the opening from
+        book: Option<String>,
+    },
+    /// Builds a Polyglot opening book from a PGN collection
+    Book {
+        pgn: String,
+        book: String,
+        /// Leaves out moves played fewer times than this
+        #[arg(long, default_value_t = 1)]
+        min_count: u32,
+        /// Only looks at this many plies of every game
+        #[arg(long, default_value_t = 20)]
+        max_ply: usize,
+    },
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
+
+/*
+A position from the command line, "startpos" for the usual one
+*/
+fn position(fen: &str) -> io::Result<Game> {
+    let fen = if fen == "startpos" { START_FEN } else { fen };
+    Game::from_fen(fen).map_err(invalid)
+}
+
+fn score_text(score: &Score) -> String {
+    match score {
+        Score::Cp(cp) => format!("{:+.2}", *cp as f64 / 100.0),
+        Score::Mate(moves) => format!("#{}", moves),
+    }
+}
+
+pub fn play(vs_engine: bool, black: bool, depth: u32) -> io::Result<()> {
+    // no colours when piped somewhere, or for people who asked for none
+    let color = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let engine = vs_engine.then_some(Opponent {
+        white: black,
+        depth: depth.max(1),
+    });
+    terminal::run(io::stdin().lock(), io::stdout(), color, engine)
+}
+
+/*
+Perft like engines print it, moves first with --divide, then the total and how fast it went
+*/
+pub fn run_perft(fen: &str, variant: &str, depth: u32, split: bool) -> io::Result<()> {
+    let mut game = position(fen)?;
+    game.variant = variant::by_name(variant).ok_or_else(|| {
+        let names: Vec<&str> = VARIANTS.iter().map(|v| v.name()).collect();
+        invalid(format!("{:?} is not one of {}", variant, names.join(", ")))
+    })?;
+    let started = Instant::now();
+    let nodes: u64 = if split {
+        let counts = divide(&game, depth);
+        for (mov, count) in &counts {
+            println!("{}: {}", uci_move(mov), count);
+        }
+        println!();
+        counts.iter().map(|(_, count)| count).sum()
+    } else {
+        perft(&game, depth)
+    };
+    let elapsed = started.elapsed();
+    println!("Nodes: {}", nodes);
+    println!(
+        "Time: {} ms ({:.0} nodes/s)",
+        elapsed.as_millis(),
+        nodes as f64 / elapsed.as_secs_f64().max(0.001)
+    );
+    Ok(())
+}
+
+/*
+A PGN file gets every game reviewed and printed back annotated, anything else is taken as a
+FEN and analysed
+*/
+pub fn run_analyse(input: &str, depth: Option<u32>, lines: usize) -> io::Result<()> {
+    if !Path::new(input).is_file() {
+        let game = position(input)?;
+        let limits = Limits {
+            depth: Some(depth.unwrap_or(6).max(1)),
+            ..Limits::default()
+        };
+        let results = analyse(&game, &limits, lines.max(1), &mut |_| {});
+        let view = AnalysisView::new(&game, &results);
+        println!("depth {}, {} nodes", view.depth, view.nodes);
+        for line in &view.lines {
+            println!(
+                "{}. {} {}",
+                line.multipv,
+                score_text(&line.score),
+                line.pv.join(" ")
+            );
+        }
+        return Ok(());
+    }
+    let limits = Limits {
+        depth: Some(depth.unwrap_or(3).max(1)),
+        ..Limits::default()
+    };
+    for (number, game) in notation::parse_pgn(&std::fs::read_to_string(input)?)
+        .iter()
+        .enumerate()
+    {
+        let analysed = game
+            .start()
+            .and_then(|start| Ok((game.coordinate_moves(&start)?, start)));
+        let (moves, start) = match analysed {
+            Ok(analysed) => analysed,
+            Err(e) => {
+                eprintln!("skipping game {}: {}", number + 1, e);
+                continue;
+            }
+        };
+        let mut tags: Vec<(&str, String)> = game
+            .tags
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "Result" | "FEN" | "SetUp" | "Variant"))
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        tags.push(("Annotator", "built-in engine".to_string()));
+        let result = if game.result.is_empty() {
+            "*"
+        } else {
+            &game.result
+        };
+        print!(
+            "{}",
+            annotated_pgn(&review(&start, &moves, &limits), &tags, result)
+        );
+    }
+    Ok(())
+}
+
+/*
+A game of a PGN file as convert writes it
+*/
+#[derive(Serialize, Debug)]
+struct ConvertedGame {
+    tags: BTreeMap<String, String>,
+    start_fen: String,
+    moves: Vec<String>, // SAN
+    uci: Vec<String>,
+    result: String,
+    final_fen: String,
+}
+
+pub fn run_convert(input: &Path, output: Option<&Path>) -> io::Result<()> {
+    let mut converted: Vec<ConvertedGame> = Vec::new();
+    for (number, game) in notation::parse_pgn(&std::fs::read_to_string(input)?)
+        .iter()
+        .enumerate()
+    {
+        match convert_game(game) {
+            Ok(game) => converted.push(game),
+            Err(e) => eprintln!("skipping game {}: {}", number + 1, e),
+        }
+    }
+    let json = serde_json::to_string_pretty(&converted)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, json)?;
+            eprintln!("{} games written to {}", converted.len(), path.display());
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+fn convert_game(game: &PgnGame) -> Result<ConvertedGame, String> {
+    let start: Game = game.start()?;
+    let moves: Vec<String> = game.coordinate_moves(&start)?;
+    let end: Game = Game::replay(&start, &moves).ok_or("the moves don't replay")?;
+    Ok(ConvertedGame {
+        tags: game.tags.iter().cloned().collect(),
+        start_fen: start.to_fen(),
+        moves: san_moves_from(&start, &moves),
+        uci: end.history.iter().map(uci_move).collect(),
+        result: if game.result.is_empty() {
+            "*".to_string()
+        } else {
+            game.result.clone()
+        },
+        final_fen: end.to_fen(),
+    })
+}
+
+/*
+Prints the engine's line UCI style, so scripts can read the last line
+*/
+pub fn run_bestmove(fen: &str, depth: u32, movetime: Option<u64>) -> io::Result<()> {
+    let game = position(fen)?;
+    let limits = match movetime {
+        Some(ms) => Limits {
+            movetime: Some(Duration::from_millis(ms)),
+            ..Limits::default()
+        },
+        None => Limits {
+            depth: Some(depth.max(1)),
+            ..Limits::default()
+        },
+    };
+    let Some(found) = search(&game, &limits) else {
+        return Err(invalid("there's no legal move in that position"));
+    };
+    let score = match found.score {
+        Score::Cp(cp) => format!("cp {}", cp),
+        Score::Mate(moves) => format!("mate {}", moves),
+    };
+    let pv: Vec<String> = found.pv.iter().map(uci_move).collect();
+    println!(
+        "info depth {} score {} nodes {} pv {}",
+        found.depth,
+        score,
+        found.nodes,
+        pv.join(" ")
+    );
+    println!("bestmove {}", uci_move(&found.best));
+    Ok(())
+}
+
+/*
+Reads a match config (see README) and plays it out on the terminal
+*/
+pub fn run_match(path: &Path) -> io::Result<()> {
+    let config: engine_match::MatchConfig =
+        serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if let Some(paths) = &config.tablebases {
+        let tables = tablebase::load(paths)?;
+        println!(
+            "{} tablebase files, up to {} pieces",
+            tables.table_count(),
+            tables.max_pieces()
+        );
+    }
+    let report = engine_match::run_match(&config, &mut |game, report| {
+        println!("{}", game);
+        println!("{}", report);
+    })?;
+    println!("\n{}", report);
+    Ok(())
+}
+
+pub fn run_xboard(book: Option<&str>) -> io::Result<()> {
+    let book = book.map(book::Book::open).transpose()?;
+    xboard::run(io::stdin().lock(), io::stdout(), book)
+}
+
+pub fn run_book(pgn_path: &str, book_path: &str, min_count: u32, max_ply: usize) -> io::Result<()> {
+    let games = notation::parse_pgn(&std::fs::read_to_string(pgn_path)?);
+    let entries = book::build_book(&games, min_count, max_ply);
+    book::write_book(book_path, &entries)?;
+    println!(
+        "{} games, {} book entries written to {}",
+        games.len(),
+        entries.len(),
+        book_path
+    );
+    Ok(())
+}
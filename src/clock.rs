@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Clock {
+    pub white_ms: u64,
+    pub black_ms: u64,
+    pub increment_ms: u64,
+    // what each side started with, 0 for games saved before it was kept
+    #[serde(default)]
+    pub initial_ms: u64,
+    #[serde(skip)]
+    pub running_since: Option<Instant>,
+}
+impl Clock {
+    pub fn new(initial_ms: u64, increment_ms: u64) -> Clock {
+        Clock {
+            white_ms: initial_ms,
+            black_ms: initial_ms,
+            increment_ms,
+            initial_ms,
+            running_since: None,
+        }
+    }
+    /*
+    The time each side started with, the time control rather than what's left of it
+    */
+    pub fn base_ms(&self) -> u64 {
+        if self.initial_ms > 0 {
+            self.initial_ms
+        } else {
+            // older games only know the time left, the closest there is
+            self.white_ms.max(self.black_ms)
+        }
+    }
+    /*
+    Stops the clock of the side that just moved and starts the other one.
+    Returns false if the side that moved had already run out of time
+    */
+    pub fn press(&mut self, white_moved: bool) -> bool {
+        let spent: u64 = match self.running_since {
+            Some(since) => since.elapsed().as_millis() as u64,
+            None => 0,
+        };
+        let remaining: &mut u64 = if white_moved {
+            &mut self.white_ms
+        } else {
+            &mut self.black_ms
+        };
+        if spent >= *remaining {
+            *remaining = 0;
+            return false;
+        }
+        *remaining = *remaining - spent + self.increment_ms;
+        self.running_since = Some(Instant::now());
+        true
+    }
+    /*
+    Remaining time of each side as of right now, counting the running clock
+    */
+    pub fn remaining(&self, white_to_move: bool) -> (u64, u64) {
+        let spent: u64 = match self.running_since {
+            Some(since) => since.elapsed().as_millis() as u64,
+            None => 0,
+        };
+        if white_to_move {
+            (self.white_ms.saturating_sub(spent), self.black_ms)
+        } else {
+            (self.white_ms, self.black_ms.saturating_sub(spent))
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::ai::{search, Limits, Score};
+use crate::book::{Book, BookPick};
+use crate::error::Error;
+use crate::game::Game;
+use crate::moves::Move;
+use crate::skill::{Strength, Weakened};
+use crate::uci::UciEngine;
+use crate::variant::{Variant, STANDARD};
+
+// seats taken by an engine are named after it with this in front, player names can't have a ':'
+const SEAT_PREFIX: &str = "engine:";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct EngineConfig {
+    pub name: String,
+    pub command: Option<String>, // left out for the built-in engine
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub options: BTreeMap<String, String>, // sent as setoption before the first game
+    #[serde(skip)]
+    pub defaults: BTreeMap<String, String>, // from the server config, for options the engine has and options leaves out
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime_ms: Option<u64>,
+    pub book: Option<String>, // polyglot .bin, looked up before the engine is asked
+    #[serde(default)]
+    pub book_pick: BookPick,
+    #[serde(flatten)]
+    pub strength: Strength, // level, elo and seed, only for the built-in engine
+}
+impl EngineConfig {
+    pub fn builtin() -> EngineConfig {
+        EngineConfig {
+            name: "builtin".to_string(),
+            command: None,
+            args: Vec::new(),
+            options: BTreeMap::new(),
+            defaults: BTreeMap::new(),
+            depth: Some(3),
+            nodes: None,
+            movetime_ms: None,
+            book: None,
+            book_pick: BookPick::default(),
+            strength: Strength::default(),
+        }
+    }
+    pub fn limits(&self) -> Limits {
+        Limits {
+            depth: self.depth,
+            nodes: self.nodes,
+            movetime: self.movetime_ms.map(Duration::from_millis),
+            stop: None,
+        }
+    }
+    /*
+    Whether the strength settings make sense for this engine
+    */
+    pub fn check_strength(&self) -> Result<(), Error> {
+        if self.command.is_some() && self.strength.is_set() {
+            return Err(Error::Engine(
+                "skill levels are only for the built-in engine".to_string(),
+            ));
+        }
+        self.strength.check()
+    }
+}
+
+/*
+Engines the server can seat or ask for a move: the built-in one, then whatever is listed in
+the file at path (a JSON array of engine configs), if there is one. Entries without a command
+are more setups of the built-in engine
+*/
+pub fn load_engines(path: &str) -> io::Result<Vec<EngineConfig>> {
+    let mut engines = vec![EngineConfig::builtin()];
+    if !Path::new(path).exists() {
+        return Ok(engines);
+    }
+    let listed: Vec<EngineConfig> = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+    for engine in listed {
+        if let Err(e) = engine.check_strength() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: engine {:?}: {}", path, engine.name, e),
+            ));
+        }
+        if engines.iter().any(|e| e.name == engine.name) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: there's already an engine called {:?}",
+                    path, engine.name
+                ),
+            ));
+        }
+        engines.push(engine);
+    }
+    Ok(engines)
+}
+
+pub fn seat_name(engine: &str) -> String {
+    format!("{}{}", SEAT_PREFIX, engine)
+}
+
+/*
+Name of the engine sitting in a seat, None for people and empty seats
+*/
+pub fn engine_of_seat(seat: &str) -> Option<&str> {
+    seat.strip_prefix(SEAT_PREFIX)
+}
+
+/*
+What an engine came up with. Score is from the point of view of the side to move
+*/
+pub struct Thought {
+    pub best: Move,
+    pub score: Option<Score>,
+    pub depth: Option<u32>,
+    pub pv: Vec<Move>,
+}
+
+enum Brain {
+    BuiltIn(Option<Box<Weakened>>), // held back when it has a strength
+    Uci(Box<UciEngine>),
+}
+
+/*
+An engine ready to be asked for moves, ours or an external UCI one, with its opening book
+*/
+pub struct Computer {
+    brain: Brain,
+    limits: Limits,
+    book: Option<(Book, BookPick)>,
+    chess960: bool,        // whether a UCI engine was told to play Chess960
+    variant: &'static str, // the UCI_Variant it was told to play
+}
+impl Computer {
+    pub fn start(config: &EngineConfig) -> io::Result<Computer> {
+        config
+            .check_strength()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let book = match &config.book {
+            Some(path) => Some((Book::open(path)?, config.book_pick)),
+            None => None,
+        };
+        let brain = match &config.command {
+            Some(command) => {
+                let mut engine = UciEngine::start(command, &config.args)?;
+                for (name, value) in &config.options {
+                    engine.set_option(name, value)?;
+                }
+                for (name, value) in &config.defaults {
+                    let overridden = config.options.keys().any(|o| o.eq_ignore_ascii_case(name));
+                    if engine.has_option(name) && !overridden {
+                        engine.set_option(name, value)?;
+                    }
+                }
+                engine.is_ready()?;
+                Brain::Uci(Box::new(engine))
+            }
+            None if config.strength.is_set() => {
+                Brain::BuiltIn(Some(Box::new(Weakened::new(&config.strength))))
+            }
+            None => Brain::BuiltIn(None),
+        };
+        Ok(Computer {
+            brain,
+            limits: config.limits(),
+            book,
+            chess960: false,
+            variant: STANDARD.uci_name(),
+        })
+    }
+    pub fn new_game(&mut self) -> io::Result<()> {
+        match &mut self.brain {
+            Brain::BuiltIn(weak) => {
+                if let Some(weak) = weak {
+                    weak.restart();
+                }
+                Ok(())
+            }
+            Brain::Uci(engine) => engine.new_game(),
+        }
+    }
+    /*
+    Best move for game, which was set up from start_fen before its history was played.
+    movetime caps the engine's own limits, for games on the clock
+    */
+    pub fn think(
+        &mut self,
+        game: &Game,
+        start_fen: &str,
+        movetime: Option<Duration>,
+    ) -> io::Result<Thought> {
+        if let Some((book, pick)) = &self.book {
+            if let Some(mov) = book.pick(game, *pick, &mut rand::thread_rng()) {
+                return Ok(Thought {
+                    pv: vec![mov.clone()],
+                    best: mov,
+                    score: None,
+                    depth: None,
+                });
+            }
+        }
+        let mut limits = self.limits.clone();
+        if let Some(cap) = movetime {
+            limits.movetime = Some(limits.movetime.map_or(cap, |time| time.min(cap)));
+        }
+        match &mut self.brain {
+            Brain::BuiltIn(weak) => match weak
+                .as_mut()
+                .map_or_else(|| search(game, &limits), |weak| weak.search(game, &limits))
+            {
+                Some(result) => Ok(Thought {
+                    best: result.best,
+                    score: Some(result.score),
+                    depth: Some(result.depth),
+                    pv: result.pv,
+                }),
+                None => Err(io::Error::other("no legal move")),
+            },
+            Brain::Uci(engine) => {
+                if game.chess960 != self.chess960 {
+                    let value = if game.chess960 { "true" } else { "false" };
+                    engine.set_option("UCI_Chess960", value)?;
+                    self.chess960 = game.chess960;
+                }
+                if game.variant.uci_name() != self.variant {
+                    engine.set_option("UCI_Variant", game.variant.uci_name())?;
+                    self.variant = game.variant.uci_name();
+                }
+                engine.position(start_fen, &game.history)?;
+                let answer = engine.go(&limits)?;
+                let (depth, pv) = match answer.lines.first() {
+                    Some(info) => (
+                        info.depth,
+                        info.pv.iter().map_while(|m| Move::parse(m)).collect(),
+                    ),
+                    None => (None, Vec::new()),
+                };
+                Ok(Thought {
+                    score: answer.score(),
+                    best: answer.mov,
+                    depth,
+                    pv,
+                })
+            }
+        }
+    }
+}
+
+/*
+A computer sitting at a table, started the first time it has to move
+*/
+pub type ComputerSlot = Arc<Mutex<Option<Computer>>>;
+
+/*
+Asks the computer in slot for a move, starting it if needed. An engine that crashes or hangs
+gets restarted once before giving up
+*/
+pub fn play_turn(
+    slot: &ComputerSlot,
+    config: &EngineConfig,
+    game: &Game,
+    start_fen: &str,
+    movetime: Option<Duration>,
+) -> io::Result<Move> {
+    let mut slot = slot.lock().unwrap();
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let answer = match slot.as_mut() {
+            Some(computer) => computer.think(game, start_fen, movetime),
+            None => Computer::start(config).and_then(|mut computer| {
+                computer.new_game()?;
+                let answer = computer.think(game, start_fen, movetime);
+                *slot = Some(computer);
+                answer
+            }),
+        };
+        match answer {
+            Ok(thought) => return Ok(thought.best),
+            Err(e) if attempt < 2 => {
+                log::warn!("engine {} failed ({}), restarting it", config.name, e);
+                *slot = None;
+            }
+            Err(e) => {
+                *slot = None;
+                return Err(e);
+            }
+        }
+    }
+}
+
+/*
+How long to think with the given time left: a 30th of it plus most of the increment
+*/
+pub fn time_budget(remaining_ms: u64, increment_ms: u64) -> Duration {
+    Duration::from_millis((remaining_ms / 30 + increment_ms * 3 / 4).max(50))
+}
@@ -0,0 +1,472 @@
+use serde::Deserialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chess::clock::Clock;
+
+// read when serve isn't given a --config
+pub const DEFAULT_PATH: &str = "chess.toml";
+// environment variables overriding the file start with this, like CHESS_PORT
+const ENV_PREFIX: &str = "CHESS_";
+
+/*
+Server settings from chess.toml (see README), every part of it optional
+*/
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub games: GamesConfig,
+    pub engine: EngineDefaults,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub static_dir: PathBuf,
+    pub allowed_origins: Vec<String>, // "*" for any
+    pub tls: Option<TlsConfig>,
+}
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            static_dir: PathBuf::from("./ui"),
+            allowed_origins: vec!["*".to_string()],
+            tls: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: PathBuf, // PEM, the chain with the server's certificate first
+    pub key: PathBuf,  // PEM
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    File,
+    Memory, // nothing survives a restart
+}
+impl FromStr for Backend {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Backend, String> {
+        match text {
+            "file" => Ok(Backend::File),
+            "memory" => Ok(Backend::Memory),
+            _ => Err(format!("{:?} is not file or memory", text)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: Backend,
+    pub path: PathBuf, // where the file backend keeps its files
+}
+impl Default for StorageConfig {
+    fn default() -> StorageConfig {
+        StorageConfig {
+            backend: Backend::File,
+            path: PathBuf::from("./games"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamesConfig {
+    pub default_minutes: Option<u64>, // for new games that don't ask for a clock
+    pub default_increment: Option<u64>,
+    pub max_concurrent: Option<usize>, // games being played at once
+}
+impl GamesConfig {
+    /*
+    The clock a new game asked for, or the default one when it didn't ask
+    */
+    pub fn clock(&self, minutes: Option<u64>, increment: Option<u64>) -> Option<Clock> {
+        let (minutes, increment) = match minutes {
+            Some(minutes) => (minutes, increment),
+            None => (self.default_minutes?, self.default_increment),
+        };
+        Some(Clock::new(minutes * 60_000, increment.unwrap_or(0) * 1000))
+    }
+}
+
+/*
+Threads and Hash for UCI engines that have them and don't set them in engines.json
+*/
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineDefaults {
+    pub threads: u32,
+    pub hash_mb: u32,
+}
+impl Default for EngineDefaults {
+    fn default() -> EngineDefaults {
+        EngineDefaults {
+            threads: 1,
+            hash_mb: 16,
+        }
+    }
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| format!("{}{}={:?}: {}", ENV_PREFIX, name, value, e))
+}
+
+impl Config {
+    /*
+    Reads path, or chess.toml when there's no path and it exists, then lets the environment
+    override it. Nothing is checked yet, see validate
+    */
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let path: Option<&Path> = match path {
+            Some(path) => Some(path),
+            None => Some(Path::new(DEFAULT_PATH)).filter(|p| p.exists()),
+        };
+        let mut config: Config = match path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+                toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            None => Config::default(),
+        };
+        config.apply_env(|name| std::env::var(format!("{}{}", ENV_PREFIX, name)).ok())?;
+        Ok(config)
+    }
+    /*
+    Overrides what var has a value for, var("PORT") standing for CHESS_PORT
+    */
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        if let Some(host) = var("HOST") {
+            self.server.host = host;
+        }
+        if let Some(port) = var("PORT") {
+            self.server.port = parse("PORT", &port)?;
+        }
+        if let Some(dir) = var("STATIC_DIR") {
+            self.server.static_dir = PathBuf::from(dir);
+        }
+        if let Some(origins) = var("ALLOWED_ORIGINS") {
+            // comma separated
+            self.server.allowed_origins = origins
+                .split(',')
+                .map(|o| o.trim().to_string())
+                .filter(|o| !o.is_empty())
+                .collect();
+        }
+        match (var("TLS_CERT"), var("TLS_KEY")) {
+            (None, None) => {}
+            (cert, key) => {
+                let tls = self.server.tls.clone();
+                self.server.tls = Some(TlsConfig {
+                    cert: cert
+                        .map(PathBuf::from)
+                        .or(tls.as_ref().map(|t| t.cert.clone()))
+                        .unwrap_or_default(),
+                    key: key
+                        .map(PathBuf::from)
+                        .or(tls.map(|t| t.key))
+                        .unwrap_or_default(),
+                });
+            }
+        }
+        if let Some(backend) = var("STORAGE_BACKEND") {
+            self.storage.backend = parse("STORAGE_BACKEND", &backend)?;
+        }
+        if let Some(path) = var("STORAGE_PATH") {
+            self.storage.path = PathBuf::from(path);
+        }
+        if let Some(minutes) = var("DEFAULT_MINUTES") {
+            self.games.default_minutes = Some(parse("DEFAULT_MINUTES", &minutes)?);
+        }
+        if let Some(increment) = var("DEFAULT_INCREMENT") {
+            self.games.default_increment = Some(parse("DEFAULT_INCREMENT", &increment)?);
+        }
+        if let Some(max) = var("MAX_CONCURRENT_GAMES") {
+            self.games.max_concurrent = Some(parse("MAX_CONCURRENT_GAMES", &max)?);
+        }
+        if let Some(threads) = var("ENGINE_THREADS") {
+            self.engine.threads = parse("ENGINE_THREADS", &threads)?;
+        }
+        if let Some(hash) = var("ENGINE_HASH_MB") {
+            self.engine.hash_mb = parse("ENGINE_HASH_MB", &hash)?;
+        }
+        Ok(())
+    }
+    /*
+    Everything wrong with the settings, one problem a line
+    */
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems: Vec<String> = Vec::new();
+        let server = &self.server;
+        if server.host.trim().is_empty() {
+            problems.push("server.host can't be empty".to_string());
+        }
+        if server.port == 0 {
+            problems.push("server.port has to be between 1 and 65535".to_string());
+        }
+        if !server.static_dir.is_dir() {
+            problems.push(format!(
+                "server.static_dir {} isn't a directory",
+                server.static_dir.display()
+            ));
+        }
+        if server.allowed_origins.is_empty() {
+            problems.push(
+                "server.allowed_origins needs at least one origin, or \"*\" for any".to_string(),
+            );
+        }
+        let any = server.allowed_origins.iter().any(|o| o == "*");
+        if any && server.allowed_origins.len() > 1 {
+            problems
+                .push("server.allowed_origins can't have \"*\" next to other origins".to_string());
+        }
+        for origin in server.allowed_origins.iter().filter(|o| *o != "*") {
+            if !origin.starts_with("http://") && !origin.starts_with("https://") {
+                problems.push(format!(
+                    "server.allowed_origins: {:?} should look like https://example.com",
+                    origin
+                ));
+            }
+        }
+        if let Some(tls) = &server.tls {
+            for (name, path) in [("cert", &tls.cert), ("key", &tls.key)] {
+                if path.as_os_str().is_empty() {
+                    problems.push(format!("server.tls.{} is missing", name));
+                } else if !path.is_file() {
+                    problems.push(format!(
+                        "server.tls.{} {} isn't a file",
+                        name,
+                        path.display()
+                    ));
+                }
+            }
+        }
+        if self.storage.backend == Backend::File && self.storage.path.as_os_str().is_empty() {
+            problems.push("storage.path can't be empty with the file backend".to_string());
+        }
+        let games = &self.games;
+        if games.default_minutes == Some(0) {
+            problems.push(
+                "games.default_minutes has to be at least 1, leave it out for untimed games"
+                    .to_string(),
+            );
+        }
+        if games.default_increment.is_some() && games.default_minutes.is_none() {
+            problems.push("games.default_increment needs games.default_minutes".to_string());
+        }
+        if games.max_concurrent == Some(0) {
+            problems.push(
+                "games.max_concurrent has to be at least 1, leave it out for no limit".to_string(),
+            );
+        }
+        if !(1..=1024).contains(&self.engine.threads) {
+            problems.push("engine.threads has to be between 1 and 1024".to_string());
+        }
+        if !(1..=65536).contains(&self.engine.hash_mb) {
+            problems.push("engine.hash_mb has to be between 1 and 65536".to_string());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+}
+
+/*
+The certificate chain and key for HTTPS
+*/
+pub fn load_tls(tls: &TlsConfig) -> Result<rustls::ServerConfig, String> {
+    let open = |path: &Path| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| format!("can't read {}: {}", path.display(), e))
+    };
+    let certs = rustls_pemfile::certs(&mut open(&tls.cert)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{}: {}", tls.cert.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("{} has no certificates", tls.cert.display()));
+    }
+    let key = rustls_pemfile::private_key(&mut open(&tls.key)?)
+        .map_err(|e| format!("{}: {}", tls.key.display(), e))?
+        .ok_or_else(|| format!("{} has no private key", tls.key.display()))?;
+    rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| {
+            format!(
+                "{} and {} don't go together: {}",
+                tls.cert.display(),
+                tls.key.display(),
+                e
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /*
+    The environment as a table, names without the CHESS_ prefix
+    */
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name: &str| vars.get(name).cloned()
+    }
+
+    // settings validate is happy with, wherever the tests run from
+    fn valid() -> Config {
+        let mut config = Config::default();
+        config.server.static_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        config
+    }
+
+    fn problems(config: &Config) -> String {
+        config.validate().unwrap_err()
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let mut config = valid();
+        config
+            .apply_env(env(&[
+                ("HOST", "0.0.0.0"),
+                ("PORT", " 9000 "),
+                ("STORAGE_BACKEND", "memory"),
+                ("DEFAULT_MINUTES", "5"),
+            ]))
+            .unwrap();
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.storage.backend, Backend::Memory);
+        assert_eq!(config.games.default_minutes, Some(5));
+        // what the environment doesn't mention stays
+        assert_eq!(config.engine.threads, 1);
+        assert!(config.server.tls.is_none());
+    }
+
+    #[test]
+    fn bad_numbers_in_the_environment() {
+        for (name, value) in [
+            ("PORT", "http"),
+            ("PORT", "70000"),
+            ("ENGINE_THREADS", "-1"),
+        ] {
+            let error = valid().apply_env(env(&[(name, value)])).unwrap_err();
+            assert!(error.starts_with(&format!("CHESS_{}=", name)), "{}", error);
+        }
+        let error = valid()
+            .apply_env(env(&[("STORAGE_BACKEND", "redis")]))
+            .unwrap_err();
+        assert!(error.contains("is not file or memory"), "{}", error);
+    }
+
+    #[test]
+    fn origins_are_split_on_commas() {
+        let mut config = valid();
+        config
+            .apply_env(env(&[(
+                "ALLOWED_ORIGINS",
+                "https://a.example, https://b.example,,",
+            )]))
+            .unwrap();
+        assert_eq!(
+            config.server.allowed_origins,
+            ["https://a.example", "https://b.example"]
+        );
+    }
+
+    #[test]
+    fn tls_from_the_environment_merges_with_the_file() {
+        let mut config = valid();
+        config.server.tls = Some(TlsConfig {
+            cert: PathBuf::from("file.crt"),
+            key: PathBuf::from("file.key"),
+        });
+        config.apply_env(env(&[("TLS_KEY", "env.key")])).unwrap();
+        let tls = config.server.tls.as_ref().unwrap();
+        assert_eq!(
+            (tls.cert.as_path(), tls.key.as_path()),
+            (Path::new("file.crt"), Path::new("env.key"))
+        );
+        // half of it with nothing in the file is caught by validate
+        let mut config = valid();
+        config.apply_env(env(&[("TLS_CERT", "env.crt")])).unwrap();
+        assert_eq!(config.server.tls.as_ref().unwrap().key, PathBuf::new());
+        assert!(problems(&config).contains("server.tls.key is missing"));
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert_eq!(valid().validate(), Ok(()));
+    }
+
+    #[test]
+    fn any_origin_stands_alone() {
+        let mut config = valid();
+        config.server.allowed_origins = vec!["*".to_string(), "https://a.example".to_string()];
+        assert!(problems(&config).contains("can't have \"*\" next to other origins"));
+        config.server.allowed_origins = vec!["a.example".to_string()];
+        assert!(problems(&config).contains("should look like https://example.com"));
+        config.server.allowed_origins.clear();
+        assert!(problems(&config).contains("needs at least one origin"));
+    }
+
+    #[test]
+    fn zero_limits_are_rejected() {
+        let mut config = valid();
+        config.server.port = 0;
+        config.games.default_minutes = Some(0);
+        config.games.max_concurrent = Some(0);
+        config.engine.threads = 0;
+        config.engine.hash_mb = 0;
+        let problems = problems(&config);
+        // every one of them, a line each
+        assert_eq!(problems.lines().count(), 5, "{}", problems);
+        assert!(problems.contains("server.port"));
+        assert!(problems.contains("games.default_minutes"));
+        assert!(problems.contains("games.max_concurrent"));
+        assert!(problems.contains("engine.threads"));
+        assert!(problems.contains("engine.hash_mb"));
+    }
+
+    #[test]
+    fn increment_needs_minutes() {
+        let mut config = valid();
+        config.games.default_increment = Some(2);
+        assert_eq!(
+            config.validate(),
+            Err("games.default_increment needs games.default_minutes".to_string())
+        );
+        config.games.default_minutes = Some(3);
+        assert_eq!(config.validate(), Ok(()));
+        let clock = config.games.clock(None, None).unwrap();
+        assert_eq!((clock.white_ms, clock.increment_ms), (180_000, 2000));
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use crate::aux_func::int_to_letter;
+use crate::error::Error;
+use crate::game::Game;
+use crate::moves::piece_letter;
+use crate::piece::Piece;
+use crate::position::Position;
+use crate::setup;
+
+/*
+A position being set up in the board editor. Anything goes while it's edited, it only has to
+make sense once a game is started from it
+*/
+pub struct Draft {
+    pub id: u64,
+    pub pieces: HashMap<(char, i32), Piece>,
+    pub white_to_move: bool,
+    pub castling: String, // FEN castling letters, "KQkq" or files like "HAha"
+    pub en_passant: Option<Position>,
+}
+impl Draft {
+    /*
+    A draft of game's current position
+    */
+    pub fn from_game(id: u64, game: &Game) -> Draft {
+        let fen = game.to_fen();
+        let castling = fen.split(' ').nth(2).unwrap_or("-");
+        Draft {
+            id,
+            pieces: game.pieces.clone(),
+            white_to_move: game.white_to_move,
+            castling: castling.replace('-', ""),
+            en_passant: game.en_passant.clone(),
+        }
+    }
+    /*
+    Puts the piece name of that color on square, replacing whatever was there
+    */
+    pub fn place(&mut self, square: Position, name: &str, white: bool) -> Result<(), Error> {
+        let piece = Piece::new(name, white, square.clone())
+            .ok_or_else(|| Error::Setup(format!("{:?} is not a piece", name)))?;
+        self.pieces.insert((square.column, square.row), piece);
+        Ok(())
+    }
+    pub fn remove(&mut self, square: &Position) {
+        self.pieces.remove(&(square.column, square.row));
+    }
+    /*
+    Empties the board, castling and en passant go with the pieces
+    */
+    pub fn clear(&mut self) {
+        self.pieces.clear();
+        self.castling.clear();
+        self.en_passant = None;
+    }
+    pub fn flip_side(&mut self) {
+        self.white_to_move = !self.white_to_move;
+        // it was the other side's pawn that could be taken
+        self.en_passant = None;
+    }
+    /*
+    Adds the castling right letter if it's missing and takes it away if it's there
+    */
+    pub fn toggle_castling(&mut self, letter: char) -> Result<(), Error> {
+        let valid = "KQkq".contains(letter) || ('A'..='H').contains(&letter.to_ascii_uppercase());
+        if !valid {
+            return Err(Error::Setup(format!(
+                "{:?} is not a castling right, they're K, Q, k, q or a rook's file",
+                letter
+            )));
+        }
+        if self.castling.contains(letter) {
+            self.castling = self.castling.replace(letter, "");
+        } else {
+            self.castling.push(letter);
+            // FEN lists white's rights first, each side's short one before its long one
+            let mut letters: Vec<char> = self.castling.chars().collect();
+            letters.sort_by_key(|c| (c.is_ascii_lowercase(), "KQkq".find(*c).unwrap_or(0)));
+            self.castling = letters.into_iter().collect();
+        }
+        Ok(())
+    }
+    /*
+    The position in FEN, even when it isn't a legal one
+    */
+    pub fn fen(&self) -> String {
+        let mut board: Vec<String> = Vec::new();
+        for row in (1..=8).rev() {
+            let mut rank = String::new();
+            let mut empty = 0;
+            for column in 1..=8 {
+                match self.pieces.get(&(int_to_letter(column), row)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            rank.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let letter = piece_letter(&piece.name);
+                        rank.push(if piece.white {
+                            letter
+                        } else {
+                            letter.to_ascii_lowercase()
+                        });
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+            }
+            board.push(rank);
+        }
+        format!(
+            "{} {} {} {} 0 1",
+            board.join("/"),
+            if self.white_to_move { "w" } else { "b" },
+            if self.castling.is_empty() {
+                "-"
+            } else {
+                &self.castling
+            },
+            self.en_passant
+                .as_ref()
+                .map_or("-".to_string(), |s| s.to_string().to_lowercase())
+        )
+    }
+    /*
+    The game the draft sets up, or what keeps it from being one
+    */
+    pub fn game(&self) -> Result<Game, Error> {
+        setup::from_fen(&self.fen())
+    }
+}
@@ -0,0 +1,449 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+
+use chess::computer::{Computer, EngineConfig};
+use chess::fen::START_FEN;
+use chess::game::{Game, Termination};
+use chess::notation::write_pgn;
+use chess::tablebase;
+
+/*
+When a game is called before it's over. Scores are in centipawns from white's point of view
+and have to come from both engines for the given number of moves in a row
+*/
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Adjudication {
+    pub resign_score: Option<i32>,
+    pub resign_moves: u32,
+    pub draw_score: Option<i32>,
+    pub draw_moves: u32,
+    pub draw_after: u32, // move number before which draws aren't adjudicated
+    pub max_moves: Option<u32>,
+}
+impl Default for Adjudication {
+    fn default() -> Adjudication {
+        Adjudication {
+            resign_score: None,
+            resign_moves: 3,
+            draw_score: None,
+            draw_moves: 8,
+            draw_after: 40,
+            max_moves: None,
+        }
+    }
+}
+
+/*
+Sequential probability ratio test between H0: the first engine is elo0 stronger, and H1: elo1
+*/
+#[derive(Deserialize, Debug, Clone)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    #[serde(default = "default_error_rate")]
+    pub alpha: f64,
+    #[serde(default = "default_error_rate")]
+    pub beta: f64,
+}
+fn default_error_rate() -> f64 {
+    0.05
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MatchConfig {
+    pub engines: Vec<EngineConfig>, // two of them, results are from the first one's side
+    pub games: u32,
+    pub openings: Option<String>, // file with a FEN or EPD per line, every one played with both colors
+    #[serde(default)]
+    pub adjudication: Adjudication,
+    pub sprt: Option<Sprt>,
+    pub pgn: Option<String>, // games are appended here as they finish
+    pub event: Option<String>,
+    pub tablebases: Option<String>, // Syzygy directories, for adjudicating and the built-in engine
+}
+
+/*
+Openings from a file of FEN or EPD lines. EPD operations after the fourth field are dropped,
+blank lines and lines starting with # are skipped
+*/
+pub fn read_openings(path: &str) -> io::Result<Vec<String>> {
+    let mut openings: Vec<String> = Vec::new();
+    for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let counters = fields.len() >= 6
+            && fields[4].parse::<u32>().is_ok()
+            && fields[5].parse::<u32>().is_ok();
+        let fen = fields[..if counters { 6 } else { fields.len().min(4) }].join(" ");
+        if let Err(e) = Game::from_fen(&fen) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} line {}: {}", path, number + 1, e),
+            ));
+        }
+        openings.push(fen);
+    }
+    Ok(openings)
+}
+
+struct Outcome {
+    result: &'static str,
+    reason: String,
+    moves: Vec<String>,
+}
+
+fn termination_reason(termination: Termination) -> &'static str {
+    match termination {
+        Termination::Checkmate => "checkmate",
+        Termination::Stalemate => "stalemate",
+        Termination::InsufficientMaterial => "insufficient material",
+        Termination::Timeout => "timeout",
+        Termination::Resignation => "resignation",
+        Termination::Abandoned => "abandoned",
+        Termination::Tablebase => "tablebase",
+        Termination::KingOfTheHill => "king of the hill",
+        Termination::ThreeChecks => "three checks",
+        Termination::Explosion => "explosion",
+    }
+}
+
+/*
+Result the recent scores agree on, if the rules let the game be called
+*/
+fn adjudicate(scores: &[Option<i32>], game: &Game, rules: &Adjudication) -> Option<&'static str> {
+    let last = |plies: usize| -> Option<Vec<i32>> {
+        if plies == 0 || scores.len() < plies {
+            return None;
+        }
+        scores[scores.len() - plies..].iter().copied().collect()
+    };
+    if let (Some(limit), Some(recent)) = (rules.resign_score, last(2 * rules.resign_moves as usize))
+    {
+        if recent.iter().all(|&s| s >= limit) {
+            return Some("1-0");
+        }
+        if recent.iter().all(|&s| s <= -limit) {
+            return Some("0-1");
+        }
+    }
+    if let (Some(limit), Some(recent)) = (rules.draw_score, last(2 * rules.draw_moves as usize)) {
+        if game.fullmove_number >= rules.draw_after && recent.iter().all(|&s| s.abs() <= limit) {
+            return Some("1/2-1/2");
+        }
+    }
+    None
+}
+
+fn play_game(
+    white: &mut Computer,
+    black: &mut Computer,
+    start_fen: &str,
+    rules: &Adjudication,
+) -> Outcome {
+    let mut game: Game = Game::from_fen(start_fen).unwrap();
+    let mut moves: Vec<String> = Vec::new();
+    let mut scores: Vec<Option<i32>> = Vec::new();
+    let mut seen: HashMap<String, u32> = HashMap::new();
+    let outcome = |result: &'static str, reason: String, moves: Vec<String>| Outcome {
+        result,
+        reason,
+        moves,
+    };
+    for (contestant, loss) in [(&mut *white, "0-1"), (&mut *black, "1-0")] {
+        if let Err(e) = contestant.new_game() {
+            return outcome(loss, format!("engine error: {}", e), moves);
+        }
+    }
+    loop {
+        *seen.entry(game.position_key()).or_insert(0) += 1;
+        if let Some((result, termination)) = game.result() {
+            let result = match result.as_str() {
+                "1-0" => "1-0",
+                "0-1" => "0-1",
+                _ => "1/2-1/2",
+            };
+            return outcome(result, termination_reason(termination).to_string(), moves);
+        }
+        if game.halfmove_clock >= 100 {
+            return outcome("1/2-1/2", "fifty move rule".to_string(), moves);
+        }
+        if seen[&game.position_key()] >= 3 {
+            return outcome("1/2-1/2", "threefold repetition".to_string(), moves);
+        }
+        if rules
+            .max_moves
+            .is_some_and(|max| moves.len() as u32 >= 2 * max)
+        {
+            return outcome("1/2-1/2", "move limit".to_string(), moves);
+        }
+        if let Some(result) = tablebase::adjudicate(&game) {
+            return outcome(result, "tablebase".to_string(), moves);
+        }
+        if let Some(result) = adjudicate(&scores, &game, rules) {
+            return outcome(result, "adjudication".to_string(), moves);
+        }
+        let white_to_move = game.white_to_move;
+        let (mover, loss) = if white_to_move {
+            (&mut *white, "0-1")
+        } else {
+            (&mut *black, "1-0")
+        };
+        let (mov, score) = match mover.think(&game, start_fen, None) {
+            Ok(thought) => (thought.best, thought.score),
+            Err(e) => return outcome(loss, format!("engine error: {}", e), moves),
+        };
+        if let Err(e) = game.play(&mov) {
+            return outcome(loss, format!("illegal move {}, {}", mov, e), moves);
+        }
+        moves.push(game.history.last().unwrap().to_string());
+        let score = if white_to_move {
+            score
+        } else {
+            score.map(|s| s.negate())
+        };
+        scores.push(score.map(|s| s.centipawns()));
+    }
+}
+
+/*
+A finished game of the match, numbered from 1
+*/
+pub struct PlayedGame {
+    pub number: u32,
+    pub white: String,
+    pub black: String,
+    pub result: &'static str,
+    pub reason: String,
+}
+impl fmt::Display for PlayedGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Game {}: {} - {} {} ({})",
+            self.number, self.white, self.black, self.result, self.reason
+        )
+    }
+}
+
+/*
+Wins, draws and losses of the first engine, with what they say about the Elo difference
+*/
+#[derive(Debug, Default)]
+pub struct MatchReport {
+    pub first: String,
+    pub second: String,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub llr: Option<f64>,
+    pub sprt_bounds: Option<(f64, f64)>,
+}
+
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn elo_of(score: f64) -> f64 {
+    let score = score.clamp(0.0001, 0.9999);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+impl MatchReport {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+    /*
+    Mean score per game and its variance
+    */
+    fn score_stats(&self) -> Option<(f64, f64)> {
+        let n = self.games() as f64;
+        if n == 0.0 {
+            return None;
+        }
+        let (w, d) = (self.wins as f64 / n, self.draws as f64 / n);
+        let score = w + d / 2.0;
+        Some((score, w + d / 4.0 - score * score))
+    }
+    /*
+    Elo difference and the half width of its 95% confidence interval
+    */
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let (score, variance) = self.score_stats()?;
+        let margin = 1.96 * (variance / self.games() as f64).sqrt();
+        let low = elo_of(score - margin);
+        let high = elo_of(score + margin);
+        Some((elo_of(score), (high - low) / 2.0))
+    }
+    /*
+    Log likelihood ratio of the SPRT, using the normal approximation of the trinomial model
+    */
+    fn update_sprt(&mut self, sprt: &Sprt) {
+        self.sprt_bounds = Some((
+            (sprt.beta / (1.0 - sprt.alpha)).ln(),
+            ((1.0 - sprt.beta) / sprt.alpha).ln(),
+        ));
+        self.llr = self.score_stats().and_then(|(score, variance)| {
+            if variance <= 0.0 {
+                return None;
+            }
+            let (s0, s1) = (expected_score(sprt.elo0), expected_score(sprt.elo1));
+            Some(self.games() as f64 * (s1 - s0) * (2.0 * score - s0 - s1) / (2.0 * variance))
+        });
+    }
+    /*
+    "H0" or "H1" once the test has decided
+    */
+    pub fn sprt_decision(&self) -> Option<&'static str> {
+        let (llr, (lower, upper)) = (self.llr?, self.sprt_bounds?);
+        if llr >= upper {
+            Some("H1")
+        } else if llr <= lower {
+            Some("H0")
+        } else {
+            None
+        }
+    }
+}
+impl fmt::Display for MatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let points = self.wins as f64 + self.draws as f64 / 2.0;
+        write!(
+            f,
+            "Score of {} vs {}: {} - {} - {} [{:.3}] {}",
+            self.first,
+            self.second,
+            self.wins,
+            self.losses,
+            self.draws,
+            if self.games() > 0 {
+                points / self.games() as f64
+            } else {
+                0.0
+            },
+            self.games()
+        )?;
+        if let Some((elo, margin)) = self.elo() {
+            write!(f, "\nElo difference: {:.1} +/- {:.1}", elo, margin)?;
+        }
+        if let (Some(llr), Some((lower, upper))) = (self.llr, self.sprt_bounds) {
+            write!(f, "\nSPRT: llr {:.2} ({:.2}, {:.2})", llr, lower, upper)?;
+            match self.sprt_decision() {
+                Some(hypothesis) => write!(f, " - {} accepted", hypothesis)?,
+                None => write!(f, " - no decision yet")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/*
+Plays the match game by game, handing each game to played with the score so far as it ends,
+and stops early when the SPRT decides. Tablebases in the config have to be loaded already
+*/
+pub fn run_match(
+    config: &MatchConfig,
+    played: &mut dyn FnMut(&PlayedGame, &MatchReport),
+) -> io::Result<MatchReport> {
+    if config.engines.len() != 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a match needs exactly two engines",
+        ));
+    }
+    let openings: Vec<String> = match &config.openings {
+        Some(path) => read_openings(path)?,
+        None => Vec::new(),
+    };
+    let openings: Vec<String> = if openings.is_empty() {
+        vec![START_FEN.to_string()]
+    } else {
+        openings
+    };
+    let mut contestants: Vec<Computer> = config
+        .engines
+        .iter()
+        .map(Computer::start)
+        .collect::<io::Result<_>>()?;
+    let mut pgn_file = match &config.pgn {
+        Some(path) => Some(
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        ),
+        None => None,
+    };
+    let names = (&config.engines[0].name, &config.engines[1].name);
+    let mut report = MatchReport {
+        first: names.0.clone(),
+        second: names.1.clone(),
+        ..MatchReport::default()
+    };
+    for number in 0..config.games {
+        // each opening is played twice, the engines swapping colors in between
+        let fen = &openings[(number as usize / 2) % openings.len()];
+        let first_is_white = number % 2 == 0;
+        let (first, second) = contestants.split_at_mut(1);
+        let (white, black) = if first_is_white {
+            (&mut first[0], &mut second[0])
+        } else {
+            (&mut second[0], &mut first[0])
+        };
+        let outcome = play_game(white, black, fen, &config.adjudication);
+        let (white_name, black_name) = if first_is_white {
+            names
+        } else {
+            (names.1, names.0)
+        };
+        match (outcome.result, first_is_white) {
+            ("1-0", true) | ("0-1", false) => report.wins += 1,
+            ("1-0", false) | ("0-1", true) => report.losses += 1,
+            _ => report.draws += 1,
+        }
+        if let Some(file) = pgn_file.as_mut() {
+            let start: Game = Game::from_fen(fen).unwrap();
+            let text = write_pgn(
+                &[
+                    (
+                        "Event",
+                        config.event.clone().unwrap_or("Engine match".to_string()),
+                    ),
+                    ("Site", "?".to_string()),
+                    ("Date", chrono::Utc::now().format("%Y.%m.%d").to_string()),
+                    ("Round", (number + 1).to_string()),
+                    ("White", white_name.clone()),
+                    ("Black", black_name.clone()),
+                    ("Termination", outcome.reason.clone()),
+                ],
+                &start,
+                &outcome.moves,
+                outcome.result,
+            );
+            file.write_all(text.as_bytes())?;
+        }
+        if let Some(sprt) = &config.sprt {
+            report.update_sprt(sprt);
+        }
+        played(
+            &PlayedGame {
+                number: number + 1,
+                white: white_name.clone(),
+                black: black_name.clone(),
+                result: outcome.result,
+                reason: outcome.reason,
+            },
+            &report,
+        );
+        if report.sprt_decision().is_some() {
+            break;
+        }
+    }
+    Ok(report)
+}
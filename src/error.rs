@@ -0,0 +1,98 @@
+use std::fmt;
+
+/*
+What goes wrong when reading positions and games or setting things up. Every variant carries
+a message for people, which is also what Display prints
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Fen(String),      // text that doesn't read as FEN
+    Position(String), // a board that reads fine but can't be played from
+    Pgn(String),      // a game whose moves don't replay
+    Setup(String),    // an odds game, Chess960 number or edit that doesn't exist
+    Engine(String),   // engine settings that don't make sense
+}
+impl Error {
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Fen(message)
+            | Error::Position(message)
+            | Error::Pgn(message)
+            | Error::Setup(message)
+            | Error::Engine(message) => message,
+        }
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+impl std::error::Error for Error {}
+impl From<Error> for String {
+    fn from(error: Error) -> String {
+        error.message().to_string()
+    }
+}
+
+/*
+Why Game::play turned a move down
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IllegalMove {
+    NoPiece,              // nothing on the square the move starts from
+    NotYourTurn,          // the piece belongs to the side that isn't moving
+    CantMoveThere,        // the piece doesn't move like that, or something is in the way
+    CantCastle,           // the king or rook moved already, or the king would cross a check
+    LeavesKingInCheck,    // or whatever else the variant rules out, like blowing up your own king
+    BadPromotion(String), // not something a pawn can become
+    NoDrops,              // the variant doesn't have pockets
+    NotInPocket(String),
+    SquareTaken,    // drops go on empty squares
+    PawnOnBackRank, // pawns can't be dropped on the first or last rank
+}
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllegalMove::NoPiece => write!(f, "there's no piece on that square"),
+            IllegalMove::NotYourTurn => write!(f, "it's the other side's move"),
+            IllegalMove::CantMoveThere => write!(f, "the piece can't move there"),
+            IllegalMove::CantCastle => write!(
+                f,
+                "castling isn't possible, the king or rook has moved, something is in the way \
+                 or the king would cross an attacked square"
+            ),
+            IllegalMove::LeavesKingInCheck => write!(f, "it would leave the king in check"),
+            IllegalMove::BadPromotion(name) => {
+                write!(f, "a pawn can't become a {}", name)
+            }
+            IllegalMove::NoDrops => write!(f, "pieces can only be dropped in crazyhouse"),
+            IllegalMove::NotInPocket(name) => write!(f, "there's no {} in the pocket", name),
+            IllegalMove::SquareTaken => write!(f, "pieces can only be dropped on empty squares"),
+            IllegalMove::PawnOnBackRank => {
+                write!(f, "pawns can't be dropped on the first or last rank")
+            }
+        }
+    }
+}
+impl std::error::Error for IllegalMove {}
+
+/*
+Why the tablebases had no answer for a position
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeError {
+    NotCovered,      // too many pieces, castling rights or not normal chess
+    Missing(String), // the table the position needs isn't among the files, by name
+    Broken(String),  // the file is there but can't be read as a table
+}
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::NotCovered => write!(f, "the tablebases don't cover this position"),
+            ProbeError::Missing(name) => write!(f, "{} is missing from the tablebases", name),
+            ProbeError::Broken(problem) => write!(f, "{}", problem),
+        }
+    }
+}
+impl std::error::Error for ProbeError {}
@@ -0,0 +1,347 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::aux_func::{int_to_letter, letter_to_int};
+use crate::error::Error;
+use crate::game::Game;
+use crate::moves::{self, piece_name};
+use crate::piece::Piece;
+use crate::position::Position;
+use crate::variant::STANDARD;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// how crazyhouse pockets are written, biggest pieces first
+pub const POCKET_ORDER: [&str; 5] = ["queen", "rook", "bishop", "knight", "pawn"];
+
+fn piece_letter(piece: &Piece) -> char {
+    let letter = match piece.name.as_str() {
+        "pawn" => 'p',
+        "knight" => 'n',
+        "bishop" => 'b',
+        "rook" => 'r',
+        "queen" => 'q',
+        _ => 'k',
+    };
+    if piece.white {
+        letter.to_ascii_uppercase()
+    } else {
+        letter
+    }
+}
+
+impl Game {
+    /*
+    Reads a position in Forsyth-Edwards notation. The move counters can be left out, like EPD
+    does. Castling rights that don't fit the board (king or rook not at home) are dropped.
+    Castling can also be given X-FEN or Shredder-FEN style for Chess960, which the game becomes
+    when rooks are named by their file or king and rooks don't start where they usually do.
+    Three-check games can end in the checks given so far, lichess style: "+1+0". Crazyhouse
+    pockets go in brackets after the board, "[Qp]", and promoted pieces get a '~' after them
+    */
+    pub fn from_fen(fen: &str) -> Result<Game, Error> {
+        Game::read_fen(fen).map_err(Error::Fen)
+    }
+    fn read_fen(fen: &str) -> Result<Game, String> {
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        let mut checks: (u32, u32) = (0, 0);
+        if fields.len() > 4 && fields[fields.len() - 1].starts_with('+') {
+            let text = fields.pop().unwrap_or_default();
+            let counts: Vec<Option<u32>> = text[1..].split('+').map(|n| n.parse().ok()).collect();
+            let [Some(white), Some(black)] = counts[..] else {
+                return Err(format!("{:?} is not a count of checks like +1+0", text));
+            };
+            checks = (white, black);
+        }
+        if fields.len() < 4 {
+            return Err(format!(
+                "a FEN needs at least 4 fields, {:?} has {}",
+                fen,
+                fields.len()
+            ));
+        }
+        let (board, pockets): (&str, Option<&str>) = match fields[0].split_once('[') {
+            Some((board, pocket)) => (
+                board,
+                Some(
+                    pocket
+                        .strip_suffix(']')
+                        .ok_or_else(|| format!("{:?} has no closing ]", fields[0]))?,
+                ),
+            ),
+            None => (fields[0], None),
+        };
+        let ranks: Vec<&str> = board.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(format!("{:?} doesn't have 8 ranks", fields[0]));
+        }
+        let mut pieces: HashMap<(char, i32), Piece> = HashMap::new();
+        let mut kings: (Option<Position>, Option<Position>) = (None, None);
+        let mut promoted: HashSet<(char, i32)> = HashSet::new();
+        for (i, rank) in ranks.iter().enumerate() {
+            let row = 8 - i as i32;
+            let mut column = 1;
+            for letter in rank.chars() {
+                if let Some(empty) = letter.to_digit(10) {
+                    column += empty as i32;
+                    continue;
+                }
+                // the piece before it was promoted
+                if letter == '~' && column > 1 {
+                    promoted.insert((int_to_letter(column - 1), row));
+                    continue;
+                }
+                let name =
+                    piece_name(letter).ok_or_else(|| format!("{:?} is not a piece", letter))?;
+                if column > 8 {
+                    return Err(format!("rank {} has more than 8 squares", row));
+                }
+                let position = Position {
+                    column: int_to_letter(column),
+                    row,
+                };
+                let white = letter.is_ascii_uppercase();
+                if name == "king" {
+                    let king = if white { &mut kings.0 } else { &mut kings.1 };
+                    if king.is_some() {
+                        return Err(format!(
+                            "{} has more than one king",
+                            if white { "white" } else { "black" }
+                        ));
+                    }
+                    *king = Some(position.clone());
+                }
+                pieces.insert(
+                    (position.column, row),
+                    Piece::new(name, white, position).unwrap(),
+                );
+                column += 1;
+            }
+            if column != 9 {
+                return Err(format!("rank {} doesn't have 8 squares", row));
+            }
+        }
+        let mut in_pockets: (Vec<String>, Vec<String>) = (Vec::new(), Vec::new());
+        for letter in pockets.unwrap_or_default().chars() {
+            let name = piece_name(letter)
+                .filter(|name| *name != "king")
+                .ok_or_else(|| format!("{:?} can't be in a pocket", letter))?;
+            let pocket = if letter.is_ascii_uppercase() {
+                &mut in_pockets.0
+            } else {
+                &mut in_pockets.1
+            };
+            pocket.push(name.to_string());
+        }
+        let (Some(white_king), Some(black_king)) = kings else {
+            return Err("both sides need a king".to_string());
+        };
+        let white_to_move: bool = match fields[1] {
+            "w" => true,
+            "b" => false,
+            side => return Err(format!("{:?} is not w or b", side)),
+        };
+        let castling: &str = fields[2];
+        let is_right =
+            |c: char| "KQkq".contains(c) || ('A'..='H').contains(&c.to_ascii_uppercase());
+        if castling != "-" && !castling.chars().all(is_right) {
+            return Err(format!("{:?} is not a castling field", castling));
+        }
+        // long and short castling: for each side the rook's file, if it has the right
+        let mut rights: [(Option<char>, Option<char>); 2] = [(None, None), (None, None)];
+        let mut chess960 = false;
+        for letter in castling.chars().filter(|c| *c != '-') {
+            let white = letter.is_ascii_uppercase();
+            let row = if white { 1 } else { 8 };
+            let king = if white { &white_king } else { &black_king };
+            if king.row != row {
+                continue;
+            }
+            let is_rook = |column: i32| {
+                pieces
+                    .get(&(int_to_letter(column), row))
+                    .is_some_and(|p| p.name == "rook" && p.white == white)
+            };
+            let king_column = letter_to_int(king.column);
+            // X-FEN's K and Q stand for the outermost rook on that side, Shredder-FEN names its file
+            let file: Option<i32> = match letter.to_ascii_uppercase() {
+                'K' => (king_column + 1..=8).rev().find(|c| is_rook(*c)),
+                'Q' => (1..king_column).find(|c| is_rook(*c)),
+                file => {
+                    chess960 = true;
+                    Some(letter_to_int(file)).filter(|c| *c != king_column && is_rook(*c))
+                }
+            };
+            let Some(file) = file else {
+                continue;
+            };
+            let side = &mut rights[if white { 0 } else { 1 }];
+            if file > king_column {
+                side.1 = Some(int_to_letter(file));
+            } else {
+                side.0 = Some(int_to_letter(file));
+            }
+        }
+        let pick = |white: Option<char>, black: Option<char>, usual: char| match (white, black) {
+            (Some(white), Some(black)) if white != black => Err(format!(
+                "white castles with the rook on {} and black with the one on {}",
+                white, black
+            )),
+            (Some(file), _) | (_, Some(file)) => Ok(file),
+            (None, None) => Ok(usual),
+        };
+        let castling_files = (
+            pick(rights[0].0, rights[1].0, 'A')?,
+            pick(rights[0].1, rights[1].1, 'H')?,
+        );
+        let kings_at_home = [(&white_king, &rights[0]), (&black_king, &rights[1])]
+            .iter()
+            .all(|(king, side)| (side.0.is_none() && side.1.is_none()) || king.column == 'E');
+        if castling_files != ('A', 'H') || !kings_at_home {
+            chess960 = true;
+        }
+        let en_passant: Option<Position> = match fields[3] {
+            "-" => None,
+            square => {
+                let position = Position::parse(square)
+                    .filter(|p| p.row == if white_to_move { 6 } else { 3 })
+                    .ok_or_else(|| format!("{:?} can't be an en passant square", square))?;
+                Some(position)
+            }
+        };
+        let number = |i: usize, default: u32| match fields.get(i) {
+            Some(text) => text
+                .parse::<u32>()
+                .map_err(|_| format!("{:?} is not a move number", text)),
+            None => Ok(default),
+        };
+        let game = Game {
+            pieces,
+            white_to_move,
+            kings: (white_king, black_king),
+            has_king_moved: (
+                rights[0].0.is_none() && rights[0].1.is_none(),
+                rights[1].0.is_none() && rights[1].1.is_none(),
+            ),
+            has_rook_moved: (
+                (rights[0].0.is_none(), rights[0].1.is_none()),
+                (rights[1].0.is_none(), rights[1].1.is_none()),
+            ),
+            en_passant,
+            history: Vec::new(),
+            halfmove_clock: number(4, 0)?,
+            fullmove_number: number(5, 1)?.max(1),
+            chess960,
+            castling_files,
+            variant: &STANDARD,
+            checks,
+            pockets: in_pockets,
+            promoted,
+        };
+        if game.is_in_check(!white_to_move) {
+            return Err("the side that just moved is in check".to_string());
+        }
+        Ok(game)
+    }
+    /*
+    The position in Forsyth-Edwards notation
+    */
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        let has_drops = self.variant.has_drops();
+        for row in (1..=8).rev() {
+            let mut empty = 0;
+            for column in 1..=8 {
+                match self.pieces.get(&(int_to_letter(column), row)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece_letter(piece));
+                        if has_drops && self.promoted.contains(&(piece.position.column, row)) {
+                            fen.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if row > 1 {
+                fen.push('/');
+            }
+        }
+        // crazyhouse writes pockets once something was taken
+        if has_drops && !(self.pockets.0.is_empty() && self.pockets.1.is_empty()) {
+            let mut pocket: Vec<char> = Vec::new();
+            for (white, names) in [(true, &self.pockets.0), (false, &self.pockets.1)] {
+                for name in POCKET_ORDER {
+                    for _ in names.iter().filter(|n| *n == name) {
+                        let letter = moves::piece_letter(name);
+                        pocket.push(if white {
+                            letter
+                        } else {
+                            letter.to_ascii_lowercase()
+                        });
+                    }
+                }
+            }
+            fen.push('[');
+            fen.extend(pocket);
+            fen.push(']');
+        }
+        fen.push_str(if self.white_to_move { " w " } else { " b " });
+        let mut castling = String::new();
+        for (white, rights) in [
+            (true, self.has_rook_moved.0),
+            (false, self.has_rook_moved.1),
+        ] {
+            let king_moved = if white {
+                self.has_king_moved.0
+            } else {
+                self.has_king_moved.1
+            };
+            if king_moved {
+                continue;
+            }
+            // Chess960 games name the rooks' files, like Shredder-FEN, so reading them back
+            // knows it's Chess960 even when the pieces start where they usually do
+            let (long, short) = if self.chess960 {
+                self.castling_files
+            } else {
+                ('Q', 'K')
+            };
+            for (rook_moved, letter) in [(rights.1, short), (rights.0, long)] {
+                if !rook_moved {
+                    castling.push(if white {
+                        letter
+                    } else {
+                        letter.to_ascii_lowercase()
+                    });
+                }
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        fen.push_str(&castling);
+        match &self.en_passant {
+            Some(square) => fen.push_str(&format!(" {} ", square.to_string().to_lowercase())),
+            None => fen.push_str(" - "),
+        }
+        fen.push_str(&format!("{} {}", self.halfmove_clock, self.fullmove_number));
+        // only three-check counts them, and no count means none given yet
+        if self.checks != (0, 0) {
+            fen.push_str(&format!(" +{}+{}", self.checks.0, self.checks.1));
+        }
+        fen
+    }
+    /*
+    The first four FEN fields, equal for positions that count as the same for repetitions
+    */
+    pub fn position_key(&self) -> String {
+        let fen = self.to_fen();
+        fen.split(' ').take(4).collect::<Vec<_>>().join(" ")
+    }
+}
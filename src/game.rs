@@ -1,8 +1,27 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 use crate::aux_func::{int_to_letter, letter_to_int};
-use crate::piece::Piece;
+use crate::error::IllegalMove;
+use crate::moves::{is_promotion_piece, Move};
+use crate::piece::{Piece, PROMOTIONS};
 use crate::position::Position;
+use crate::variant::{Variant, STANDARD};
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    InsufficientMaterial,
+    Timeout,
+    Resignation,
+    Abandoned,
+    Tablebase, // called by the endgame tablebases
+    KingOfTheHill,
+    ThreeChecks,
+    Explosion, // a king blown up in atomic chess
+}
 
 #[derive(Clone)]
 pub struct Game {
@@ -11,10 +30,41 @@ pub struct Game {
     pub kings: (Position, Position),
     pub has_king_moved: (bool, bool),
     pub has_rook_moved: ((bool, bool), (bool, bool)),
+    pub en_passant: Option<Position>, // square a pawn skipped over on the last move
+    pub history: Vec<Move>,
+    pub halfmove_clock: u32, // moves since the last capture or pawn move, for the 50 move rule
+    pub fullmove_number: u32,
+    pub chess960: bool, // castling is written as the king taking its own rook
+    pub castling_files: (char, char), // where the long and short castling rooks start
+    pub variant: &'static dyn Variant,
+    pub checks: (u32, u32), // given by white and by black, for three-check
+    pub pockets: (Vec<String>, Vec<String>), // pieces white and black can drop, for crazyhouse
+    pub promoted: HashSet<(char, i32)>, // squares of pieces that started out as pawns
+}
+
+/*
+Where king and rook go when castling, and the square the move is written to: two squares over
+for the king in normal chess, the rook's square in Chess960
+*/
+#[derive(Clone)]
+pub struct Castling {
+    pub short: bool,
+    pub rook_from: Position,
+    pub king_to: Position,
+    pub rook_to: Position,
+    pub target: Position,
 }
+
 impl Game {
     pub fn update_piece(&mut self, piece: &Piece, pos: Position) {
         let init_pos: Position = piece.position.clone();
+        // a rook taken on its starting square can't castle anymore either
+        if let Some(captured) = self.pieces.get(&(pos.column, pos.row)) {
+            if captured.name == *"rook" {
+                let captured_white = captured.white;
+                self.mark_rook_moved(captured_white, &pos);
+            }
+        }
         if piece.name.clone() == *"king" {
             if piece.white {
                 self.kings.0 = pos.clone();
@@ -25,35 +75,13 @@ impl Game {
             }
         }
         if piece.name.clone() == *"rook" {
-            if piece.white
-                && init_pos.equals(&Position {
-                    column: 'A',
-                    row: 1,
-                })
-            {
-                self.has_rook_moved.0 .0 = true;
-            } else if piece.white
-                && init_pos.equals(&Position {
-                    column: 'H',
-                    row: 1,
-                })
-            {
-                self.has_rook_moved.0 .1 = true;
-            } else if !piece.white
-                && init_pos.equals(&Position {
-                    column: 'A',
-                    row: 8,
-                })
-            {
-                self.has_rook_moved.1 .0 = true;
-            } else if !piece.white
-                && init_pos.equals(&Position {
-                    column: 'H',
-                    row: 8,
-                })
-            {
-                self.has_rook_moved.1 .1 = true;
-            }
+            self.mark_rook_moved(piece.white, &init_pos);
+        }
+        // a promoted piece stays one wherever it goes
+        let promoted = self.promoted.remove(&(init_pos.column, init_pos.row));
+        self.promoted.remove(&(pos.column, pos.row));
+        if promoted {
+            self.promoted.insert((pos.column, pos.row));
         }
         self.pieces
             .remove(&(piece.position.column, piece.position.row));
@@ -68,6 +96,22 @@ impl Game {
         );
         self.pieces.remove(&(init_pos.column, init_pos.row));
     }
+    pub fn mark_rook_moved(&mut self, white: bool, square: &Position) {
+        let row = if white { 1 } else { 8 };
+        if square.row != row {
+            return;
+        }
+        let rooks = if white {
+            &mut self.has_rook_moved.0
+        } else {
+            &mut self.has_rook_moved.1
+        };
+        if square.column == self.castling_files.0 {
+            rooks.0 = true;
+        } else if square.column == self.castling_files.1 {
+            rooks.1 = true;
+        }
+    }
 
     pub fn can_make_single_move(
         &self,
@@ -89,6 +133,13 @@ impl Game {
             if !*is_white && current_pos.row != 7 {
                 return false;
             }
+            // can't jump over whatever is right in front
+            if self
+                .pieces
+                .contains_key(&(current_pos.column, current_pos.row + mov.1 / 2))
+            {
+                return false;
+            }
         }
         let end_row: i32 = end_pos.row;
         let end_column: i32 = letter_to_int(end_pos.column);
@@ -97,15 +148,13 @@ impl Game {
         let value: Option<&Piece> = self.pieces.get(&(end_pos.column, end_pos.row));
 
         if let Some(piece) = value {
-            if &piece.white == is_white {
-                return false;
-            } else if !is_last {
+            if &piece.white == is_white || !is_last {
                 return false;
             }
             if is_pawn && (mov.0.abs() != mov.1.abs()) {
                 return false;
             }
-        } else if is_pawn && (mov.0.abs() == mov.1.abs()) {
+        } else if is_pawn && (mov.0.abs() == mov.1.abs()) && !self.is_en_passant(piece, end_pos) {
             return false;
         }
         if end_column - start_column != mov.0 || end_row - start_row != mov.1 {
@@ -122,28 +171,6 @@ impl Game {
     ) -> bool {
         let mut cur_pos: Position = piece.position.clone();
 
-        if piece.name == *"king" {
-            if piece.white
-                && ((self.has_king_moved.0
-                    && (letter_to_int(end_pos.column) - letter_to_int(cur_pos.column)).abs() > 1)
-                    || (mov.0 < 0 && self.has_rook_moved.0 .0)
-                    || (mov.0 > 0 && self.has_rook_moved.0 .1))
-            {
-                println!("King has moved");
-                return false;
-            }
-            if !piece.white
-                && ((self.has_king_moved.1
-                    && (letter_to_int(end_pos.column) - letter_to_int(cur_pos.column)).abs() > 1)
-                    || (mov.0 < 0 && self.has_rook_moved.1 .0)
-                    || (mov.0 > 0 && self.has_rook_moved.1 .1))
-            {
-                println!("King has moved");
-                return false;
-            }
-        }
-        let mut count: i32 = 0;
-
         while is_within_bounds(&cur_pos) {
             let next_pos: Position = cur_pos.next_move(mov);
             let is_last = next_pos.equals(end_pos);
@@ -153,129 +180,361 @@ impl Game {
             if next_pos.equals(end_pos) {
                 return true;
             }
+            // the king only ever takes one step, castling is looked at on its own
             if piece.name == *"king" {
-                count += 1;
-                if count == 2 {
-                    return next_pos.equals(end_pos);
-                }
+                return false;
             }
             cur_pos = next_pos;
         }
         false
     }
-    pub fn check_can_castle(
-        &self,
-        piece: &Piece,
-        mov: &(i32, i32, bool),
-    ) -> (bool, Position, Position) {
-        if piece.white {
-            if mov.0 > 0 {
-                (
-                    self.can_make_multiple_move(
-                        &(1, 0, true),
-                        &Position {
-                            column: 'F',
-                            row: 1,
-                        },
-                        self.pieces.get(&('H', 1)).unwrap(),
-                    ),
-                    Position {
-                        column: 'F',
-                        row: 1,
-                    },
-                    Position {
-                        column: 'H',
-                        row: 1,
-                    },
-                )
-            } else {
-                (
-                    self.can_make_multiple_move(
-                        &(-1, 0, true),
-                        &Position {
-                            column: 'D',
-                            row: 1,
-                        },
-                        self.pieces.get(&('A', 1)).unwrap(),
-                    ),
-                    Position {
-                        column: 'D',
-                        row: 1,
-                    },
-                    Position {
-                        column: 'A',
-                        row: 1,
-                    },
-                )
+    /*
+    The castlings the side can make right now, by the Chess960 rules that normal chess is a case
+    of: king and rook haven't moved, every square either of them crosses or lands on is empty but
+    for the two of them, and the king isn't attacked anywhere from where it starts to where it
+    ends up
+    */
+    pub fn castlings(&self, white: bool) -> Vec<Castling> {
+        let row = if white { 1 } else { 8 };
+        let (king, king_moved, rooks_moved) = if white {
+            (&self.kings.0, self.has_king_moved.0, self.has_rook_moved.0)
+        } else {
+            (&self.kings.1, self.has_king_moved.1, self.has_rook_moved.1)
+        };
+        let mut castlings: Vec<Castling> = Vec::new();
+        if king_moved || king.row != row {
+            return castlings;
+        }
+        let king_column = letter_to_int(king.column);
+        for (short, rook_moved, file) in [
+            (false, rooks_moved.0, self.castling_files.0),
+            (true, rooks_moved.1, self.castling_files.1),
+        ] {
+            match self.pieces.get(&(file, row)) {
+                Some(rook) if !rook_moved && rook.name == *"rook" && rook.white == white => {}
+                _ => continue,
             }
-        } else if mov.0 > 0 {
-            (
-                self.can_make_multiple_move(
-                    &(1, 0, true),
-                    &Position {
-                        column: 'F',
-                        row: 8,
+            let rook_column = letter_to_int(file);
+            let king_to = Position {
+                column: if short { 'G' } else { 'C' },
+                row,
+            };
+            let rook_to = Position {
+                column: if short { 'F' } else { 'D' },
+                row,
+            };
+            let span = |from: i32, to: char| {
+                let to = letter_to_int(to);
+                from.min(to)..=from.max(to)
+            };
+            let blocked = span(king_column, king_to.column)
+                .chain(span(rook_column, rook_to.column))
+                .any(|column| {
+                    column != king_column
+                        && column != rook_column
+                        && self.pieces.contains_key(&(int_to_letter(column), row))
+                });
+            if blocked {
+                continue;
+            }
+            // the king walks with its rook already gone, so the rook can't shield it
+            let mut walk: Game = self.clone();
+            walk.pieces.remove(&(file, row));
+            let Some(walker) = walk.pieces.remove(&(king.column, row)) else {
+                continue;
+            };
+            let attacked = span(king_column, king_to.column).any(|column| {
+                let square = Position {
+                    column: int_to_letter(column),
+                    row,
+                };
+                walk.pieces.insert(
+                    (square.column, row),
+                    Piece {
+                        position: square.clone(),
+                        ..walker.clone()
                     },
-                    self.pieces.get(&('H', 8)).unwrap(),
-                ),
-                Position {
-                    column: 'F',
-                    row: 8,
-                },
-                Position {
-                    column: 'H',
-                    row: 8,
-                },
-            )
+                );
+                if white {
+                    walk.kings.0 = square.clone();
+                } else {
+                    walk.kings.1 = square.clone();
+                }
+                let attacked = walk.is_in_check(white);
+                walk.pieces.remove(&(square.column, row));
+                attacked
+            });
+            if attacked {
+                continue;
+            }
+            let target = if self.chess960 {
+                Position { column: file, row }
+            } else {
+                king_to.clone()
+            };
+            castlings.push(Castling {
+                short,
+                rook_from: Position { column: file, row },
+                king_to,
+                rook_to,
+                target,
+            });
+        }
+        castlings
+    }
+    /*
+    The castling mov stands for, if it's one that can be made
+    */
+    pub fn castling_move(&self, mov: &Move) -> Option<Castling> {
+        let king: &Piece = self
+            .pieces
+            .get(&(mov.from.column, mov.from.row))
+            .filter(|p| p.name == *"king")?;
+        self.castlings(king.white)
+            .into_iter()
+            .find(|c| c.target.equals(&mov.to))
+    }
+    /*
+    Puts king and rook where castling takes them, both squares are cleared first since in
+    Chess960 either may land where the other one stood
+    */
+    fn castle(&mut self, king: &Piece, castling: &Castling) {
+        let rook_from = &castling.rook_from;
+        let Some(rook) = self.pieces.remove(&(rook_from.column, rook_from.row)) else {
+            return;
+        };
+        self.pieces
+            .remove(&(king.position.column, king.position.row));
+        self.mark_rook_moved(king.white, rook_from);
+        if king.white {
+            self.kings.0 = castling.king_to.clone();
+            self.has_king_moved.0 = true;
         } else {
-            (
-                self.can_make_multiple_move(
-                    &(-1, 0, true),
-                    &Position {
-                        column: 'D',
-                        row: 8,
-                    },
-                    self.pieces.get(&('A', 8)).unwrap(),
-                ),
-                Position {
-                    column: 'D',
-                    row: 8,
-                },
-                Position {
-                    column: 'A',
-                    row: 8,
+            self.kings.1 = castling.king_to.clone();
+            self.has_king_moved.1 = true;
+        }
+        for (piece, square) in [(king.clone(), &castling.king_to), (rook, &castling.rook_to)] {
+            self.pieces.insert(
+                (square.column, square.row),
+                Piece {
+                    position: square.clone(),
+                    ..piece
                 },
-            )
+            );
+        }
+    }
+    /*
+    A pawn moving diagonally onto the square the enemy pawn just skipped over
+    */
+    fn is_en_passant(&self, piece: &Piece, end_pos: &Position) -> bool {
+        let target_row: i32 = if piece.white { 6 } else { 3 };
+        match &self.en_passant {
+            Some(square) => {
+                piece.name == *"pawn" && end_pos.row == target_row && square.equals(end_pos)
+            }
+            None => false,
+        }
+    }
+    /*
+    Square of the pawn taken en passant by this move, if it is one
+    */
+    fn en_passant_victim(&self, piece: &Piece, end_pos: &Position) -> Option<Position> {
+        if piece.position.column != end_pos.column && self.is_en_passant(piece, end_pos) {
+            Some(Position {
+                column: end_pos.column,
+                row: piece.position.row,
+            })
+        } else {
+            None
+        }
+    }
+    /*
+    The piece that piece going to end_pos takes, if any. Promoted pieces are taken as the pawns
+    they were
+    */
+    fn captured_piece(&self, piece: &Piece, end_pos: &Position) -> Option<Piece> {
+        let square: Position = self
+            .en_passant_victim(piece, end_pos)
+            .unwrap_or(end_pos.clone());
+        let captured: &Piece = self
+            .pieces
+            .get(&(square.column, square.row))
+            .filter(|p| p.white != piece.white)?;
+        if self.promoted.contains(&(square.column, square.row)) {
+            return Piece::new("pawn", captured.white, square);
         }
+        Some(captured.clone())
     }
+    /*
+    Squares the piece could reach ignoring checks, is_move_legal has the final say
+    */
     pub fn playable_pos(&self, piece: &Piece) -> Vec<Position> {
         let mut pos: Vec<Position> = Vec::<Position>::new();
-        let mut cur_pos: Position = piece.position.clone();
 
         for mov in &piece.ways_to_move {
-            if mov.2 {
-                while cur_pos.is_within_bounds() {
-                    let next_pos: Position = cur_pos.next_move(mov);
-                    // let is_last = &next_pos == &cur_pos;
-                    if self.can_make_single_move(mov, &cur_pos, &next_pos, piece, true) {
-                        pos.push(next_pos.clone());
-                    } else {
-                        break;
-                    }
-                    cur_pos = next_pos;
+            let mut cur_pos: Position = piece.position.clone();
+            loop {
+                let next_pos: Position = cur_pos.next_move(mov);
+                if !self.can_make_single_move(mov, &cur_pos, &next_pos, piece, true) {
+                    break;
                 }
-            } else {
-                let next_pos = cur_pos.next_move(mov);
-                if self.can_make_single_move(mov, &cur_pos, &next_pos, piece, true) {
-                    pos.push(next_pos);
+                pos.push(next_pos.clone());
+                // sliding stops on a capture, the king never slides
+                if !mov.2
+                    || self.pieces.contains_key(&(next_pos.column, next_pos.row))
+                    || piece.name == *"king"
+                {
+                    break;
                 }
+                cur_pos = next_pos;
             }
         }
+        if piece.name == *"king" {
+            pos.extend(self.castlings(piece.white).into_iter().map(|c| c.target));
+        }
 
         pos
     }
+    /*
+    Every legal move of the piece on pos, a promotion is listed once per piece the pawn can become
+    */
+    pub fn legal_moves_from(&self, pos: &Position) -> Vec<Move> {
+        let mut moves: Vec<Move> = Vec::new();
+        let piece: &Piece = match self.pieces.get(&(pos.column, pos.row)) {
+            Some(piece) => piece,
+            None => return moves,
+        };
+        for end_pos in self.playable_pos(piece) {
+            if !self.is_move_legal(piece, end_pos.clone()).0 {
+                continue;
+            }
+            if piece.name == *"pawn" && (end_pos.row == 8 || end_pos.row == 1) {
+                for name in PROMOTIONS {
+                    moves.push(Move {
+                        from: pos.clone(),
+                        to: end_pos.clone(),
+                        promotion: Some(name.to_string()),
+                        drop: None,
+                    });
+                }
+            } else {
+                moves.push(Move {
+                    from: pos.clone(),
+                    to: end_pos,
+                    promotion: None,
+                    drop: None,
+                });
+            }
+        }
+        moves
+    }
+    /*
+    Every legal move of the side to move, drops included
+    */
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves: Vec<Move> = Vec::new();
+        for piece in self.pieces.values() {
+            if piece.white == self.white_to_move {
+                moves.extend(self.legal_moves_from(&piece.position));
+            }
+        }
+        moves.extend(self.legal_drops());
+        moves
+    }
+    /*
+    Pieces the side to move has in its pocket
+    */
+    pub fn pocket(&self) -> &Vec<String> {
+        if self.white_to_move {
+            &self.pockets.0
+        } else {
+            &self.pockets.1
+        }
+    }
+    /*
+    Every drop the side to move can make, each piece of its pocket on every square it may go
+    */
+    pub fn legal_drops(&self) -> Vec<Move> {
+        let mut moves: Vec<Move> = Vec::new();
+        if !self.variant.has_drops() {
+            return moves;
+        }
+        let mut names: Vec<&String> = self.pocket().iter().collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            for row in 1..=8 {
+                for column in 1..=8 {
+                    let square = Position {
+                        column: int_to_letter(column),
+                        row,
+                    };
+                    if self.is_drop_legal(name, &square) {
+                        moves.push(Move::drop(name, square));
+                    }
+                }
+            }
+        }
+        moves
+    }
+    pub fn is_drop_legal(&self, name: &str, square: &Position) -> bool {
+        self.check_drop(name, square).is_ok()
+    }
+    /*
+    Whether the side to move can put the pocket piece name on square: an empty one, no pawns on
+    the first or last row, and the king can't be left in check
+    */
+    fn check_drop(&self, name: &str, square: &Position) -> Result<(), IllegalMove> {
+        if !self.variant.has_drops() {
+            return Err(IllegalMove::NoDrops);
+        }
+        if !self.pocket().iter().any(|p| p == name) {
+            return Err(IllegalMove::NotInPocket(name.to_string()));
+        }
+        if !square.is_within_bounds() {
+            return Err(IllegalMove::CantMoveThere);
+        }
+        if self.pieces.contains_key(&(square.column, square.row)) {
+            return Err(IllegalMove::SquareTaken);
+        }
+        if name == "pawn" && (square.row == 1 || square.row == 8) {
+            return Err(IllegalMove::PawnOnBackRank);
+        }
+        // a drop can't uncover anything, it only matters when it has to block a check
+        if !self.is_in_check(self.white_to_move) {
+            return Ok(());
+        }
+        let mut after: Game = self.clone();
+        after.drop_piece(name, square);
+        if !self.variant.allows(&after, self.white_to_move) {
+            return Err(IllegalMove::LeavesKingInCheck);
+        }
+        Ok(())
+    }
+    /*
+    Takes name out of the side to move's pocket and puts it on square
+    */
+    fn drop_piece(&mut self, name: &str, square: &Position) {
+        let white = self.white_to_move;
+        let pocket = if white {
+            &mut self.pockets.0
+        } else {
+            &mut self.pockets.1
+        };
+        if let Some(i) = pocket.iter().position(|p| p == name) {
+            pocket.remove(i);
+        }
+        if let Some(piece) = Piece::new(name, white, square.clone()) {
+            self.pieces.insert((square.column, square.row), piece);
+        }
+        self.promoted.remove(&(square.column, square.row));
+    }
 
     pub fn is_draw(&self) -> bool {
+        // whatever is in a pocket can still come back to mate
+        if !self.pockets.0.is_empty() || !self.pockets.1.is_empty() {
+            return false;
+        }
         let mut white_count = [0, 0, 0, 0, 0, 0]; // pawn, rook, knight, bishop, queen, king
         let mut black_count = [0, 0, 0, 0, 0, 0]; // pawn, rook, knight, bishop, queen, king
 
@@ -324,24 +583,45 @@ impl Game {
         false
     }
 
-
-
     /*
-    Checks if King_color is in check
+    Checks if King_color is in check, by the rules of the game's variant
     */
     pub fn is_in_check(&self, king_color: bool) -> bool {
-        let king: &Position;
-        if king_color {
-            king = &self.kings.0;
+        self.variant.is_in_check(self, king_color)
+    }
+    /*
+    Whether the king is still on the board, atomic chess can blow it up
+    */
+    pub fn has_king(&self, white: bool) -> bool {
+        let king = if white { &self.kings.0 } else { &self.kings.1 };
+        self.pieces
+            .get(&(king.column, king.row))
+            .is_some_and(|p| p.name == "king" && p.white == white)
+    }
+    /*
+    Whether any piece of the other side attacks King_color's king
+    */
+    pub fn is_king_attacked(&self, king_color: bool) -> bool {
+        let king: &Position = if king_color {
+            &self.kings.0
         } else {
-            king = &self.kings.1;
-        }
+            &self.kings.1
+        };
 
         for val in &self.pieces {
             let piece = val.1;
             if piece.white == king_color {
                 continue;
             }
+            // castling never attacks anything, so the other king only checks from next door
+            if piece.name == *"king" {
+                if (letter_to_int(piece.position.column) - letter_to_int(king.column)).abs() <= 1
+                    && (piece.position.row - king.row).abs() <= 1
+                {
+                    return true;
+                }
+                continue;
+            }
             for mov in &piece.ways_to_move {
                 if mov.2 {
                     if self.can_make_multiple_move(mov, king, piece) {
@@ -354,150 +634,211 @@ impl Game {
         }
         false
     }
+    /*
+    Whether piece can go to end_pos without leaving its king in check. For castling the other
+    two are where the rook goes and where it comes from, otherwise they're off the board
+    */
     pub fn is_move_legal(&self, piece: &Piece, end_pos: Position) -> (bool, Position, Position) {
-        let current_pos: Position = piece.position.clone();
-        let move_coll: &Vec<(i32, i32, bool)> = &piece.ways_to_move;
-        let mut can_make: bool = false;
-        let mut can_castle: (bool, Position, Position) = (
-            false,
-            Position {
-                column: 'Z',
-                row: -1,
-            },
-            Position {
-                column: 'Z',
-                row: -1,
-            },
-        );
-        for mov in move_coll {
-            if mov.2 {
-                if self.can_make_multiple_move( mov, &end_pos, piece) {
-                    can_make = true;
-                    if piece.name == *"king"
-                        && (letter_to_int(piece.position.column) - letter_to_int(end_pos.column)).abs()
-                            >= 2
-                    {
-                        can_castle = self.check_can_castle( piece, mov);
-                    }
-                    break;
-                }
-            } else if self.can_make_single_move( mov, &current_pos, &end_pos, piece, true) {
-                can_make = true;
-                break;
+        let nowhere = || Position {
+            column: 'Z',
+            row: -1,
+        };
+        if piece.name == *"king" {
+            let castling = self
+                .castlings(piece.white)
+                .into_iter()
+                .find(|c| c.target.equals(&end_pos));
+            if let Some(castling) = castling {
+                // castlings() already walked the king to its square
+                return (true, castling.rook_to, castling.rook_from);
             }
         }
-        if !can_make {
-            return (
-                false,
-                Position {
-                    column: can_castle.1.column,
-                    row: can_castle.1.row,
-                },
-                Position {
-                    column: can_castle.2.column,
-                    row: can_castle.2.row,
-                },
-            );
+        if !self.reaches(piece, &end_pos) {
+            return (false, nowhere(), nowhere());
         }
         let mut copy_game: Game = self.clone();
-        let copy_piece: Piece = piece.clone();
-        let color: bool = copy_piece.white;
-    
-        if can_castle.0 {
-            copy_game.update_piece(
-                self.pieces
-                    .get(&(can_castle.1.column, can_castle.1.row))
-                    .unwrap(),
-                end_pos.clone(),
-            );
+        let captured: Option<Piece> = self.captured_piece(piece, &end_pos);
+        if let Some(victim) = self.en_passant_victim(piece, &end_pos) {
+            copy_game.pieces.remove(&(victim.column, victim.row));
         }
-        copy_game = copy_game.clone();
-        copy_game.update_piece(&copy_piece, end_pos);
-    
-        if copy_game.is_in_check( color) {
-            // println!("Can't move into check");
-            return (false, can_castle.1, can_castle.2);
+        copy_game.update_piece(piece, end_pos.clone());
+        if let Some(captured) = &captured {
+            self.variant
+                .after_capture(&mut copy_game, &end_pos, captured);
         }
-        //println!("moved piece from {}{} to {}{}, color of king = {}", copy_piece.position.0, copy_piece.position.1, end_pos.0, end_pos.1, color);
-        (true, can_castle.1, can_castle.2)
+        (
+            self.variant.allows(&copy_game, piece.white),
+            nowhere(),
+            nowhere(),
+        )
     }
 
-    pub fn is_mate(&self) -> i32 {
-        for val in &self.pieces {
-            if val.1.white == self.white_to_move {
-                for pos in self.playable_pos(val.1) {
-                    let res = self.is_move_legal(val.1, pos);
-                    if res.0 {
-                        return 0;
-                    }
-                }
+    /*
+    Whether piece moves like that with nothing in the way, never mind its king
+    */
+    fn reaches(&self, piece: &Piece, end_pos: &Position) -> bool {
+        piece.ways_to_move.iter().any(|mov| {
+            if mov.2 {
+                self.can_make_multiple_move(mov, end_pos, piece)
+            } else {
+                self.can_make_single_move(mov, &piece.position, end_pos, piece, true)
             }
+        })
+    }
+    /*
+    Why is_move_legal turned piece going to end_pos down
+    */
+    fn why_illegal(&self, piece: &Piece, end_pos: &Position) -> IllegalMove {
+        if self.reaches(piece, end_pos) {
+            return IllegalMove::LeavesKingInCheck;
+        }
+        // two squares along the back rank, or onto its own rook like Chess960 writes it
+        let home = if piece.white { 1 } else { 8 };
+        let sideways = letter_to_int(end_pos.column) - letter_to_int(piece.position.column);
+        let onto_rook = self
+            .pieces
+            .get(&(end_pos.column, end_pos.row))
+            .is_some_and(|p| p.name == "rook" && p.white == piece.white);
+        if piece.name == "king"
+            && piece.position.row == home
+            && end_pos.row == home
+            && (sideways.abs() == 2 || onto_rook)
+        {
+            return IllegalMove::CantCastle;
+        }
+        IllegalMove::CantMoveThere
+    }
+    pub fn is_mate(&self) -> i32 {
+        if !self.legal_moves().is_empty() {
+            return 0;
         }
         if self.is_in_check(self.white_to_move) {
             return 1;
         }
         2
     }
-    pub fn make_move(&mut self, start_pos: Position, end_pos: Position) -> bool {
-        let cloned_game: Game = self.clone();
-        let moving_piece: Option<&Piece> = cloned_game.pieces.get(&(start_pos.column, start_pos.row));
-        if let Some(piece) = moving_piece {
-            if piece.white != self.white_to_move {
-                return false;
-            }
-            let res: (bool, Position, Position) = self.is_move_legal( piece, end_pos.clone());
-            if res.0 {
-                self.update_piece(piece, end_pos);
-                if res.1.column != 'Z' {
-                    println!("castling {}{}", res.1.column, res.1.row);
-                    let castle_piece: Piece =
-                    self.pieces.get(&(res.2.column, res.2.row)).unwrap().clone();
-                    self.update_piece(&castle_piece, res.1);
-                }
-                self.white_to_move = !self.white_to_move;
-                return true;
+    /*
+    Plays mov for the side to move, or says why it can't be played
+    */
+    pub fn play(&mut self, mov: &Move) -> Result<(), IllegalMove> {
+        if let Some(name) = &mov.drop {
+            self.check_drop(name, &mov.to)?;
+            self.drop_piece(name, &mov.to);
+            // a pawn can't go back either way
+            if name == "pawn" {
+                self.halfmove_clock = 0;
             } else {
-                return false;
+                self.halfmove_clock += 1;
             }
+            if !self.white_to_move {
+                self.fullmove_number += 1;
+            }
+            self.en_passant = None;
+            self.history.push(mov.clone());
+            self.white_to_move = !self.white_to_move;
+            self.variant.after_move(self);
+            return Ok(());
         }
-        println!("no piece found");
-        false
-    }
-    pub fn print_board(&self) {
-        for i in (1..=8).rev() {
-            for j in 1..=8 {
-                let key: i32 = i;
-                let value: Option<&Piece> = self.pieces.get(&(int_to_letter(j), key));
-                if let Some(piece) = value {
-                    let padding: f32 = 10.0 - piece.name.len() as f32;
-                    let left_pd: i32 = (padding / 2.0).floor() as i32;
-                    let right_pd: i32 = (padding / 2.0).ceil() as i32;
-                    let mut color: String = "b".to_string();
-                    if piece.white {
-                        color = "w".to_string();
-                    }
-                    print!(
-                        "{}{}{}{}",
-                        " ".repeat(left_pd as usize),
-                        color,
-                        piece.name,
-                        " ".repeat(right_pd as usize)
-                    );
-                } else {
-                    let st: String = ".".to_string();
-                    print!("     {}     ", st);
+        let cloned_game: Game = self.clone();
+        let start_pos: Position = mov.from.clone();
+        let end_pos: Position = mov.to.clone();
+        let moving_piece: Option<&Piece> =
+            cloned_game.pieces.get(&(start_pos.column, start_pos.row));
+        let Some(piece) = moving_piece else {
+            return Err(IllegalMove::NoPiece);
+        };
+        if piece.white != self.white_to_move {
+            return Err(IllegalMove::NotYourTurn);
+        }
+        let promotion: Option<String> =
+            if piece.name == *"pawn" && (end_pos.row == 8 || end_pos.row == 1) {
+                let name: String = mov.promotion.clone().unwrap_or("queen".to_string());
+                if !is_promotion_piece(&name) {
+                    return Err(IllegalMove::BadPromotion(name));
                 }
+                Some(name)
+            } else {
+                None
+            };
+        let res: (bool, Position, Position) = self.is_move_legal(piece, end_pos.clone());
+        if !res.0 {
+            return Err(self.why_illegal(piece, &end_pos));
+        }
+        let castling: Option<Castling> = if res.1.column != 'Z' {
+            self.castling_move(mov)
+        } else {
+            None
+        };
+        let victim: Option<Position> = self.en_passant_victim(piece, &end_pos);
+        // castling onto its own rook in Chess960 takes nothing
+        let captured: Option<Piece> = self.captured_piece(piece, &end_pos);
+        if piece.name == *"pawn" || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if !self.white_to_move {
+            self.fullmove_number += 1;
+        }
+        match &castling {
+            Some(castling) => self.castle(piece, castling),
+            None => self.update_piece(piece, end_pos.clone()),
+        }
+        if let Some(victim) = victim {
+            self.pieces.remove(&(victim.column, victim.row));
+        }
+        if let Some(name) = &promotion {
+            if let Some(promoted) = Piece::new(name, piece.white, end_pos.clone()) {
+                self.pieces.insert((end_pos.column, end_pos.row), promoted);
+                self.promoted.insert((end_pos.column, end_pos.row));
             }
-            println!("{}\n", i);
         }
-        for i in 1..=8 {
-            let st = int_to_letter(i);
-            print!("     {}     ", st);
+        if let Some(captured) = &captured {
+            self.variant.after_capture(self, &end_pos, captured);
         }
-        println!();
+        self.en_passant = if piece.name == *"pawn" && (end_pos.row - start_pos.row).abs() == 2 {
+            Some(Position {
+                column: start_pos.column,
+                row: (start_pos.row + end_pos.row) / 2,
+            })
+        } else {
+            None
+        };
+        self.history.push(Move {
+            from: start_pos,
+            to: end_pos,
+            promotion,
+            drop: None,
+        });
+        self.white_to_move = !self.white_to_move;
+        self.variant.after_move(self);
+        Ok(())
     }
-    pub fn reset(&mut self) {
-        *self = init_pieces();
+    /*
+    Rebuilds a game from its history in "E2E4" form, None if any move doesn't apply
+    */
+    pub fn replay(start: &Game, moves: &[String]) -> Option<Game> {
+        let mut game = start.clone();
+        for mov in moves {
+            game.play(&Move::parse(mov)?).ok()?;
+        }
+        Some(game)
+    }
+    /*
+    "1-0", "0-1" or "1/2-1/2" and how it ended once the board says the game is over,
+    None while it's still going
+    */
+    pub fn result(&self) -> Option<(String, Termination)> {
+        if let Some(result) = self.variant.result(self) {
+            return Some(result);
+        }
+        match self.is_mate() {
+            1 if self.white_to_move => Some(("0-1".to_string(), Termination::Checkmate)),
+            1 => Some(("1-0".to_string(), Termination::Checkmate)),
+            2 => Some(("1/2-1/2".to_string(), Termination::Stalemate)),
+            _ if self.is_draw() => Some(("1/2-1/2".to_string(), Termination::InsufficientMaterial)),
+            _ => None,
+        }
     }
 }
 
@@ -530,7 +871,7 @@ pub fn init_pieces() -> Game {
                 (0, -1, false),
                 (-1, -1, false),
                 (1, -1, false),
-                (0, -1, true),
+                (0, -2, false),
             ],
         };
         piece_map.insert((int_to_letter(i), 7), black_pawn);
@@ -725,6 +1066,16 @@ pub fn init_pieces() -> Game {
         ),
         has_king_moved: (false, false),
         has_rook_moved: ((false, false), (false, false)),
+        en_passant: None,
+        history: Vec::new(),
+        halfmove_clock: 0,
+        fullmove_number: 1,
+        chess960: false,
+        castling_files: ('A', 'H'),
+        variant: &STANDARD,
+        checks: (0, 0),
+        pockets: (Vec::new(), Vec::new()),
+        promoted: HashSet::new(),
     }
 }
 fn is_within_bounds(pos: &Position) -> bool {
@@ -0,0 +1,43 @@
+/*
+The rules of chess and its variants, FEN and PGN, move generation and the built-in engine.
+The server and the command line tools in main.rs are built on top of this, behind the server
+feature, so everything here builds without any of the web dependencies:
+
+    let mut game = chess::Game::from_fen(chess::fen::START_FEN)?;
+    let mov = chess::notation::parse_san(&game, "e4").unwrap();
+    game.play(&mov)?;
+    let reply = chess::search(&game, &chess::Limits { depth: Some(4), ..Default::default() });
+*/
+pub mod ai;
+#[doc(hidden)]
+pub mod aux_func;
+pub mod book;
+pub mod chess960;
+pub mod clock;
+pub mod computer;
+pub mod editor;
+pub mod error;
+pub mod fen;
+pub mod game;
+pub mod moves;
+pub mod notation;
+pub mod perft;
+pub mod piece;
+pub mod position;
+pub mod review;
+pub mod setup;
+pub mod skill;
+pub mod tablebase;
+pub mod uci;
+pub mod variant;
+
+// what most users need, without going through the modules
+pub use ai::{analyse, search, Limits, Score, SearchResult};
+pub use error::{Error, IllegalMove};
+pub use game::{init_pieces, Game, Termination};
+pub use moves::Move;
+pub use notation::{parse_pgn, parse_san, san, PgnGame};
+pub use perft::perft;
+pub use piece::Piece;
+pub use position::Position;
+pub use variant::Variant;
@@ -0,0 +1,431 @@
+use actix::{Message, Recipient};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use chess::clock::Clock;
+use chess::computer::{ComputerSlot, EngineConfig};
+use chess::editor::Draft;
+use chess::fen::START_FEN;
+use chess::game::{init_pieces, Game};
+use chess::variant::{Variant, STANDARD};
+
+use crate::api::{board_state, AnalysisView, ApiError, BoardState, PlayersView};
+use crate::config::GamesConfig;
+use crate::matchmaking::{pick_colors, ColorChoice, Seek};
+use crate::storage::{GameStore, StoredGame};
+use crate::tournament::{EntrantKind, Tournament};
+
+pub type Store = Arc<Mutex<Box<dyn GameStore>>>;
+pub type SharedLobby = Arc<Mutex<Lobby>>;
+
+/*
+Text frame pushed to everyone connected to a game or to the lobby
+*/
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Event(pub String);
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent<'a> {
+    State(&'a BoardState),
+    Seeks {
+        seeks: &'a [Seek],
+    },
+    GameStarted {
+        id: u64,
+        white: &'a str,
+        black: &'a str,
+        token: &'a str, // the seat token of whoever it's sent to
+    },
+    Analysis(&'a AnalysisView),
+    // the analysis is over, stopped when asked to before reaching its limits
+    AnalysisDone {
+        stopped: bool,
+    },
+}
+impl ServerEvent<'_> {
+    pub fn encode(&self) -> Option<String> {
+        match serde_json::to_string(self) {
+            Ok(text) => Some(text),
+            Err(e) => {
+                println!("failed to encode event: {}", e);
+                None
+            }
+        }
+    }
+}
+
+pub struct Watcher {
+    pub name: Option<String>,
+    pub recipient: Recipient<Event>,
+}
+
+/*
+A game being played, its stored record and everyone connected to it
+*/
+pub struct Table {
+    pub game: Game,
+    pub record: StoredGame,
+    pub watchers: HashMap<usize, Watcher>,
+    pub computers: HashMap<bool, ComputerSlot>, // engines playing here, by color (true for white)
+}
+impl Table {
+    pub fn new(game: Game, record: StoredGame) -> Table {
+        Table {
+            game,
+            record,
+            watchers: HashMap::new(),
+            computers: HashMap::new(),
+        }
+    }
+    pub fn is_seated(&self, name: &str) -> bool {
+        name != "none" && (self.record.white == name || self.record.black == name)
+    }
+    /*
+    A fresh token for whoever sits on the white (or black) side, names alone prove nothing
+    */
+    pub fn issue_token(&mut self, white: bool) -> String {
+        let token = format!("{:032x}", rand::random::<u128>());
+        let seat = if white {
+            &mut self.record.white_token
+        } else {
+            &mut self.record.black_token
+        };
+        *seat = Some(token.clone());
+        token
+    }
+    /*
+    The side token was handed out for, None if it's no seat's here
+    */
+    pub fn token_color(&self, token: &str) -> Option<bool> {
+        if self.record.white_token.as_deref() == Some(token) {
+            Some(true)
+        } else if self.record.black_token.as_deref() == Some(token) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+    /*
+    Connections that don't belong to one of the two players
+    */
+    pub fn spectators(&self) -> usize {
+        self.watchers
+            .values()
+            .filter(|w| !w.name.as_deref().is_some_and(|name| self.is_seated(name)))
+            .count()
+    }
+    pub fn state(&self) -> BoardState {
+        let mut state = board_state(&self.game, &self.record);
+        state.spectators = self.spectators();
+        state
+    }
+    pub fn broadcast(&self) {
+        let state = self.state();
+        let Some(text) = ServerEvent::State(&state).encode() else {
+            return;
+        };
+        for watcher in self.watchers.values() {
+            watcher.recipient.do_send(Event(text.clone()));
+        }
+    }
+    /*
+    Result of the game if a flag just fell, the caller ends the game with it
+    */
+    pub fn flag_fell(&self) -> Option<&'static str> {
+        if self.record.result.is_some() {
+            return None;
+        }
+        let clock = self.record.clock.as_ref()?;
+        let (white_ms, black_ms) = clock.remaining(self.game.white_to_move);
+        if white_ms == 0 {
+            Some("0-1")
+        } else if black_ms == 0 {
+            Some("1-0")
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct GameSummary {
+    pub id: u64,
+    pub players: PlayersView,
+    pub moves: usize,
+    pub spectators: usize,
+    pub rated: bool,
+    pub category: String,
+}
+
+pub struct Lobby {
+    pub tables: BTreeMap<u64, Table>,
+    pub next_id: u64,
+    next_watcher: usize,
+    pub seeks: Vec<Seek>, // oldest first, so the longest waiting get paired first
+    next_seek: u64,
+    pub listeners: HashMap<usize, Watcher>, // connected to the lobby instead of a game
+    pub tournaments: BTreeMap<u64, Tournament>,
+    pub engines: Vec<EngineConfig>, // the ones that can be seated or asked for moves
+    pub drafts: BTreeMap<u64, Draft>, // positions being set up in the board editor
+    pub games: GamesConfig,         // default clock and how many games can go on at once
+}
+impl Lobby {
+    pub fn new(next_id: u64) -> Lobby {
+        Lobby {
+            tables: BTreeMap::new(),
+            next_id,
+            next_watcher: 0,
+            seeks: Vec::new(),
+            next_seek: 1,
+            listeners: HashMap::new(),
+            tournaments: BTreeMap::new(),
+            engines: vec![EngineConfig::builtin()],
+            drafts: BTreeMap::new(),
+            games: GamesConfig::default(),
+        }
+    }
+    pub fn table(&mut self, id: u64) -> Result<&mut Table, ApiError> {
+        self.tables
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::not_found("unknown_game", format!("there's no game {}", id)))
+    }
+    /*
+    Whether another game can start without going over the configured limit
+    */
+    pub fn check_room(&self) -> Result<(), ApiError> {
+        let Some(max) = self.games.max_concurrent else {
+            return Ok(());
+        };
+        let running = self
+            .tables
+            .values()
+            .filter(|t| t.record.result.is_none())
+            .count();
+        if running >= max {
+            return Err(ApiError::unavailable(
+                "too_many_games",
+                format!("there are already {} games going on, try again later", max),
+            ));
+        }
+        Ok(())
+    }
+    /*
+    Opens a new table with a fresh game and returns its id
+    */
+    pub fn create(&mut self, clock: Option<Clock>, rated: bool) -> u64 {
+        self.create_from(clock, rated, init_pieces())
+    }
+    /*
+    Same as create, with the game starting from another position
+    */
+    pub fn create_from(&mut self, clock: Option<Clock>, rated: bool, start: Game) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut record = StoredGame::new(id);
+        record.clock = clock;
+        record.rated = rated;
+        let fen = start.to_fen();
+        if fen != START_FEN {
+            record.start_fen = Some(fen);
+        }
+        if start.variant.name() != STANDARD.name() {
+            record.variant = Some(start.variant.name().to_string());
+        }
+        self.tables.insert(id, Table::new(start, record));
+        id
+    }
+    pub fn add(&mut self, table: Table) {
+        self.next_id = self.next_id.max(table.record.id + 1);
+        self.tables.insert(table.record.id, table);
+    }
+    pub fn watcher_id(&mut self) -> usize {
+        self.next_watcher += 1;
+        self.next_watcher
+    }
+    /*
+    Games still being played, for people looking for something to watch
+    */
+    pub fn running(&self) -> Vec<GameSummary> {
+        self.tables
+            .values()
+            .filter(|t| t.record.result.is_none())
+            .map(|t| {
+                let state = t.state();
+                GameSummary {
+                    id: t.record.id,
+                    players: state.players,
+                    moves: t.record.moves.len(),
+                    spectators: state.spectators,
+                    rated: state.rated,
+                    category: state.category,
+                }
+            })
+            .collect()
+    }
+    pub fn engine(&self, name: &str) -> Result<&EngineConfig, ApiError> {
+        self.engines.iter().find(|e| e.name == name).ok_or_else(|| {
+            ApiError::not_found("unknown_engine", format!("there's no engine {:?}", name))
+        })
+    }
+    pub fn seek_id(&mut self) -> u64 {
+        self.next_seek += 1;
+        self.next_seek - 1
+    }
+    pub fn seek(&self, id: u64) -> Result<&Seek, ApiError> {
+        self.seeks
+            .iter()
+            .find(|s| s.id == id)
+            .ok_or_else(|| ApiError::not_found("unknown_seek", format!("there's no seek {}", id)))
+    }
+    /*
+    Takes every seek of name off the list, a player waits for one game at a time
+    */
+    pub fn withdraw(&mut self, name: &str) -> bool {
+        let before = self.seeks.len();
+        self.seeks.retain(|s| s.name != name);
+        self.seeks.len() != before
+    }
+    /*
+    Sends the open seeks to everyone connected to the lobby
+    */
+    pub fn announce_seeks(&self) {
+        let Some(text) = (ServerEvent::Seeks { seeks: &self.seeks }).encode() else {
+            return;
+        };
+        for listener in self.listeners.values() {
+            listener.recipient.do_send(Event(text.clone()));
+        }
+    }
+    /*
+    Starts the game of seek against opponent with both already seated,
+    and tells both of them where to go. Returns the new game's id
+    */
+    pub fn pair(&mut self, seek: &Seek, opponent: &str, opponent_color: ColorChoice) -> u64 {
+        let (white, black) = pick_colors(seek, opponent, opponent_color);
+        let (white, black) = (white.to_string(), black.to_string());
+        let id = self.create(seek.clock(), seek.rated);
+        let table: &mut Table = self.tables.get_mut(&id).unwrap();
+        table.record.white = white.clone();
+        table.record.black = black.clone();
+        let tokens = (table.issue_token(true), table.issue_token(false));
+        self.withdraw(&white);
+        self.withdraw(&black);
+        self.announce_game(id, (&white, &black), (&tokens.0, &tokens.1));
+        self.announce_seeks();
+        id
+    }
+    /*
+    Tells both players that their game id is waiting for them, each with their own seat token
+    */
+    fn announce_game(&self, id: u64, (white, black): (&str, &str), tokens: (&str, &str)) {
+        for listener in self.listeners.values() {
+            let token = if listener.name.as_deref() == Some(white) {
+                tokens.0
+            } else if listener.name.as_deref() == Some(black) {
+                tokens.1
+            } else {
+                continue;
+            };
+            let event = ServerEvent::GameStarted {
+                id,
+                white,
+                black,
+                token,
+            };
+            if let Some(text) = event.encode() {
+                listener.recipient.do_send(Event(text));
+            }
+        }
+    }
+    /*
+    A game by id, from memory if it's still there or from the store
+    */
+    pub fn record(&self, id: u64, store: &dyn GameStore) -> Option<StoredGame> {
+        match self.tables.get(&id) {
+            Some(table) => Some(table.record.clone()),
+            None => store.load(id).ok().flatten(),
+        }
+    }
+
+    pub fn tournament(&mut self, id: u64) -> Result<&mut Tournament, ApiError> {
+        self.tournaments.get_mut(&id).ok_or_else(|| {
+            ApiError::not_found(
+                "unknown_tournament",
+                format!("there's no tournament {}", id),
+            )
+        })
+    }
+    pub fn draft(&mut self, id: u64) -> Result<&mut Draft, ApiError> {
+        self.drafts
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::not_found("unknown_draft", format!("there's no draft {}", id)))
+    }
+    pub fn draft_id(&self) -> u64 {
+        self.drafts.keys().max().map_or(1, |id| id + 1)
+    }
+    pub fn tournament_id(&self) -> u64 {
+        self.tournaments.keys().max().map_or(1, |id| id + 1)
+    }
+    /*
+    Brings the results of the tournament's games that ended since last time
+    */
+    pub fn sync_tournament(
+        &mut self,
+        id: u64,
+        store: &dyn GameStore,
+    ) -> Result<&mut Tournament, ApiError> {
+        let Lobby {
+            tables,
+            tournaments,
+            ..
+        } = self;
+        let tournament: &mut Tournament = tournaments.get_mut(&id).ok_or_else(|| {
+            ApiError::not_found(
+                "unknown_tournament",
+                format!("there's no tournament {}", id),
+            )
+        })?;
+        tournament.collect_results(|game_id| match tables.get(&game_id) {
+            Some(table) => table.record.result.clone(),
+            None => store.load(game_id).ok().flatten()?.result,
+        });
+        Ok(tournament)
+    }
+    /*
+    Pairs the tournament's next round and opens a seated game for every board between two players.
+    Returns the ids of the new games
+    */
+    pub fn start_round(&mut self, id: u64) -> Result<Vec<u64>, ApiError> {
+        let tournament: &mut Tournament = self.tournament(id)?;
+        let (clock, rated) = (tournament.clock(), tournament.rated);
+        let round_index = tournament.next_round()? as usize - 1;
+        let boards: Vec<(usize, String, String)> = tournament.rounds[round_index]
+            .pairings
+            .iter()
+            .enumerate()
+            .filter_map(|(board, p)| {
+                let white = &tournament.entrants[p.white];
+                let black = &tournament.entrants[p.black?];
+                if white.kind == EntrantKind::Engine || black.kind == EntrantKind::Engine {
+                    return None;
+                }
+                Some((board, white.name.clone(), black.name.clone()))
+            })
+            .collect();
+        let mut games: Vec<u64> = Vec::new();
+        for (board, white, black) in boards {
+            let game_id = self.create(clock.clone(), rated);
+            let table: &mut Table = self.tables.get_mut(&game_id).unwrap();
+            table.record.white = white.clone();
+            table.record.black = black.clone();
+            let tokens = (table.issue_token(true), table.issue_token(false));
+            self.announce_game(game_id, (&white, &black), (&tokens.0, &tokens.1));
+            self.tournament(id)?.rounds[round_index].pairings[board].game_id = Some(game_id);
+            games.push(game_id);
+        }
+        Ok(games)
+    }
+}
@@ -1,321 +1,1956 @@
-use std::clone;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
 // use std::collections::HashMap;
 use actix_cors::Cors;
 use actix_files::Files;
-use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Responder};
-use serde::{Deserialize, Serialize};
-use std::process::Command;
-use std::{collections::HashMap, io};
-mod aux_func;
-mod game;
-mod piece;
-mod position;
-use actix::{Actor, StreamHandler};
+use actix_web::{error, web, App, HttpRequest, HttpResponse, HttpServer};
 use actix_web_actors::ws;
-use game::{init_pieces, Game};
-use position::Position;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+mod api;
+mod cli;
+mod config;
+mod engine_match;
+mod lobby;
+mod matchmaking;
+mod rating;
+mod socket;
+mod storage;
+mod terminal;
+mod tournament;
+mod xboard;
+use api::{
+    legal_moves, AnalysisView, ApiError, BoardState, DraftView, EngineMoveView, EngineView,
+    LegalMoves, ReviewView, SeatedState, TablebaseView,
+};
+use chess::ai::{self, Limits};
+use chess::clock::Clock;
+use chess::computer::{Computer, ComputerSlot, EngineConfig, Thought};
+use chess::editor::Draft;
+use chess::error::ProbeError;
+use chess::game::{init_pieces, Game, Termination};
+use chess::moves::{is_promotion_piece, Move};
+use chess::position::Position;
+use chess::review::{annotated_pgn, Review};
+use chess::skill::Strength;
+use chess::variant::VARIANTS;
+use chess::{chess960, computer, review, setup, tablebase, variant};
+use clap::Parser;
+use cli::{Cli, Command};
+use config::{Backend, Config, ServerConfig};
+use lobby::{GameSummary, Lobby, SharedLobby, Store, Table};
+use matchmaking::{ColorChoice, Seek, SeekOutcome};
+use rating::{category, is_valid_name, leaderboard, rate_game, Account, LeaderboardEntry};
+use socket::{AnalysisSocket, GameSocket};
+use storage::{pgn, record_tags, ArchiveQuery, FileStore, GameStore, MemoryStore, StoredGame};
+use tournament::{EntrantKind, Format, Tournament, TournamentSummary, TournamentView};
 
-#[derive(Serialize)] // This macro will allow us to convert this struct to JSON
-struct Message {
-    content: String,
+/*
+What a player got when they sat down, moves, resigning and resets need it. Kept out of the logs
+*/
+#[derive(Deserialize, Default)]
+struct SeatToken(String);
+impl std::fmt::Debug for SeatToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("SeatToken(..)")
+    }
 }
+
 #[derive(Deserialize, Debug)]
 struct MoveRequest {
+    name: String, // who is moving, has to be sitting on the side to move
+    #[serde(default)]
+    token: SeatToken,
+    #[serde(default)]
     start_pos: String,
+    #[serde(default)]
     end_pos: String,
+    promotion: Option<String>, // "queen", "rook", "bishop" or "knight"
+    drop: Option<String>,      // "N@F3" instead of the squares, crazyhouse only
+}
+#[derive(Deserialize, Debug)]
+struct LegalMovesRequest {
+    from: String,
 }
 #[derive(Deserialize, Debug)]
-struct ColorRequest {
+struct SeatRequest {
     name: String,
-    color: String,
+}
+#[derive(Deserialize, Debug)]
+struct NewGameRequest {
+    minutes: Option<u64>,
+    increment: Option<u64>, // seconds added after every move
+    rated: Option<bool>,
+    variant: Option<String>, // "standard", "chess960" or one of the variants, like "atomic"
+    position: Option<u32>,   // Chess960 starting position, a random one when left out
+    fen: Option<String>,     // to start from a position of your own
+    odds: Option<String>,    // the handicap white gives, like "queen"
+}
+#[derive(Deserialize, Debug)]
+struct NewDraftRequest {
+    fen: Option<String>, // where editing starts, the usual position when left out
+    game: Option<u64>,   // or the current position of a game
+}
+#[derive(Deserialize, Debug)]
+struct PlacePieceRequest {
+    color: String, // "white" or "black"
+    name: String,  // "pawn", "knight", ...
+}
+#[derive(Deserialize, Debug)]
+struct EnPassantRequest {
+    square: Option<String>, // none to take it away
+}
+#[derive(Deserialize, Debug)]
+struct StartDraftRequest {
+    minutes: Option<u64>,
+    increment: Option<u64>,
+}
+#[derive(Deserialize, Debug)]
+struct ResetRequest {
+    name: String,
+    #[serde(default)]
+    token: SeatToken,
+    minutes: Option<u64>,
+    increment: Option<u64>,
+    rated: Option<bool>,
+}
+#[derive(Deserialize, Debug)]
+struct ResignRequest {
+    name: String,
+    #[serde(default)]
+    token: SeatToken,
+}
+#[derive(Deserialize, Debug)]
+struct LeaderboardRequest {
+    category: String,
+    limit: Option<usize>,
+}
+#[derive(Deserialize, Debug)]
+struct SeekRequest {
+    name: String,
+    minutes: Option<u64>, // none for an untimed game
+    increment: Option<u64>,
+    rated: Option<bool>,
+    color: Option<ColorChoice>, // random if left out
+    rating_min: Option<f64>,
+    rating_max: Option<f64>,
+}
+#[derive(Deserialize, Debug)]
+struct CancelSeekRequest {
+    name: String,
+}
+#[derive(Deserialize, Debug)]
+struct NewTournamentRequest {
+    name: String,
+    organizer: String, // the only one who can start rounds and report results
+    format: Format,
+    rounds: Option<u32>, // how many rounds a swiss event has
+    minutes: Option<u64>,
+    increment: Option<u64>,
+    rated: Option<bool>,
+}
+#[derive(Deserialize, Debug)]
+struct RegisterRequest {
+    name: String,
+    kind: Option<EntrantKind>,
+    rating: Option<f64>, // engines only, players are seeded by their account
+}
+#[derive(Deserialize, Debug)]
+struct OrganizerRequest {
+    name: String,
+}
+#[derive(Deserialize, Debug)]
+struct TournamentResultRequest {
+    name: String,
+    round: u32,
+    board: usize,
+    result: String,
+}
+#[derive(Deserialize, Debug)]
+struct ComputerSeatRequest {
+    engine: Option<String>, // the built-in one when left out
+    #[serde(flatten)]
+    strength: Strength, // overrides the engine's own
+}
+#[derive(Deserialize, Debug)]
+struct EngineMoveRequest {
+    engine: Option<String>,
+    depth: Option<u32>, // replace the engine's own limits
+    movetime_ms: Option<u64>,
+}
+#[derive(Deserialize, Debug)]
+struct TablebaseRequest {
+    fen: String,
+}
+#[derive(Deserialize, Debug)]
+struct ReviewRequest {
+    depth: Option<u32>,
+}
+#[derive(Deserialize, Debug)]
+struct AnalysisRequest {
+    fen: Option<String>, // only for positions that aren't games
+    lines: Option<usize>,
+    depth: Option<u32>,
+    movetime_ms: Option<u64>,
+}
+#[derive(Deserialize, Debug)]
+struct WatchRequest {
+    name: Option<String>,
 }
 
-#[derive(Clone)]
-struct Players {
-    white: String,
-    black: String,
+fn save(store: &Store, record: &StoredGame) {
+    if let Err(e) = store.lock().unwrap().save(record) {
+        println!("failed to save game {}: {}", record.id, e);
+    }
 }
 
-fn get_user_pos() -> Option<(char, i32)> {
-    let mut input_str = String::new();
-    io::stdin().read_line(&mut input_str).expect("lolada");
-    match input_str.to_uppercase().trim().chars().collect::<Vec<_>>()[..] {
-        [letter, num] => {
-            if num.is_numeric() {
-                Some((letter, (num) as i32 - '0' as i32))
-            } else {
-                None
+/*
+Ends the game, stores it and moves the ratings if it counted for them
+*/
+fn finish(store: &Store, record: &mut StoredGame, result: &str, termination: Termination) {
+    record.finish(result, termination);
+    save(store, record);
+    if record.rated && termination != Termination::Abandoned {
+        if let Err(e) = rate_game(&mut **store.lock().unwrap(), record) {
+            println!("failed to rate game {}: {}", record.id, e);
+        }
+    }
+}
+
+/*
+Account of name, opened on the spot the first time it shows up
+*/
+fn open_account(store: &Store, name: &str) -> Account {
+    let mut store = store.lock().unwrap();
+    match store.account(name) {
+        Ok(Some(account)) => account,
+        Ok(None) => {
+            let account = Account::new(name);
+            if let Err(e) = store.save_account(&account) {
+                println!("failed to create account {}: {}", name, e);
             }
+            account
+        }
+        Err(e) => {
+            println!("failed to load account {}: {}", name, e);
+            Account::new(name)
         }
-        _ => None,
     }
 }
 
-fn gaming() {
-    println!("Game initialized!");
-    let mut game = init_pieces();
-    let mut res = Command::new("clear");
-    res.status().expect("failed to clear");
-    game.print_board();
+fn save_tournament(store: &Store, tournament: &Tournament) {
+    if let Err(e) = store.lock().unwrap().save_tournament(tournament) {
+        println!("failed to save tournament {}: {}", tournament.id, e);
+    }
+}
 
-    loop {
-        let player = if game.white_to_move {
-            "White".to_string()
+fn check_organizer(tournament: &Tournament, name: &str) -> Result<(), ApiError> {
+    if tournament.organizer != name {
+        return Err(ApiError::forbidden(
+            "not_organizer",
+            format!("only {} runs this tournament", tournament.organizer),
+        ));
+    }
+    Ok(())
+}
+
+/*
+The side name sits on at table, as long as token is the one they got for it. Engines move by
+themselves, nobody gets to speak for them
+*/
+fn seated_color(table: &Table, name: &str, token: &SeatToken) -> Result<bool, ApiError> {
+    let not_a_player = || {
+        ApiError::forbidden(
+            "not_a_player",
+            format!("{} isn't sitting at this game with that token", name),
+        )
+    };
+    if computer::engine_of_seat(name).is_some() {
+        return Err(not_a_player());
+    }
+    let white = table.token_color(&token.0).ok_or_else(not_a_player)?;
+    let seat = if white {
+        &table.record.white
+    } else {
+        &table.record.black
+    };
+    if seat != name {
+        return Err(not_a_player());
+    }
+    Ok(white)
+}
+
+/*
+The board for name, who just got a seat at table, with their token
+*/
+fn seated_state(table: &Table, name: &str) -> SeatedState {
+    let token = if table.record.white == name {
+        &table.record.white_token
+    } else {
+        &table.record.black_token
+    };
+    SeatedState {
+        token: token.clone().unwrap_or_default(),
+        state: table.state(),
+    }
+}
+
+fn parse_square(text: &str) -> Result<Position, ApiError> {
+    Position::parse(text).ok_or_else(|| {
+        ApiError::bad_request("invalid_square", format!("{:?} is not a square", text))
+    })
+}
+
+async fn list_games(lobby: web::Data<SharedLobby>) -> web::Json<Vec<GameSummary>> {
+    println!("Received request in /games");
+    web::Json(lobby.lock().unwrap().running())
+}
+
+async fn new_game(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    request: web::Query<NewGameRequest>,
+) -> Result<web::Json<BoardState>, ApiError> {
+    println!("Received request in /games: {:?}", request);
+    let custom: Option<Game> = match (request.fen.as_deref(), request.odds.as_deref()) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::bad_request(
+                "invalid_position",
+                "a game starts either from a FEN or with odds, not both",
+            ))
+        }
+        (Some(fen), None) => {
+            Some(setup::from_fen(fen).map_err(|e| ApiError::bad_request("invalid_fen", e))?)
+        }
+        (None, Some(name)) => {
+            Some(setup::odds(name).map_err(|e| ApiError::bad_request("invalid_odds", e))?)
+        }
+        (None, None) => None,
+    };
+    // nobody should gain or lose rating points over a handicap
+    if custom.is_some() && request.rated.unwrap_or(false) {
+        return Err(ApiError::bad_request(
+            "invalid_position",
+            "games from a position of your own or with odds can't be rated",
+        ));
+    }
+    let start: Game = match (request.variant.as_deref(), request.position, custom) {
+        (Some("chess960"), _, Some(_)) => {
+            return Err(ApiError::bad_request(
+                "invalid_position",
+                "Chess960 games start from one of their own positions",
+            ))
+        }
+        (Some("chess960"), index, None) => {
+            chess960::start(index).map_err(|e| ApiError::bad_request("invalid_position", e))?
+        }
+        (_, Some(_), _) => {
+            return Err(ApiError::bad_request(
+                "invalid_position",
+                "only Chess960 games take a starting position",
+            ))
+        }
+        (name, None, custom) => {
+            let name = name.unwrap_or("standard");
+            let mut game = custom.unwrap_or_else(init_pieces);
+            game.variant = variant::by_name(name).ok_or_else(|| {
+                let names: Vec<&str> = VARIANTS.iter().map(|v| v.name()).collect();
+                ApiError::bad_request(
+                    "invalid_variant",
+                    format!("{:?} is not chess960 or one of {}", name, names.join(", ")),
+                )
+            })?;
+            game
+        }
+    };
+    let mut lobby = lobby.lock().unwrap();
+    lobby.check_room()?;
+    let clock: Option<Clock> = lobby.games.clock(request.minutes, request.increment);
+    let id = lobby.create_from(clock, request.rated.unwrap_or(false), start);
+    let table: &mut Table = lobby.table(id)?;
+    save(&store, &table.record);
+    Ok(web::Json(table.state()))
+}
+
+/*
+Abandons the game and starts a new one on the same table, only the players sitting at it can do
+this. A table without anyone seated (or only engines) can't be reset
+*/
+async fn reset(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+    store: web::Data<Store>,
+    request: web::Json<ResetRequest>,
+) -> Result<web::Json<BoardState>, ApiError> {
+    println!("Received request in /reset");
+    let mut lobby = lobby.lock().unwrap();
+    let next_id = lobby.next_id;
+    let clock: Option<Clock> = lobby.games.clock(request.minutes, request.increment);
+    let table: &mut Table = lobby.table(*id)?;
+    let white = seated_color(table, &request.name, &request.token)?;
+
+    // the old game goes to the archive as abandoned, unless nothing happened in it.
+    // Walking away from a rated game counts as resigning it, so it can't be used to dodge a loss
+    if table.record.result.is_none() && !table.record.moves.is_empty() {
+        if table.record.rated {
+            let result = if white { "0-1" } else { "1-0" };
+            finish(&store, &mut table.record, result, Termination::Resignation);
         } else {
-            "Black".to_string()
-        };
-        println!("{} to play", player);
-        let init_pos: Position;
-        let end_pos: Position;
-        match get_user_pos() {
-            Some(pos) => {
-                init_pos = Position {
-                    column: pos.0,
-                    row: pos.1,
-                };
+            finish(&store, &mut table.record, "*", Termination::Abandoned);
+        }
+    }
+    let mut record: StoredGame = if table.record.moves.is_empty() && table.record.result.is_none() {
+        table.record.clone()
+    } else {
+        let mut record = StoredGame::new(next_id);
+        // it starts over from the same position
+        record.start_fen = table.record.start_fen.clone();
+        record.variant = table.record.variant.clone();
+        record
+    };
+    record.white = "none".to_string();
+    record.black = "none".to_string();
+    record.white_token = None;
+    record.black_token = None;
+    record.clock = clock;
+    record.rated = request.rated.unwrap_or(false);
+    save(&store, &record);
+
+    // everyone watching moves over to the new game
+    let old: Table = lobby.tables.remove(&*id).unwrap();
+    let start: Game = record.start().unwrap_or_else(|_| init_pieces());
+    let mut table = Table::new(start, record);
+    table.watchers = old.watchers;
+    let state = table.state();
+    table.broadcast();
+    lobby.add(table);
+    Ok(web::Json(state))
+}
+
+/*
+Plays mov for the side to move, runs the clock and ends the game if that was its last move
+*/
+fn play_on(table: &mut Table, store: &Store, mov: &Move) -> Result<(), ApiError> {
+    let Table { game, record, .. } = &mut *table;
+    let white_moved = game.white_to_move;
+    if let Err(e) = game.play(mov) {
+        return Err(ApiError::conflict(
+            "illegal_move",
+            format!("{} is not a legal move: {}", mov, e),
+        ));
+    }
+    record.moves.push(game.history.last().unwrap().to_string());
+    let flagged = match record.clock.as_mut() {
+        Some(clock) => !clock.press(white_moved),
+        None => false,
+    };
+    if flagged {
+        let result = if white_moved { "0-1" } else { "1-0" };
+        finish(store, record, result, Termination::Timeout);
+    } else if let Some((result, termination)) = game.result() {
+        finish(store, record, &result, termination);
+    } else if let Some(result) = tablebase::adjudicate(game) {
+        finish(store, record, result, Termination::Tablebase);
+    } else {
+        save(store, record);
+    }
+    table.broadcast();
+    Ok(())
+}
+
+/*
+Lets the computer move when it's its turn at table id. The thinking happens on a blocking
+thread without the lobby locked, so the move is only played if the game didn't change meanwhile.
+Engines of finished games are shut down here too
+*/
+fn wake_computer(lobby: SharedLobby, store: Store, id: u64) {
+    tokio::spawn(async move {
+        let (slot, config, game, start_fen, movetime) = {
+            let mut lobby = lobby.lock().unwrap();
+            let engines = lobby.engines.clone();
+            let Ok(table) = lobby.table(id) else {
+                return;
+            };
+            if table.record.result.is_some() {
+                table.computers.clear();
+                return;
             }
-            None => {
+            let white = table.game.white_to_move;
+            let seat = if white {
+                &table.record.white
+            } else {
+                &table.record.black
+            };
+            let Some(engine) = computer::engine_of_seat(seat) else {
+                return;
+            };
+            let Some(mut config) = engines.into_iter().find(|e| e.name == engine) else {
+                println!("game {} has unknown engine {} seated", id, engine);
                 return;
-                // continue;
+            };
+            let strength = if white {
+                table.record.white_strength
+            } else {
+                table.record.black_strength
+            };
+            if let Some(strength) = strength {
+                config.strength = strength;
             }
+            let movetime = table.record.clock.as_ref().map(|clock| {
+                let (white_ms, black_ms) = clock.remaining(white);
+                computer::time_budget(if white { white_ms } else { black_ms }, clock.increment_ms)
+            });
+            let slot: ComputerSlot = table.computers.entry(white).or_default().clone();
+            let start_fen = table.record.start_fen().to_string();
+            (slot, config, table.game.clone(), start_fen, movetime)
+        };
+        let thinking = game.clone();
+        let answer = tokio::task::spawn_blocking(move || {
+            computer::play_turn(&slot, &config, &thinking, &start_fen, movetime)
+                .map_err(|e| format!("engine {}: {}", config.name, e))
+        })
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+
+        let mut locked = lobby.lock().unwrap();
+        let Ok(table) = locked.table(id) else {
+            return;
+        };
+        if table.record.result.is_some() || table.game.history.len() != game.history.len() {
+            return;
         }
-        match get_user_pos() {
-            Some(pos) => {
-                end_pos = Position {
-                    column: pos.0,
-                    row: pos.1,
-                };
-            }
-            None => {
-                println!("lolada2");
-                continue;
-            }
+        let played = answer.and_then(|mov| play_on(table, &store, &mov).map_err(|e| e.message));
+        if let Err(e) = played {
+            // a computer that can't come up with a legal move loses the game
+            println!("game {}: {}", id, e);
+            let result = if table.game.white_to_move {
+                "0-1"
+            } else {
+                "1-0"
+            };
+            finish(&store, &mut table.record, result, Termination::Resignation);
+            table.broadcast();
         }
+        drop(locked);
+        // the other side may be a computer too
+        wake_computer(lobby, store, id);
+    });
+}
 
-        res.status().expect("failed to clear");
-        if !game.make_move(init_pos, end_pos) {
-            game.print_board();
-            println!("Invalid move lol");
-            continue;
+async fn move_piece(
+    lobby_handle: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+    request: web::Json<MoveRequest>,
+    store: web::Data<Store>,
+) -> Result<web::Json<BoardState>, ApiError> {
+    println!("Received request: {:?}", request);
+    let mut lobby = lobby_handle.lock().unwrap();
+    let table: &mut Table = lobby.table(*id)?;
+    let dropped: Option<Move> =
+        match &request.drop {
+            Some(text) => Some(Move::parse(text).filter(|m| m.drop.is_some()).ok_or_else(
+                || {
+                    ApiError::bad_request(
+                        "invalid_drop",
+                        format!("{:?} is not a drop like N@F3", text),
+                    )
+                },
+            )?),
+            None => None,
+        };
+    if table.record.result.is_some() {
+        return Err(ApiError::conflict("game_over", "the game is already over"));
+    }
+    if seated_color(table, &request.name, &request.token)? != table.game.white_to_move {
+        return Err(ApiError::forbidden(
+            "not_your_turn",
+            format!("{} isn't playing the side to move", request.name),
+        ));
+    }
+    if let Some(name) = &request.promotion {
+        if !is_promotion_piece(name) {
+            return Err(ApiError::bad_request(
+                "invalid_promotion",
+                format!("a pawn can't become a {:?}", name),
+            ));
         }
+    }
+    let mov = match dropped {
+        Some(mov) => mov,
+        None => Move {
+            from: parse_square(&request.start_pos)?,
+            to: parse_square(&request.end_pos)?,
+            promotion: request.promotion.clone(),
+            drop: None,
+        },
+    };
+    // a pawn reaching the last row needs the client to say what it becomes
+    let promotes = table
+        .game
+        .legal_moves_from(&mov.from)
+        .iter()
+        .any(|m| m.to.equals(&mov.to) && m.promotion.is_some());
+    if promotes && mov.promotion.is_none() {
+        return Err(ApiError::bad_request(
+            "missing_promotion",
+            format!("{} promotes, pick a piece", mov),
+        ));
+    }
+    play_on(table, &store, &mov)?;
+    let state = table.state();
+    wake_computer(lobby_handle.get_ref().clone(), store.get_ref().clone(), *id);
+    Ok(web::Json(state))
+}
 
-        game.print_board();
-        if game.is_in_check(game.white_to_move) {
-            println!("{} is in check", game.white_to_move);
-        }
+async fn resign(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+    request: web::Json<ResignRequest>,
+    store: web::Data<Store>,
+) -> Result<web::Json<BoardState>, ApiError> {
+    println!("Received request in /resign: {:?}", request);
+    let mut lobby = lobby.lock().unwrap();
+    let table: &mut Table = lobby.table(*id)?;
+    let white = seated_color(table, &request.name, &request.token)?;
+    if table.record.result.is_some() {
+        return Err(ApiError::conflict("game_over", "the game is already over"));
+    }
+    let result = if white { "0-1" } else { "1-0" };
+    finish(&store, &mut table.record, result, Termination::Resignation);
+    table.broadcast();
+    table.computers.clear();
+    Ok(web::Json(table.state()))
+}
+
+async fn game_to_json(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+) -> Result<web::Json<BoardState>, ApiError> {
+    println!("Received request in /boardstate");
+    let mut lobby = lobby.lock().unwrap();
+    Ok(web::Json(lobby.table(*id)?.state()))
+}
 
-        if game.is_mate() == 1 {
-            println!("Game over");
-            println!("{} wins", player);
+async fn get_legal_moves(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+    request: web::Query<LegalMovesRequest>,
+) -> Result<web::Json<LegalMoves>, ApiError> {
+    println!("Received request in /legalmoves: {:?}", request);
+    let mut lobby = lobby.lock().unwrap();
+    let table: &mut Table = lobby.table(*id)?;
+    let from: Position = parse_square(&request.from)?;
+    if table.record.result.is_some() {
+        return Ok(web::Json(LegalMoves {
+            from: from.to_string(),
+            moves: Vec::new(),
+        }));
+    }
+    Ok(web::Json(legal_moves(&table.game, &from)))
+}
+
+async fn archive(
+    store: web::Data<Store>,
+    query: web::Query<ArchiveQuery>,
+) -> Result<HttpResponse, ApiError> {
+    println!("Received request in /archive: {:?}", query);
+    let mut games = store
+        .lock()
+        .unwrap()
+        .archive(&query)
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    for game in games.iter_mut() {
+        game.white_token = None;
+        game.black_token = None;
+    }
+    Ok(HttpResponse::Ok().json(games))
+}
 
-            break;
+async fn choose_seat(
+    lobby: web::Data<SharedLobby>,
+    path: web::Path<(u64, String)>,
+    request: web::Json<SeatRequest>,
+    store: web::Data<Store>,
+) -> Result<web::Json<SeatedState>, ApiError> {
+    let (id, color) = path.into_inner();
+    println!("Received request in /seats/{}", color);
+    let mut lobby = lobby.lock().unwrap();
+    let table: &mut Table = lobby.table(id)?;
+    let record: &mut StoredGame = &mut table.record;
+    let seat: &mut String = match color.as_str() {
+        "white" => &mut record.white,
+        "black" => &mut record.black,
+        _ => {
+            return Err(ApiError::bad_request(
+                "invalid_color",
+                format!("{:?} is not white or black", color),
+            ))
         }
-        if game.is_mate() == 2 {
-            println!("Game over");
-            println!("Stalemate");
-            break;
+    };
+    if !is_valid_name(&request.name) {
+        return Err(ApiError::bad_request(
+            "invalid_name",
+            "names are up to 32 letters, digits, '-' or '_'",
+        ));
+    }
+    if seat != "none" {
+        return Err(ApiError::conflict(
+            "seat_taken",
+            format!("{} is already taken", color),
+        ));
+    }
+    *seat = request.name.clone();
+    let token = table.issue_token(color == "white");
+    save(&store, &table.record);
+    // sitting down for the first time opens an account
+    open_account(&store, &request.name);
+    table.broadcast();
+    Ok(web::Json(SeatedState {
+        token,
+        state: table.state(),
+    }))
+}
+
+/*
+Sits an engine down on one side of a casual game, it moves as soon as it's its turn
+*/
+async fn seat_computer(
+    lobby_handle: web::Data<SharedLobby>,
+    path: web::Path<(u64, String)>,
+    request: web::Json<ComputerSeatRequest>,
+    store: web::Data<Store>,
+) -> Result<web::Json<BoardState>, ApiError> {
+    let (id, color) = path.into_inner();
+    println!(
+        "Received request in /seats/{}/computer: {:?}",
+        color, request
+    );
+    let mut lobby = lobby_handle.lock().unwrap();
+    let mut config: EngineConfig = lobby
+        .engine(request.engine.as_deref().unwrap_or("builtin"))?
+        .clone();
+    if request.strength.is_set() {
+        config.strength = request.strength;
+        config
+            .check_strength()
+            .map_err(|e| ApiError::bad_request("invalid_strength", e))?;
+    }
+    let table: &mut Table = lobby.table(id)?;
+    let record: &mut StoredGame = &mut table.record;
+    let (seat, strength) = match color.as_str() {
+        "white" => (&mut record.white, &mut record.white_strength),
+        "black" => (&mut record.black, &mut record.black_strength),
+        _ => {
+            return Err(ApiError::bad_request(
+                "invalid_color",
+                format!("{:?} is not white or black", color),
+            ))
         }
-        if game.is_draw() {
-            println!("Game over");
-            println!("Draw");
-            break;
+    };
+    if seat != "none" {
+        return Err(ApiError::conflict(
+            "seat_taken",
+            format!("{} is already taken", color),
+        ));
+    }
+    if record.rated {
+        return Err(ApiError::conflict(
+            "rated_game",
+            "computers only play casual games",
+        ));
+    }
+    *seat = computer::seat_name(&config.name);
+    *strength = request.strength.is_set().then_some(config.strength);
+    save(&store, record);
+    table.broadcast();
+    let state = table.state();
+    wake_computer(lobby_handle.get_ref().clone(), store.get_ref().clone(), id);
+    Ok(web::Json(state))
+}
+
+async fn list_engines(lobby: web::Data<SharedLobby>) -> web::Json<Vec<EngineView>> {
+    let lobby = lobby.lock().unwrap();
+    web::Json(
+        lobby
+            .engines
+            .iter()
+            .map(|e| EngineView {
+                name: e.name.clone(),
+                builtin: e.command.is_none(),
+            })
+            .collect(),
+    )
+}
+
+/*
+Asks an engine what it would play in a game, without playing it. A fresh engine is started
+for every request
+*/
+async fn engine_move(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+    request: web::Query<EngineMoveRequest>,
+) -> Result<web::Json<EngineMoveView>, ApiError> {
+    println!("Received request in /games/{}/bestmove: {:?}", id, request);
+    let (mut config, game, start_fen) = {
+        let mut lobby = lobby.lock().unwrap();
+        let config = lobby
+            .engine(request.engine.as_deref().unwrap_or("builtin"))?
+            .clone();
+        let table: &mut Table = lobby.table(*id)?;
+        if table.game.result().is_some() {
+            return Err(ApiError::conflict("game_over", "the game is already over"));
         }
+        let start_fen = table.record.start_fen().to_string();
+        (config, table.game.clone(), start_fen)
+    };
+    if request.depth.is_some() || request.movetime_ms.is_some() {
+        config.depth = request.depth;
+        config.nodes = None;
+        config.movetime_ms = request.movetime_ms;
     }
+    let thinking = game.clone();
+    let thought: Thought = tokio::task::spawn_blocking(move || {
+        Computer::start(&config)?.think(&thinking, &start_fen, None)
+    })
+    .await
+    .map_err(|e| ApiError::internal(e.to_string()))?
+    .map_err(|e| ApiError::internal(format!("engine failed: {}", e)))?;
+    let name = request.engine.as_deref().unwrap_or("builtin");
+    Ok(web::Json(EngineMoveView::new(name, &game, &thought)))
 }
 
-// fn main() {
-//     gaming();
-// }
-async fn reset(
-    game: web::Data<Arc<Mutex<Game>>>,
-    players: web::Data<Arc<Mutex<Players>>>,
-) -> impl Responder {
-    println!("Received request in /reset");
-    let mut game: std::sync::MutexGuard<'_, Game> = game.lock().unwrap();
-    let mut players: std::sync::MutexGuard<'_, Players> = players.lock().unwrap();
-    players.white = "none".to_string();
-    players.black = "none".to_string();
-    game.reset();
-    let mut board: HashMap<String, String> = HashMap::new();
-
-    for (pos, piece) in game.pieces.iter() {
-        let color = if piece.white {
-            "w".to_string()
-        } else {
-            "b".to_string()
+/*
+Looks game up in the tablebases, away from the async threads since tables get read from disk
+*/
+async fn probe_tablebase(game: Game) -> Result<web::Json<TablebaseView>, ApiError> {
+    let Some(tables) = tablebase::tablebase() else {
+        return Err(ApiError::not_found(
+            "no_tablebases",
+            "the server has no tablebases",
+        ));
+    };
+    tokio::task::spawn_blocking(move || {
+        let probe = || {
+            let wdl = tables.probe_wdl(&game)?;
+            let dtz = tables.probe_dtz(&game)?;
+            let moves = tables.rate_moves(&game)?;
+            Ok(TablebaseView::new(&game, wdl, dtz, &moves))
         };
-        board.insert(
-            format!("{}{}", pos.0, pos.1),
-            format!("{}{}", color, piece.name),
-        );
+        probe().map(web::Json).map_err(|e| match e {
+            ProbeError::NotCovered => ApiError::not_found(
+                "not_in_tablebase",
+                format!(
+                    "the tablebases don't have this position (up to {} pieces, no castling)",
+                    tables.max_pieces()
+                ),
+            ),
+            // the server's tables are incomplete or damaged, not the request
+            e => ApiError::unavailable("tablebase_unavailable", e.to_string()),
+        })
+    })
+    .await
+    .map_err(|e| ApiError::internal(e.to_string()))?
+}
+
+async fn tablebase_position(
+    request: web::Query<TablebaseRequest>,
+) -> Result<web::Json<TablebaseView>, ApiError> {
+    println!("Received request in /tablebase: {:?}", request);
+    let game: Game =
+        Game::from_fen(&request.fen).map_err(|e| ApiError::bad_request("invalid_fen", e))?;
+    probe_tablebase(game).await
+}
+
+async fn game_tablebase(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+) -> Result<web::Json<TablebaseView>, ApiError> {
+    println!("Received request in /games/{}/tablebase", id);
+    let game: Game = lobby.lock().unwrap().table(*id)?.game.clone();
+    probe_tablebase(game).await
+}
+
+// most lines an analysis can ask for
+const MAX_LINES: usize = 10;
+// analyses over the websocket without limits go on until stopped, or for this long
+const MAX_ANALYSIS: Duration = Duration::from_secs(600);
+
+/*
+How many lines and how deep, out of an analysis request. Without limits the search goes 4
+plies deep, or as long as it's let to when streamed
+*/
+fn analysis_setup(request: &AnalysisRequest, streamed: bool) -> Result<(Limits, usize), ApiError> {
+    let lines = request.lines.unwrap_or(1);
+    if !(1..=MAX_LINES).contains(&lines) {
+        return Err(ApiError::bad_request(
+            "invalid_lines",
+            format!("lines has to be between 1 and {}", MAX_LINES),
+        ));
     }
-    board.insert(
-        "to_move".to_string(),
-        if game.white_to_move {
-            "w".to_string()
-        } else {
-            "b".to_string()
-        },
+    let mut limits = Limits {
+        depth: request.depth,
+        movetime: request.movetime_ms.map(Duration::from_millis),
+        ..Limits::default()
+    };
+    if streamed && limits.depth.is_none() && limits.movetime.is_none() {
+        limits.movetime = Some(MAX_ANALYSIS);
+    }
+    Ok((limits, lines))
+}
+
+fn analysis_game(
+    lobby: &SharedLobby,
+    id: Option<u64>,
+    request: &AnalysisRequest,
+) -> Result<Game, ApiError> {
+    match (id, &request.fen) {
+        (Some(id), _) => Ok(lobby.lock().unwrap().table(id)?.game.clone()),
+        (None, Some(fen)) => {
+            Game::from_fen(fen).map_err(|e| ApiError::bad_request("invalid_fen", e))
+        }
+        (None, None) => Err(ApiError::bad_request(
+            "missing_fen",
+            "give the position as fen",
+        )),
+    }
+}
+
+/*
+The best lines of the built-in engine for a position, once it's done
+*/
+async fn analysis(
+    game: Game,
+    request: &AnalysisRequest,
+) -> Result<web::Json<AnalysisView>, ApiError> {
+    let (limits, lines) = analysis_setup(request, false)?;
+    tokio::task::spawn_blocking(move || {
+        let results = ai::analyse(&game, &limits, lines, &mut |_| {});
+        web::Json(AnalysisView::new(&game, &results))
+    })
+    .await
+    .map_err(|e| ApiError::internal(e.to_string()))
+}
+
+async fn analyse_position(
+    lobby: web::Data<SharedLobby>,
+    request: web::Query<AnalysisRequest>,
+) -> Result<web::Json<AnalysisView>, ApiError> {
+    println!("Received request in /analysis: {:?}", request);
+    analysis(analysis_game(&lobby, None, &request)?, &request).await
+}
+
+async fn analyse_game(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+    request: web::Query<AnalysisRequest>,
+) -> Result<web::Json<AnalysisView>, ApiError> {
+    println!("Received request in /games/{}/analysis: {:?}", id, request);
+    analysis(analysis_game(&lobby, Some(*id), &request)?, &request).await
+}
+
+/*
+Analysis pushed over a websocket as it gets deeper, of a game's position or of ?fen=
+*/
+async fn watch_analysis(
+    req: HttpRequest,
+    stream: web::Payload,
+    lobby: web::Data<SharedLobby>,
+    id: Option<web::Path<u64>>,
+    request: web::Query<AnalysisRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    println!("Received request in /analysis/watch: {:?}", request);
+    let game: Game = analysis_game(&lobby, id.map(|id| *id), &request)?;
+    let (limits, lines) = analysis_setup(&request, true)?;
+    ws::start(AnalysisSocket::new(game, limits, lines), &req, stream)
+}
+
+// reviews search every position, so they don't get to go as deep as single searches
+const MAX_REVIEW_DEPTH: u32 = 6;
+
+/*
+Reviews a finished game with the built-in engine, 3 plies deep unless asked otherwise
+*/
+async fn review_game(
+    lobby: &SharedLobby,
+    store: &Store,
+    id: u64,
+    request: &ReviewRequest,
+) -> Result<(StoredGame, Review), ApiError> {
+    let depth = request.depth.unwrap_or(3);
+    if !(1..=MAX_REVIEW_DEPTH).contains(&depth) {
+        return Err(ApiError::bad_request(
+            "invalid_depth",
+            format!("depth has to be between 1 and {}", MAX_REVIEW_DEPTH),
+        ));
+    }
+    let record: StoredGame = lobby
+        .lock()
+        .unwrap()
+        .record(id, &**store.lock().unwrap())
+        .ok_or_else(|| ApiError::not_found("unknown_game", format!("there's no game {}", id)))?;
+    if record.result.is_none() {
+        return Err(ApiError::conflict(
+            "game_not_over",
+            "only finished games can be reviewed",
+        ));
+    }
+    let limits = Limits {
+        depth: Some(depth),
+        ..Limits::default()
+    };
+    tokio::task::spawn_blocking(move || {
+        let start: Game = record.start().unwrap_or_else(|_| init_pieces());
+        let review = review::review(&start, &record.moves, &limits);
+        (record, review)
+    })
+    .await
+    .map_err(|e| ApiError::internal(e.to_string()))
+}
+
+async fn game_review(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+    request: web::Query<ReviewRequest>,
+) -> Result<web::Json<ReviewView>, ApiError> {
+    println!("Received request in /games/{}/review: {:?}", id, request);
+    let (_, review) = review_game(&lobby, &store, *id, &request).await?;
+    Ok(web::Json(ReviewView::new(*id, &review)))
+}
+
+async fn game_review_pgn(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+    request: web::Query<ReviewRequest>,
+) -> Result<HttpResponse, ApiError> {
+    println!(
+        "Received request in /games/{}/review/pgn: {:?}",
+        id, request
     );
-    board.insert("bchosen".to_string(), players.black.clone());
-    board.insert("wchosen".to_string(), players.white.clone());
-    HttpResponse::Ok().json(board)
+    let (record, review) = review_game(&lobby, &store, *id, &request).await?;
+    let mut tags = record_tags(&record, "Casual game", "-");
+    tags.push(("Annotator", "built-in engine".to_string()));
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-chess-pgn")
+        .body(annotated_pgn(
+            &review,
+            &tags,
+            record.result.as_deref().unwrap_or("*"),
+        )))
 }
 
-async fn move_piece(
-    game: web::Data<Arc<Mutex<Game>>>,
-    request: web::Json<MoveRequest>,
-    players: web::Data<Arc<Mutex<Players>>>,
-) -> impl Responder {
-    println!("Received request: {:?}", request);
-    let mut game: std::sync::MutexGuard<'_, Game> = game.lock().unwrap();
-    let players: std::sync::MutexGuard<'_, Players> = players.lock().unwrap();
-    let start_pos = Position {
-        column: request.start_pos.chars().next().unwrap(),
-        row: request.start_pos[1..].parse::<i32>().unwrap(),
+async fn player(
+    store: web::Data<Store>,
+    name: web::Path<String>,
+) -> Result<web::Json<Account>, ApiError> {
+    println!("Received request in /players/{}", name);
+    // the name becomes a file name, so nothing like ".." gets that far
+    if !is_valid_name(&name) {
+        return Err(ApiError::bad_request(
+            "invalid_name",
+            "names are up to 32 letters, digits, '-' or '_'",
+        ));
+    }
+    match store.lock().unwrap().account(&name) {
+        Ok(Some(account)) => Ok(web::Json(account)),
+        Ok(None) => Err(ApiError::not_found(
+            "unknown_player",
+            format!("there's no player {}", name),
+        )),
+        Err(e) => Err(ApiError::internal(e.to_string())),
+    }
+}
+
+async fn get_leaderboard(
+    store: web::Data<Store>,
+    request: web::Query<LeaderboardRequest>,
+) -> Result<web::Json<Vec<LeaderboardEntry>>, ApiError> {
+    println!("Received request in /leaderboard: {:?}", request);
+    let accounts = store
+        .lock()
+        .unwrap()
+        .accounts()
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(web::Json(leaderboard(
+        &accounts,
+        &request.category,
+        request.limit.unwrap_or(50),
+    )))
+}
+
+async fn list_seeks(lobby: web::Data<SharedLobby>) -> web::Json<Vec<Seek>> {
+    println!("Received request in /seeks");
+    web::Json(lobby.lock().unwrap().seeks.clone())
+}
+
+/*
+Looks for an opponent. A compatible seek already waiting starts the game right away,
+otherwise this one waits in the list, replacing any earlier seek of the same player
+*/
+async fn post_seek(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    request: web::Json<SeekRequest>,
+) -> Result<web::Json<SeekOutcome>, ApiError> {
+    println!("Received request in /seeks: {:?}", request);
+    if !is_valid_name(&request.name) {
+        return Err(ApiError::bad_request(
+            "invalid_name",
+            "names are up to 32 letters, digits, '-' or '_'",
+        ));
+    }
+    if let (Some(min), Some(max)) = (request.rating_min, request.rating_max) {
+        if min > max {
+            return Err(ApiError::bad_request(
+                "invalid_rating_range",
+                format!("rating_min {} is above rating_max {}", min, max),
+            ));
+        }
+    }
+    let mut seek = Seek {
+        id: 0,
+        name: request.name.clone(),
+        rating: 0.0,
+        minutes: request.minutes,
+        increment: request.increment.unwrap_or(0),
+        rated: request.rated.unwrap_or(false),
+        color: request.color.unwrap_or(ColorChoice::Random),
+        rating_min: request.rating_min,
+        rating_max: request.rating_max,
+    };
+    seek.rating = open_account(&store, &seek.name)
+        .rating(seek.category())
+        .rating;
+
+    let mut lobby = lobby.lock().unwrap();
+    lobby.withdraw(&seek.name);
+    match lobby.seeks.iter().find(|s| s.matches(&seek)).cloned() {
+        Some(other) => {
+            lobby.check_room()?;
+            let id = lobby.pair(&other, &seek.name, seek.color);
+            let table: &mut Table = lobby.table(id)?;
+            save(&store, &table.record);
+            Ok(web::Json(SeekOutcome::Matched {
+                game: Box::new(seated_state(table, &seek.name)),
+            }))
+        }
+        None => {
+            seek.id = lobby.seek_id();
+            lobby.seeks.push(seek.clone());
+            lobby.announce_seeks();
+            Ok(web::Json(SeekOutcome::Waiting { seek }))
+        }
+    }
+}
+
+/*
+Takes up an open seek by hand, as long as the seeker's rating range allows it
+*/
+async fn accept_seek(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+    request: web::Json<SeatRequest>,
+) -> Result<web::Json<SeatedState>, ApiError> {
+    println!("Received request in /seeks/{}/accept: {:?}", id, request);
+    if !is_valid_name(&request.name) {
+        return Err(ApiError::bad_request(
+            "invalid_name",
+            "names are up to 32 letters, digits, '-' or '_'",
+        ));
+    }
+    let mut lobby = lobby.lock().unwrap();
+    let seek: Seek = lobby.seek(*id)?.clone();
+    if seek.name == request.name {
+        return Err(ApiError::conflict(
+            "own_seek",
+            "you can't accept your own seek",
+        ));
+    }
+    let rating = open_account(&store, &request.name)
+        .rating(seek.category())
+        .rating;
+    if !seek.accepts_rating(rating) {
+        return Err(ApiError::forbidden(
+            "rating_out_of_range",
+            format!(
+                "{} is looking for someone else, {:.0} is outside the rating range",
+                seek.name, rating
+            ),
+        ));
+    }
+    lobby.check_room()?;
+    let game_id = lobby.pair(&seek, &request.name, ColorChoice::Random);
+    let table: &mut Table = lobby.table(game_id)?;
+    save(&store, &table.record);
+    Ok(web::Json(seated_state(table, &request.name)))
+}
+
+async fn cancel_seek(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+    request: web::Query<CancelSeekRequest>,
+) -> Result<HttpResponse, ApiError> {
+    println!("Received request in DELETE /seeks/{}: {:?}", id, request);
+    let mut lobby = lobby.lock().unwrap();
+    if lobby.seek(*id)?.name != request.name {
+        return Err(ApiError::forbidden(
+            "not_your_seek",
+            "only whoever posted a seek can cancel it",
+        ));
+    }
+    lobby.seeks.retain(|s| s.id != *id);
+    lobby.announce_seeks();
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn game_pgn(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+) -> Result<HttpResponse, ApiError> {
+    println!("Received request in /games/{}/pgn", id);
+    let lobby = lobby.lock().unwrap();
+    let record: StoredGame = lobby
+        .record(*id, &**store.lock().unwrap())
+        .ok_or_else(|| ApiError::not_found("unknown_game", format!("there's no game {}", id)))?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-chess-pgn")
+        .body(pgn(&record, "Casual game", "-")))
+}
+
+/*
+Opens a position in the board editor, copied from a FEN, a game or the usual start
+*/
+async fn new_draft(
+    lobby: web::Data<SharedLobby>,
+    request: web::Json<NewDraftRequest>,
+) -> Result<web::Json<DraftView>, ApiError> {
+    println!("Received request in /drafts: {:?}", request);
+    let mut lobby = lobby.lock().unwrap();
+    let id = lobby.draft_id();
+    let draft: Draft = match (request.fen.as_deref(), request.game) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::bad_request(
+                "invalid_position",
+                "a draft starts either from a FEN or from a game, not both",
+            ))
+        }
+        // it only has to be readable, making sense comes later
+        (Some(fen), None) => Draft::from_game(
+            id,
+            &Game::from_fen(fen).map_err(|e| ApiError::bad_request("invalid_fen", e))?,
+        ),
+        (None, Some(game_id)) => Draft::from_game(id, &lobby.table(game_id)?.game),
+        (None, None) => Draft::from_game(id, &init_pieces()),
     };
-    let end_pos = Position {
-        column: request.end_pos.chars().next().unwrap(),
-        row: request.end_pos[1..].parse::<i32>().unwrap(),
+    let view = DraftView::from_draft(&draft);
+    lobby.drafts.insert(id, draft);
+    Ok(web::Json(view))
+}
+
+async fn get_draft(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+) -> Result<web::Json<DraftView>, ApiError> {
+    println!("Received request in /drafts/{}", id);
+    let mut lobby = lobby.lock().unwrap();
+    Ok(web::Json(DraftView::from_draft(lobby.draft(*id)?)))
+}
+
+async fn delete_draft(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+) -> Result<HttpResponse, ApiError> {
+    println!("Received request in DELETE /drafts/{}", id);
+    let mut lobby = lobby.lock().unwrap();
+    lobby.draft(*id)?;
+    lobby.drafts.remove(&*id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn place_piece(
+    lobby: web::Data<SharedLobby>,
+    path: web::Path<(u64, String)>,
+    request: web::Json<PlacePieceRequest>,
+) -> Result<web::Json<DraftView>, ApiError> {
+    let (id, square) = path.into_inner();
+    println!(
+        "Received request in /drafts/{}/squares/{}: {:?}",
+        id, square, request
+    );
+    let white = match request.color.as_str() {
+        "white" => true,
+        "black" => false,
+        _ => {
+            return Err(ApiError::bad_request(
+                "invalid_color",
+                format!("{:?} is not white or black", request.color),
+            ))
+        }
     };
-    let err = game.make_move(start_pos, end_pos);
-
-    let mut response: HashMap<String, String> = HashMap::new();
-    // fields: is_valid, is_mate, draw, check
-    response.insert("is_valid".to_string(), err.to_string());
-    response.insert("mate".to_string(), game.is_mate().to_string());
-    response.insert("draw".to_string(), game.is_draw().to_string());
-    response.insert(
-        "check".to_string(),
-        game.is_in_check(game.white_to_move).to_string(),
+    let square = parse_square(&square)?;
+    let mut lobby = lobby.lock().unwrap();
+    let draft: &mut Draft = lobby.draft(id)?;
+    draft
+        .place(square, &request.name, white)
+        .map_err(|e| ApiError::bad_request("invalid_piece", e))?;
+    Ok(web::Json(DraftView::from_draft(draft)))
+}
+
+async fn remove_piece(
+    lobby: web::Data<SharedLobby>,
+    path: web::Path<(u64, String)>,
+) -> Result<web::Json<DraftView>, ApiError> {
+    let (id, square) = path.into_inner();
+    println!(
+        "Received request in DELETE /drafts/{}/squares/{}",
+        id, square
     );
-    for (pos, piece) in game.pieces.iter() {
-        let color = if piece.white {
-            "w".to_string()
-        } else {
-            "b".to_string()
-        };
-        response.insert(
-            format!("{}{}", pos.0, pos.1),
-            format!("{}{}", color, piece.name),
-        );
+    let square = parse_square(&square)?;
+    let mut lobby = lobby.lock().unwrap();
+    let draft: &mut Draft = lobby.draft(id)?;
+    draft.remove(&square);
+    Ok(web::Json(DraftView::from_draft(draft)))
+}
+
+async fn clear_draft(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+) -> Result<web::Json<DraftView>, ApiError> {
+    println!("Received request in /drafts/{}/clear", id);
+    let mut lobby = lobby.lock().unwrap();
+    let draft: &mut Draft = lobby.draft(*id)?;
+    draft.clear();
+    Ok(web::Json(DraftView::from_draft(draft)))
+}
+
+async fn flip_side(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+) -> Result<web::Json<DraftView>, ApiError> {
+    println!("Received request in /drafts/{}/flip", id);
+    let mut lobby = lobby.lock().unwrap();
+    let draft: &mut Draft = lobby.draft(*id)?;
+    draft.flip_side();
+    Ok(web::Json(DraftView::from_draft(draft)))
+}
+
+/*
+Gives or takes away one castling right, K, Q, k or q, or a rook's file for Chess960 positions
+*/
+async fn toggle_castling(
+    lobby: web::Data<SharedLobby>,
+    path: web::Path<(u64, String)>,
+) -> Result<web::Json<DraftView>, ApiError> {
+    let (id, right) = path.into_inner();
+    println!("Received request in /drafts/{}/castling/{}", id, right);
+    let mut letters = right.chars();
+    let letter = match (letters.next(), letters.next()) {
+        (Some(letter), None) => letter,
+        _ => {
+            return Err(ApiError::bad_request(
+                "invalid_castling",
+                format!("{:?} is not a castling right", right),
+            ))
+        }
+    };
+    let mut lobby = lobby.lock().unwrap();
+    let draft: &mut Draft = lobby.draft(id)?;
+    draft
+        .toggle_castling(letter)
+        .map_err(|e| ApiError::bad_request("invalid_castling", e))?;
+    Ok(web::Json(DraftView::from_draft(draft)))
+}
+
+async fn set_en_passant(
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+    request: web::Json<EnPassantRequest>,
+) -> Result<web::Json<DraftView>, ApiError> {
+    println!(
+        "Received request in /drafts/{}/en_passant: {:?}",
+        id, request
+    );
+    let square: Option<Position> = match &request.square {
+        Some(text) => Some(parse_square(text)?),
+        None => None,
+    };
+    let mut lobby = lobby.lock().unwrap();
+    let draft: &mut Draft = lobby.draft(*id)?;
+    draft.en_passant = square;
+    Ok(web::Json(DraftView::from_draft(draft)))
+}
+
+/*
+Starts a game from the draft, as long as the position is one that can be played. The draft stays
+around to set up the next one
+*/
+async fn start_draft(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+    request: web::Json<StartDraftRequest>,
+) -> Result<web::Json<BoardState>, ApiError> {
+    println!("Received request in /drafts/{}/game: {:?}", id, request);
+    let mut lobby = lobby.lock().unwrap();
+    lobby.check_room()?;
+    let start: Game = lobby
+        .draft(*id)?
+        .game()
+        .map_err(|e| ApiError::conflict("invalid_position", e))?;
+    // like any game from a position of its own, it isn't rated
+    let clock: Option<Clock> = lobby.games.clock(request.minutes, request.increment);
+    let game_id = lobby.create_from(clock, false, start);
+    let table: &mut Table = lobby.table(game_id)?;
+    save(&store, &table.record);
+    Ok(web::Json(table.state()))
+}
+
+async fn list_tournaments(lobby: web::Data<SharedLobby>) -> web::Json<Vec<TournamentSummary>> {
+    println!("Received request in /tournaments");
+    web::Json(
+        lobby
+            .lock()
+            .unwrap()
+            .tournaments
+            .values()
+            .map(TournamentSummary::new)
+            .collect(),
+    )
+}
+
+async fn new_tournament(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    request: web::Json<NewTournamentRequest>,
+) -> Result<web::Json<TournamentView>, ApiError> {
+    println!("Received request in /tournaments: {:?}", request);
+    if request.name.trim().is_empty() {
+        return Err(ApiError::bad_request(
+            "invalid_name",
+            "the tournament needs a name",
+        ));
     }
-    response.insert(
-        "to_move".to_string(),
-        if game.white_to_move {
-            "w".to_string()
-        } else {
-            "b".to_string()
-        },
+    if !is_valid_name(&request.organizer) {
+        return Err(ApiError::bad_request(
+            "invalid_name",
+            "names are up to 32 letters, digits, '-' or '_'",
+        ));
+    }
+    let swiss_rounds: u32 = request.rounds.unwrap_or(0);
+    if request.format == Format::Swiss && swiss_rounds == 0 {
+        return Err(ApiError::bad_request(
+            "missing_rounds",
+            "a swiss tournament needs a number of rounds",
+        ));
+    }
+    let mut lobby = lobby.lock().unwrap();
+    let mut tournament = Tournament::new(
+        lobby.tournament_id(),
+        request.name.trim(),
+        &request.organizer,
+        request.format,
     );
-    response.insert("bchosen".to_string(), players.black.clone());
-    response.insert("wchosen".to_string(), players.white.clone());
-    HttpResponse::Ok().json(response)
+    tournament.swiss_rounds = swiss_rounds;
+    tournament.minutes = request.minutes;
+    tournament.increment = request.increment.unwrap_or(0);
+    tournament.rated = request.rated.unwrap_or(false);
+    save_tournament(&store, &tournament);
+    let view = TournamentView::new(&tournament);
+    lobby.tournaments.insert(tournament.id, tournament);
+    Ok(web::Json(view))
 }
 
-async fn game_to_json(
-    game: web::Data<Arc<Mutex<Game>>>,
-    players: web::Data<Arc<Mutex<Players>>>,
-) -> impl Responder {
-    println!("Received request in /boardstate");
-    let game: std::sync::MutexGuard<'_, Game> = game.lock().unwrap();
-    let mut board: HashMap<String, String> = HashMap::new();
-    let players: std::sync::MutexGuard<'_, Players> = players.lock().unwrap();
+/*
+Pairings, results and standings, with the results of games that ended since the last look
+*/
+async fn get_tournament(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+) -> Result<web::Json<TournamentView>, ApiError> {
+    println!("Received request in /tournaments/{}", id);
+    let mut lobby = lobby.lock().unwrap();
+    let tournament: &mut Tournament = lobby.sync_tournament(*id, &**store.lock().unwrap())?;
+    save_tournament(&store, tournament);
+    Ok(web::Json(TournamentView::new(tournament)))
+}
 
-    for (pos, piece) in game.pieces.iter() {
-        let color = if piece.white {
-            "w".to_string()
-        } else {
-            "b".to_string()
-        };
-        board.insert(
-            format!("{}{}", pos.0, pos.1),
-            format!("{}{}", color, piece.name),
-        );
+async fn register(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+    request: web::Json<RegisterRequest>,
+) -> Result<web::Json<TournamentView>, ApiError> {
+    println!(
+        "Received request in /tournaments/{}/entrants: {:?}",
+        id, request
+    );
+    if !is_valid_name(&request.name) {
+        return Err(ApiError::bad_request(
+            "invalid_name",
+            "names are up to 32 letters, digits, '-' or '_'",
+        ));
+    }
+    let kind = request.kind.unwrap_or(EntrantKind::Player);
+    let mut lobby = lobby.lock().unwrap();
+    let tournament: &mut Tournament = lobby.tournament(*id)?;
+    let rating: f64 = match kind {
+        EntrantKind::Player => {
+            open_account(&store, &request.name)
+                .rating(category(tournament.clock().as_ref()))
+                .rating
+        }
+        EntrantKind::Engine => request.rating.unwrap_or(1500.0),
+    };
+    tournament.register(&request.name, kind, rating)?;
+    save_tournament(&store, tournament);
+    Ok(web::Json(TournamentView::new(tournament)))
+}
+
+/*
+Pairs the next round and opens the games between players, who get told over the lobby websocket
+*/
+async fn start_round(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+    request: web::Json<OrganizerRequest>,
+) -> Result<web::Json<TournamentView>, ApiError> {
+    println!(
+        "Received request in /tournaments/{}/rounds: {:?}",
+        id, request
+    );
+    let mut lobby = lobby.lock().unwrap();
+    check_organizer(
+        lobby.sync_tournament(*id, &**store.lock().unwrap())?,
+        &request.name,
+    )?;
+    let result = lobby.start_round(*id);
+    let tournament: &Tournament = lobby.tournament(*id)?;
+    // finding out there's no next round can finish the tournament, keep that too
+    save_tournament(&store, tournament);
+    for game_id in result? {
+        if let Some(table) = lobby.tables.get(&game_id) {
+            save(&store, &table.record);
+        }
     }
+    Ok(web::Json(TournamentView::new(lobby.tournament(*id)?)))
+}
 
-    board.insert(
-        "to_move".to_string(),
-        if game.white_to_move {
-            "w".to_string()
-        } else {
-            "b".to_string()
-        },
+async fn report_result(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+    request: web::Json<TournamentResultRequest>,
+) -> Result<web::Json<TournamentView>, ApiError> {
+    println!(
+        "Received request in /tournaments/{}/results: {:?}",
+        id, request
     );
-    board.insert("bchosen".to_string(), players.black.clone());
-    board.insert("wchosen".to_string(), players.white.clone());
-    HttpResponse::Ok().json(board)
+    let mut lobby = lobby.lock().unwrap();
+    let tournament: &mut Tournament = lobby.tournament(*id)?;
+    check_organizer(tournament, &request.name)?;
+    tournament.set_result(request.round, request.board, &request.result)?;
+    save_tournament(&store, tournament);
+    Ok(web::Json(TournamentView::new(tournament)))
+}
+
+async fn crosstable(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+) -> Result<HttpResponse, ApiError> {
+    println!("Received request in /tournaments/{}/crosstable", id);
+    let mut lobby = lobby.lock().unwrap();
+    let tournament: &mut Tournament = lobby.sync_tournament(*id, &**store.lock().unwrap())?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(tournament.crosstable()))
+}
+
+/*
+Every game of the tournament in one PGN file, results reported by hand come without moves
+*/
+async fn tournament_pgn(
+    lobby: web::Data<SharedLobby>,
+    store: web::Data<Store>,
+    id: web::Path<u64>,
+) -> Result<HttpResponse, ApiError> {
+    println!("Received request in /tournaments/{}/pgn", id);
+    let mut lobby = lobby.lock().unwrap();
+    let store = store.lock().unwrap();
+    let tournament: Tournament = lobby.sync_tournament(*id, &**store)?.clone();
+    let mut text = String::new();
+    for round in &tournament.rounds {
+        for pairing in &round.pairings {
+            let Some(black) = pairing.black else {
+                continue;
+            };
+            let record: StoredGame = match pairing.game_id.and_then(|g| lobby.record(g, &**store)) {
+                Some(record) => record,
+                None => {
+                    let mut record = StoredGame::new(0);
+                    record.white = tournament.entrants[pairing.white].name.clone();
+                    record.black = tournament.entrants[black].name.clone();
+                    record.result = pairing.result.clone();
+                    record.started_at = tournament.created_at;
+                    record
+                }
+            };
+            text.push_str(&pgn(&record, &tournament.name, &round.number.to_string()));
+        }
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-chess-pgn")
+        .body(text))
+}
+
+/*
+Live updates of the lobby: the open seeks, and a game_started event when a seek of name gets paired
+*/
+async fn watch_lobby(
+    req: HttpRequest,
+    stream: web::Payload,
+    lobby: web::Data<SharedLobby>,
+    query: web::Query<WatchRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    println!("Received request in /lobby/watch");
+    let watcher_id = lobby.lock().unwrap().watcher_id();
+    ws::start(
+        GameSocket {
+            id: watcher_id,
+            game_id: None,
+            name: query.into_inner().name,
+            lobby: lobby.get_ref().clone(),
+        },
+        &req,
+        stream,
+    )
+}
+
+/*
+Live updates of a game. Anyone can connect, connections whose name isn't seated count as spectators
+*/
+async fn watch(
+    req: HttpRequest,
+    stream: web::Payload,
+    lobby: web::Data<SharedLobby>,
+    id: web::Path<u64>,
+    query: web::Query<WatchRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    println!("Received request in /watch for game {}", id);
+    let watcher_id = {
+        let mut lobby = lobby.lock().unwrap();
+        lobby.table(*id)?;
+        lobby.watcher_id()
+    };
+    ws::start(
+        GameSocket {
+            id: watcher_id,
+            game_id: Some(*id),
+            name: query.into_inner().name,
+            lobby: lobby.get_ref().clone(),
+        },
+        &req,
+        stream,
+    )
+}
+
+/*
+Nobody moves when out of time, so the flags are checked here every second
+*/
+async fn watch_clocks(lobby: SharedLobby, store: Store) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let mut lobby = lobby.lock().unwrap();
+        for table in lobby.tables.values_mut() {
+            if let Some(result) = table.flag_fell() {
+                finish(&store, &mut table.record, result, Termination::Timeout);
+                table.broadcast();
+                table.computers.clear();
+            }
+        }
+    }
+}
+
+/*
+Picks up every unfinished game from the store, or starts a new one if there's none
+*/
+fn restore_games(store: &dyn GameStore) -> io::Result<Lobby> {
+    let mut lobby = Lobby::new(store.next_id()?);
+    for record in store.unfinished()? {
+        match record
+            .start()
+            .ok()
+            .and_then(|start| Game::replay(&start, &record.moves))
+        {
+            Some(game) => {
+                println!(
+                    "Restored game {} after {} moves",
+                    record.id,
+                    record.moves.len()
+                );
+                lobby.add(Table::new(game, record));
+            }
+            None => println!(
+                "Game {} has an illegal move history, skipping it",
+                record.id
+            ),
+        }
+    }
+    if lobby.tables.is_empty() {
+        lobby.create(None, false);
+    }
+    for tournament in store.tournaments()? {
+        lobby.tournaments.insert(tournament.id, tournament);
+    }
+    Ok(lobby)
+}
+
+/*
+Prints what the chess library logs, like engines that had to be restarted. It goes to stderr so
+the xboard and UCI talk on stdout stays clean, and the web crates' own logging stays out
+*/
+struct LibraryLog;
+impl log::Log for LibraryLog {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("chess") && metadata.level() <= log::Level::Info
+    }
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", record.args());
+        }
+    }
+    fn flush(&self) {}
 }
+static LIBRARY_LOG: LibraryLog = LibraryLog;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    let game: Arc<Mutex<Game>> = Arc::new(Mutex::new(init_pieces()));
-    let players: Arc<Mutex<Players>> = Arc::new(Mutex::new(Players {
-        white: "none".to_string(),
-        black: "none".to_string(),
-    }));
+    if log::set_logger(&LIBRARY_LOG).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+    match Cli::parse().command {
+        None => serve(settings(None, None, None, None)).await,
+        Some(Command::Serve {
+            config,
+            host,
+            port,
+            static_dir,
+        }) => serve(settings(config, host, port, static_dir)).await,
+        Some(Command::Play {
+            vs_engine,
+            black,
+            depth,
+        }) => cli::play(vs_engine, black, depth),
+        Some(Command::Perft {
+            fen,
+            depth,
+            divide,
+            variant,
+        }) => cli::run_perft(&fen, &variant, depth, divide),
+        Some(Command::Analyse {
+            input,
+            depth,
+            lines,
+        }) => cli::run_analyse(&input, depth, lines),
+        Some(Command::Convert { input, output }) => cli::run_convert(&input, output.as_deref()),
+        Some(Command::Bestmove {
+            fen,
+            depth,
+            movetime,
+        }) => cli::run_bestmove(&fen, depth, movetime),
+        Some(Command::Match { config }) => cli::run_match(&config),
+        // for GUIs that speak the Chess Engine Communication Protocol
+        Some(Command::Xboard { book }) => cli::run_xboard(book.as_deref()),
+        Some(Command::Book {
+            pgn,
+            book,
+            min_count,
+            max_ply,
+        }) => cli::run_book(&pgn, &book, min_count, max_ply),
+    }
+}
+
+/*
+The server's settings: the config file, then the environment, then the command line, checked
+before anything starts. Exits with every problem listed when they don't add up
+*/
+fn settings(
+    path: Option<PathBuf>,
+    host: Option<String>,
+    port: Option<u16>,
+    static_dir: Option<PathBuf>,
+) -> Config {
+    let mut config = Config::load(path.as_deref()).unwrap_or_else(|e| bad_configuration(e));
+    if let Some(host) = host {
+        config.server.host = host;
+    }
+    if let Some(port) = port {
+        config.server.port = port;
+    }
+    if let Some(dir) = static_dir {
+        config.server.static_dir = dir;
+    }
+    if let Err(e) = config.validate() {
+        bad_configuration(e);
+    }
+    config
+}
+
+fn bad_configuration(problems: String) -> ! {
+    eprintln!("bad configuration:\n{}", problems);
+    std::process::exit(2)
+}
 
-    let ip = "127.0.0.1";
+/*
+Runs the HTTP server the way config says
+*/
+async fn serve(config: Config) -> std::io::Result<()> {
+    let store: Box<dyn GameStore> = match config.storage.backend {
+        Backend::File => Box::new(FileStore::open(&config.storage.path)?),
+        Backend::Memory => Box::new(MemoryStore::default()),
+    };
+    let tls = config
+        .server
+        .tls
+        .as_ref()
+        .map(|tls| config::load_tls(tls).unwrap_or_else(|e| bad_configuration(e)));
+    let mut restored: Lobby = restore_games(store.as_ref())?;
+    restored.engines = computer::load_engines("engines.json")?;
+    for engine in restored.engines.iter_mut() {
+        engine.defaults = BTreeMap::from([
+            ("Threads".to_string(), config.engine.threads.to_string()),
+            ("Hash".to_string(), config.engine.hash_mb.to_string()),
+        ]);
+    }
+    restored.games = config.games.clone();
+    // Syzygy tables in ./tablebases end games that reach them and help the built-in engine
+    if std::path::Path::new("tablebases").is_dir() {
+        let tables = tablebase::load("tablebases")?;
+        println!(
+            "{} tablebase files, up to {} pieces",
+            tables.table_count(),
+            tables.max_pieces()
+        );
+    }
+    let running: Vec<u64> = restored.tables.keys().copied().collect();
+    let lobby: SharedLobby = Arc::new(Mutex::new(restored));
+    let store: Store = Arc::new(Mutex::new(store));
+    tokio::spawn(watch_clocks(lobby.clone(), store.clone()));
+    // computers that were to move when the server went down pick up where they were
+    for id in running {
+        wake_computer(lobby.clone(), store.clone(), id);
+    }
 
-    let addr = format!("http://{}:8080", ip);
-    println!("Server running at {}", addr);
+    let ServerConfig {
+        host,
+        port,
+        static_dir,
+        allowed_origins,
+        ..
+    } = config.server;
+    println!(
+        "Server running at {}://{}:{}",
+        if tls.is_some() { "https" } else { "http" },
+        host,
+        port
+    );
     // Start the HTTP server
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
+        let cors = if allowed_origins.iter().any(|o| o == "*") {
+            Cors::default().allow_any_origin()
+        } else {
+            allowed_origins
+                .iter()
+                .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        };
         App::new()
-            .wrap(
-                Cors::default()
-                    .allow_any_origin()
-                    .allow_any_method()
-                    .allow_any_header(),
+            .wrap(cors.allow_any_method().allow_any_header())
+            .app_data(web::Data::new(lobby.clone()))
+            .app_data(web::Data::new(store.clone()))
+            // malformed bodies and query strings get the same error shape as everything else
+            .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                error::Error::from(ApiError::bad_request("invalid_json", err.to_string()))
+            }))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                error::Error::from(ApiError::bad_request("invalid_query", err.to_string()))
+            }))
+            .app_data(web::PathConfig::default().error_handler(|err, _| {
+                error::Error::from(ApiError::bad_request("invalid_path", err.to_string()))
+            }))
+            .service(
+                web::scope("/api/v1")
+                    .route("/games", web::get().to(list_games))
+                    .route("/games", web::post().to(new_game))
+                    .route("/games/{id}", web::get().to(game_to_json))
+                    .route("/games/{id}/move", web::post().to(move_piece))
+                    .route("/games/{id}/legalmoves", web::get().to(get_legal_moves))
+                    .route("/games/{id}/reset", web::post().to(reset))
+                    .route("/games/{id}/resign", web::post().to(resign))
+                    .route("/games/{id}/seats/{color}", web::post().to(choose_seat))
+                    .route(
+                        "/games/{id}/seats/{color}/computer",
+                        web::post().to(seat_computer),
+                    )
+                    .route("/games/{id}/bestmove", web::get().to(engine_move))
+                    .route("/games/{id}/tablebase", web::get().to(game_tablebase))
+                    .route("/tablebase", web::get().to(tablebase_position))
+                    .route("/games/{id}/analysis", web::get().to(analyse_game))
+                    .route("/games/{id}/analysis/watch", web::get().to(watch_analysis))
+                    .route("/analysis", web::get().to(analyse_position))
+                    .route("/analysis/watch", web::get().to(watch_analysis))
+                    .route("/games/{id}/watch", web::get().to(watch))
+                    .route("/games/{id}/pgn", web::get().to(game_pgn))
+                    .route("/games/{id}/review", web::get().to(game_review))
+                    .route("/games/{id}/review/pgn", web::get().to(game_review_pgn))
+                    .route("/drafts", web::post().to(new_draft))
+                    .route("/drafts/{id}", web::get().to(get_draft))
+                    .route("/drafts/{id}", web::delete().to(delete_draft))
+                    .route("/drafts/{id}/squares/{square}", web::put().to(place_piece))
+                    .route(
+                        "/drafts/{id}/squares/{square}",
+                        web::delete().to(remove_piece),
+                    )
+                    .route("/drafts/{id}/clear", web::post().to(clear_draft))
+                    .route("/drafts/{id}/flip", web::post().to(flip_side))
+                    .route(
+                        "/drafts/{id}/castling/{right}",
+                        web::post().to(toggle_castling),
+                    )
+                    .route("/drafts/{id}/en_passant", web::put().to(set_en_passant))
+                    .route("/drafts/{id}/game", web::post().to(start_draft))
+                    .route("/seeks", web::get().to(list_seeks))
+                    .route("/seeks", web::post().to(post_seek))
+                    .route("/seeks/{id}", web::delete().to(cancel_seek))
+                    .route("/seeks/{id}/accept", web::post().to(accept_seek))
+                    .route("/lobby/watch", web::get().to(watch_lobby))
+                    .route("/tournaments", web::get().to(list_tournaments))
+                    .route("/tournaments", web::post().to(new_tournament))
+                    .route("/tournaments/{id}", web::get().to(get_tournament))
+                    .route("/tournaments/{id}/entrants", web::post().to(register))
+                    .route("/tournaments/{id}/rounds", web::post().to(start_round))
+                    .route("/tournaments/{id}/results", web::post().to(report_result))
+                    .route("/tournaments/{id}/crosstable", web::get().to(crosstable))
+                    .route("/tournaments/{id}/pgn", web::get().to(tournament_pgn))
+                    .route("/engines", web::get().to(list_engines))
+                    .route("/archive", web::get().to(archive))
+                    .route("/players/{name}", web::get().to(player))
+                    .route("/leaderboard", web::get().to(get_leaderboard)),
             )
-            .app_data(web::Data::new(game.clone()))
-            .app_data(web::Data::new(players.clone()))
-            //
-            .route("/boardstate", web::get().to(game_to_json))
-            .route("/movepiece", web::post().to(move_piece))
-            .route("/reset", web::get().to(reset))
-            .route("/black", web::post().to(choose_black))
-            .route("/white", web::post().to(choose_white))
-            .service(Files::new("/", "./ui").index_file("index.html"))
-    })
-    .bind((ip, 8080))?
+            .service(Files::new("/", &static_dir).index_file("index.html"))
+    });
+    match tls {
+        Some(tls) => server.bind_rustls_0_23((host, port), tls)?,
+        None => server.bind((host, port))?,
+    }
     .run()
     .await
 }
-
-async fn choose_black(
-    players: web::Data<Arc<Mutex<Players>>>,
-    request: web::Json<ColorRequest>,
-) -> impl Responder {
-    println!("Received request in /black");
-    let mut pla: std::sync::MutexGuard<'_, Players> = players.lock().unwrap();
-    let mut map: HashMap<String, String> = HashMap::new();
-
-    if pla.black != "none" {
-        map.insert("status".to_string(), "taken".to_string());
-        HttpResponse::Ok().json(map)
-    } else {
-        pla.black = request.name.clone();
-        map.insert("status".to_string(), "chosen".to_string());
-        HttpResponse::Ok().json(map)
-    }
-}
-async fn choose_white(
-    players: web::Data<Arc<Mutex<Players>>>,
-    request: web::Json<ColorRequest>,
-) -> impl Responder {
-    println!("Received request in /white");
-    let mut pla: std::sync::MutexGuard<'_, Players> = players.lock().unwrap();
-    let mut map: HashMap<String, String> = HashMap::new();
-
-    if pla.white != "none" {
-        map.insert("status".to_string(), "taken".to_string());
-        HttpResponse::Ok().json(map)
-    } else {
-        pla.white = request.name.clone();
-        map.insert("status".to_string(), "chosen".to_string());
-        HttpResponse::Ok().json(map)
-    }
-}
@@ -0,0 +1,91 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use chess::clock::Clock;
+
+use crate::api::SeatedState;
+use crate::rating::category;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorChoice {
+    White,
+    Black,
+    Random,
+}
+
+/*
+Someone waiting for an opponent with a given time control
+*/
+#[derive(Clone, Serialize, Debug)]
+pub struct Seek {
+    pub id: u64,
+    pub name: String,
+    pub rating: f64, // in the category of the seek, when it was posted
+    pub minutes: Option<u64>,
+    pub increment: u64,
+    pub rated: bool,
+    pub color: ColorChoice,
+    pub rating_min: Option<f64>,
+    pub rating_max: Option<f64>,
+}
+impl Seek {
+    pub fn clock(&self) -> Option<Clock> {
+        self.minutes
+            .map(|minutes| Clock::new(minutes * 60_000, self.increment * 1000))
+    }
+    pub fn category(&self) -> &'static str {
+        category(self.clock().as_ref())
+    }
+    /*
+    Whether someone rated rating is inside the range this seek asked for
+    */
+    pub fn accepts_rating(&self, rating: f64) -> bool {
+        self.rating_min.is_none_or(|min| rating >= min)
+            && self.rating_max.is_none_or(|max| rating <= max)
+    }
+    /*
+    Same game wanted on both sides: time control, rated or not, colors that fit and ratings in range
+    */
+    pub fn matches(&self, other: &Seek) -> bool {
+        self.name != other.name
+            && self.minutes == other.minutes
+            && self.increment == other.increment
+            && self.rated == other.rated
+            && !(self.color != ColorChoice::Random && self.color == other.color)
+            && self.accepts_rating(other.rating)
+            && other.accepts_rating(self.rating)
+    }
+}
+
+/*
+Who gets white between the seek's owner and opponent, opponent_color being what they asked for
+*/
+pub fn pick_colors<'a>(
+    seek: &'a Seek,
+    opponent: &'a str,
+    opponent_color: ColorChoice,
+) -> (&'a str, &'a str) {
+    let seeker_white: bool = match (seek.color, opponent_color) {
+        (ColorChoice::White, _) => true,
+        (ColorChoice::Black, _) => false,
+        (ColorChoice::Random, ColorChoice::White) => false,
+        (ColorChoice::Random, ColorChoice::Black) => true,
+        (ColorChoice::Random, ColorChoice::Random) => rand::thread_rng().gen_bool(0.5),
+    };
+    if seeker_white {
+        (&seek.name, opponent)
+    } else {
+        (opponent, &seek.name)
+    }
+}
+
+/*
+What posting a seek led to: waiting in the list, or straight into a game
+*/
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SeekOutcome {
+    Waiting { seek: Seek },
+    Matched { game: Box<SeatedState> },
+}
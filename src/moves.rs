@@ -0,0 +1,101 @@
+use std::fmt;
+
+use crate::piece::PROMOTIONS;
+use crate::position::Position;
+
+#[derive(Clone)]
+pub struct Move {
+    pub from: Position, // same as to for drops
+    pub to: Position,
+    pub promotion: Option<String>, // name of the piece a pawn turns into
+    pub drop: Option<String>,      // name of the pocket piece put on to, crazyhouse only
+}
+impl Move {
+    /*
+    Putting the pocket piece name on square
+    */
+    pub fn drop(name: &str, square: Position) -> Move {
+        Move {
+            from: square.clone(),
+            to: square,
+            promotion: None,
+            drop: Some(name.to_string()),
+        }
+    }
+    /*
+    Parses "E2E4", "E7E8Q" or the drop "N@F3" (case insensitive), the same forms Display writes
+    */
+    pub fn parse(text: &str) -> Option<Move> {
+        let text = text.trim();
+        if !text.is_ascii() {
+            return None;
+        }
+        if let Some((piece, square)) = text.split_once('@') {
+            let mut letters = piece.chars();
+            let name = match (letters.next(), letters.next()) {
+                (Some(letter), None) => piece_name(letter)?,
+                _ => return None,
+            };
+            return Some(Move::drop(name, Position::parse(square)?));
+        }
+        if text.len() != 4 && text.len() != 5 {
+            return None;
+        }
+        let promotion: Option<String> = match text[4..].chars().next() {
+            None => None,
+            Some(letter) => Some(
+                piece_name(letter)
+                    .filter(|n| is_promotion_piece(n))?
+                    .to_string(),
+            ),
+        };
+        Some(Move {
+            from: Position::parse(&text[0..2])?,
+            to: Position::parse(&text[2..4])?,
+            promotion,
+            drop: None,
+        })
+    }
+}
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.drop {
+            return write!(f, "{}@{}", piece_letter(name), self.to);
+        }
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(name) = &self.promotion {
+            write!(f, "{}", piece_letter(name))?;
+        }
+        Ok(())
+    }
+}
+
+/*
+Upper case letter of a piece, P for pawns
+*/
+pub fn piece_letter(name: &str) -> char {
+    if name == "knight" {
+        'N'
+    } else {
+        name.chars().next().unwrap_or('Q').to_ascii_uppercase()
+    }
+}
+
+/*
+The piece a letter stands for, either case
+*/
+pub fn piece_name(letter: char) -> Option<&'static str> {
+    match letter.to_ascii_lowercase() {
+        'p' => Some("pawn"),
+        'n' => Some("knight"),
+        'b' => Some("bishop"),
+        'r' => Some("rook"),
+        'q' => Some("queen"),
+        'k' => Some("king"),
+        _ => None,
+    }
+}
+
+pub fn is_promotion_piece(name: &str) -> bool {
+    PROMOTIONS.contains(&name)
+}
@@ -0,0 +1,397 @@
+use std::fmt::Write;
+
+use crate::aux_func::letter_to_int;
+use crate::error::Error;
+use crate::fen::START_FEN;
+use crate::game::{init_pieces, Game};
+use crate::moves::{self, Move};
+use crate::piece::Piece;
+use crate::variant::{self, Variant, STANDARD};
+
+// PGN lines are kept under 80 characters, as the standard asks
+const LINE_WIDTH: usize = 79;
+
+fn piece_letter(name: &str) -> &'static str {
+    match name {
+        "knight" => "N",
+        "bishop" => "B",
+        "rook" => "R",
+        "queen" => "Q",
+        "king" => "K",
+        _ => "",
+    }
+}
+
+fn square(column: char, row: i32) -> String {
+    format!("{}{}", column.to_ascii_lowercase(), row)
+}
+
+/*
+Standard algebraic notation of mov, before it's played on game: "Nf3", "exd6", "O-O", "e8=Q+"
+or the drop "N@f3". None if the move isn't legal there
+*/
+pub fn san(game: &Game, mov: &Move) -> Option<String> {
+    if let Some(name) = &mov.drop {
+        let mut after: Game = game.clone();
+        after.play(mov).ok()?;
+        let mut text = format!(
+            "{}@{}",
+            moves::piece_letter(name),
+            square(mov.to.column, mov.to.row)
+        );
+        text.push_str(check_mark(&after));
+        return Some(text);
+    }
+    let piece: &Piece = game.pieces.get(&(mov.from.column, mov.from.row))?;
+    let mut after: Game = game.clone();
+    after.play(mov).ok()?;
+    let mut text = String::new();
+    let columns_moved = letter_to_int(mov.to.column) - letter_to_int(mov.from.column);
+    if let Some(castling) = game.castling_move(mov) {
+        text.push_str(if castling.short { "O-O" } else { "O-O-O" });
+    } else {
+        let captures = game.pieces.contains_key(&(mov.to.column, mov.to.row))
+            || (piece.name == "pawn" && columns_moved != 0);
+        text.push_str(piece_letter(&piece.name));
+        if piece.name == "pawn" {
+            if captures {
+                text.push(mov.from.column.to_ascii_lowercase());
+            }
+        } else {
+            // other pieces of the same kind that could go to the same square
+            let rivals: Vec<&Piece> = game
+                .pieces
+                .values()
+                .filter(|p| {
+                    p.name == piece.name
+                        && p.white == piece.white
+                        && !p.position.equals(&piece.position)
+                        && game
+                            .legal_moves_from(&p.position)
+                            .iter()
+                            .any(|m| m.to.equals(&mov.to))
+                })
+                .collect();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|p| p.position.column != mov.from.column) {
+                    text.push(mov.from.column.to_ascii_lowercase());
+                } else if rivals.iter().all(|p| p.position.row != mov.from.row) {
+                    text.push_str(&mov.from.row.to_string());
+                } else {
+                    text.push_str(&square(mov.from.column, mov.from.row));
+                }
+            }
+        }
+        if captures {
+            text.push('x');
+        }
+        text.push_str(&square(mov.to.column, mov.to.row));
+        if let Some(name) = &after.history.last().unwrap().promotion {
+            text.push('=');
+            text.push_str(piece_letter(name));
+        }
+    }
+    text.push_str(check_mark(&after));
+    Some(text)
+}
+
+/*
+'#' or '+' when the move that led to after mates or checks
+*/
+fn check_mark(after: &Game) -> &'static str {
+    if after.is_mate() == 1 {
+        "#"
+    } else if after.is_in_check(after.white_to_move) {
+        "+"
+    } else {
+        ""
+    }
+}
+
+/*
+The legal move that text names in SAN on game. Check marks and annotations don't matter,
+castling can be written with zeros and promotions without the '='
+*/
+pub fn parse_san(game: &Game, text: &str) -> Option<Move> {
+    let mut wanted: String = text
+        .trim_end_matches(['+', '#', '!', '?'])
+        .replace('0', "O");
+    let bytes = wanted.as_bytes();
+    if bytes.len() >= 3
+        && bytes[bytes.len() - 2].is_ascii_digit()
+        && "NBRQ".contains(bytes[bytes.len() - 1] as char)
+    {
+        wanted.insert(wanted.len() - 1, '=');
+    }
+    // drops are written with the pawn's letter too, some leave it out
+    if wanted.starts_with('@') {
+        wanted.insert(0, 'P');
+    }
+    let castles = wanted.starts_with("O-O");
+    game.legal_moves().into_iter().find(|mov| {
+        // only spell out the moves that could be it
+        (castles || wanted.contains(&square(mov.to.column, mov.to.row)))
+            && san(game, mov).is_some_and(|san| san.trim_end_matches(['+', '#']) == wanted)
+    })
+}
+
+/*
+A game read from a PGN file, moves still in SAN
+*/
+#[derive(Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: String,
+}
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+    /*
+    Where the game starts, from its FEN tag when it has one
+    */
+    pub fn start(&self) -> Result<Game, Error> {
+        match self.tag("FEN") {
+            Some(fen) => Game::from_fen(fen),
+            None => Ok(init_pieces()),
+        }
+        .map(|mut game| {
+            // Chess960 games that start like normal chess only say so here
+            if let Some(tag) = self.tag("Variant") {
+                if tag.eq_ignore_ascii_case("chess960") {
+                    game.chess960 = true;
+                } else {
+                    game.variant = variant::by_pgn_name(tag);
+                }
+            }
+            game
+        })
+    }
+    /*
+    The moves in "E2E4" form from where the game starts, or which one isn't legal
+    */
+    pub fn coordinate_moves(&self, start: &Game) -> Result<Vec<String>, Error> {
+        let mut game: Game = start.clone();
+        let mut moves: Vec<String> = Vec::new();
+        for (ply, text) in self.moves.iter().enumerate() {
+            let Some(mov) = parse_san(&game, text) else {
+                return Err(Error::Pgn(format!(
+                    "{} at ply {} isn't a legal move",
+                    text,
+                    ply + 1
+                )));
+            };
+            let _ = game.play(&mov);
+            moves.push(mov.to_string());
+        }
+        Ok(moves)
+    }
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((
+        name.to_string(),
+        value.replace("\\\"", "\"").replace("\\\\", "\\"),
+    ))
+}
+
+/*
+Moves and result out of movetext, leaving out comments, variations, NAGs and move numbers
+*/
+fn read_movetext(text: &str, game: &mut PgnGame) {
+    let mut plain = String::new();
+    let mut comment = false;
+    let mut rest_of_line = false;
+    let mut variations = 0;
+    for c in text.chars() {
+        let skipped = match c {
+            '\n' if rest_of_line => {
+                rest_of_line = false;
+                true
+            }
+            _ if rest_of_line => true,
+            '}' if comment => {
+                comment = false;
+                true
+            }
+            _ if comment => true,
+            '{' => {
+                comment = true;
+                true
+            }
+            ';' => {
+                rest_of_line = true;
+                true
+            }
+            '(' => {
+                variations += 1;
+                true
+            }
+            ')' => {
+                variations -= 1;
+                true
+            }
+            _ => variations > 0,
+        };
+        // what's left out still separates the moves around it
+        plain.push(if skipped { ' ' } else { c });
+    }
+    for token in plain.split_whitespace() {
+        if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+            game.result = token.to_string();
+            continue;
+        }
+        let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if !token.is_empty() && !token.starts_with('$') {
+            game.moves.push(token.to_string());
+        }
+    }
+}
+
+/*
+Every game in a PGN file
+*/
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games: Vec<PgnGame> = Vec::new();
+    let mut game = PgnGame::default();
+    let mut movetext = String::new();
+    for line in text.lines() {
+        if line.starts_with('[') {
+            // tags after some movetext start the next game
+            if !movetext.trim().is_empty() {
+                read_movetext(&movetext, &mut game);
+                games.push(std::mem::take(&mut game));
+                movetext.clear();
+            }
+            if let Some(tag) = parse_tag(line) {
+                game.tags.push(tag);
+            }
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    if !movetext.trim().is_empty() || !game.tags.is_empty() {
+        read_movetext(&movetext, &mut game);
+        games.push(game);
+    }
+    games
+}
+
+/*
+SAN of a game given in "E2E4" form from start, stops at the first move that doesn't apply
+*/
+pub fn san_moves_from(start: &Game, moves: &[String]) -> Vec<String> {
+    let mut game: Game = start.clone();
+    let mut sans: Vec<String> = Vec::new();
+    for text in moves {
+        let Some(mov) = Move::parse(text) else {
+            break;
+        };
+        let Some(san) = san(&game, &mov) else {
+            break;
+        };
+        let _ = game.play(&mov);
+        sans.push(san);
+    }
+    sans
+}
+
+/*
+Moves with their numbers in front, for a line starting at move number with white (or black)
+to play
+*/
+pub fn numbered(number: u32, white: bool, sans: &[String]) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut number = number;
+    let mut white = white;
+    for (i, san) in sans.iter().enumerate() {
+        if white {
+            tokens.push(format!("{}.", number));
+        } else if i == 0 {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(san.clone());
+        if !white {
+            number += 1;
+        }
+        white = !white;
+    }
+    tokens
+}
+
+/*
+Tokens joined by spaces, wrapped to the PGN line width
+*/
+pub fn wrap(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len > 0 && line_len + 1 + token.len() > LINE_WIDTH {
+            text.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            text.push(' ');
+            line_len += 1;
+        }
+        line_len += token.len();
+        text.push_str(token);
+    }
+    text
+}
+
+/*
+Move numbers and moves wrapped to the PGN line width, ending with the result
+*/
+pub fn movetext(start: &Game, sans: &[String], result: &str) -> String {
+    let mut tokens: Vec<String> = numbered(start.fullmove_number, start.white_to_move, sans);
+    tokens.push(result.to_string());
+    wrap(&tokens)
+}
+
+/*
+A PGN game with the given tags, Result added last and SetUp/FEN when it doesn't start from
+the usual position
+*/
+pub fn write_pgn(tags: &[(&str, String)], start: &Game, moves: &[String], result: &str) -> String {
+    let mut text = tag_section(tags, start, result);
+    text.push_str(&movetext(start, &san_moves_from(start, moves), result));
+    text.push_str("\n\n");
+    text
+}
+
+/*
+The tag pairs of a game and the empty line after them
+*/
+pub fn tag_section(tags: &[(&str, String)], start: &Game, result: &str) -> String {
+    let mut tags: Vec<(&str, String)> = tags.to_vec();
+    tags.push(("Result", result.to_string()));
+    if start.chess960 {
+        tags.push(("Variant", "Chess960".to_string()));
+    } else if start.variant.name() != STANDARD.name() {
+        tags.push(("Variant", start.variant.pgn_name().to_string()));
+    }
+    let fen = start.to_fen();
+    if fen != START_FEN {
+        tags.push(("SetUp", "1".to_string()));
+        tags.push(("FEN", fen));
+    }
+    let mut text = String::new();
+    for (tag, value) in tags {
+        let _ = writeln!(
+            text,
+            "[{} \"{}\"]",
+            tag,
+            value.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+    }
+    text.push('\n');
+    text
+}
@@ -0,0 +1,138 @@
+use crate::game::Game;
+use crate::moves::Move;
+
+/*
+How many move sequences depth plies long there are from game, the usual way to check move
+generation against known counts
+*/
+pub fn perft(game: &Game, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves: Vec<Move> = game.legal_moves();
+    // the leaves don't need playing
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .iter()
+        .map(|mov| {
+            let mut next: Game = game.clone();
+            let _ = next.play(mov);
+            perft(&next, depth - 1)
+        })
+        .sum()
+}
+
+/*
+Perft split by the first move, to find which one a wrong count comes from
+*/
+pub fn divide(game: &Game, depth: u32) -> Vec<(Move, u64)> {
+    game.legal_moves()
+        .into_iter()
+        .map(|mov| {
+            let mut next: Game = game.clone();
+            let _ = next.play(&mov);
+            let count = perft(&next, depth.saturating_sub(1));
+            (mov, count)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::START_FEN;
+    use crate::variant;
+
+    /*
+    Perft at every depth up to depth, for game played by the rules of variant
+    */
+    fn counts(fen: &str, variant: &str, depth: u32) -> Vec<u64> {
+        let mut game = Game::from_fen(fen).unwrap();
+        game.variant = variant::by_name(variant).unwrap();
+        (1..=depth).map(|depth| perft(&game, depth)).collect()
+    }
+
+    #[test]
+    fn standard() {
+        assert_eq!(counts(START_FEN, "standard", 4), [20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn kiwipete() {
+        // castling both ways, en passant, promotions and pins from the first ply
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(counts(fen, "standard", 3), [48, 2039, 97862]);
+    }
+
+    #[test]
+    fn more_standard_positions() {
+        // en passant out of a rook's pin along the rank
+        let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+        assert_eq!(counts(fen, "standard", 4), [14, 191, 2812, 43238]);
+        // promotions with captures, and castling black still has
+        let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        assert_eq!(counts(fen, "standard", 3), [6, 264, 9467]);
+        let fen = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+        assert_eq!(counts(fen, "standard", 3), [44, 1486, 62379]);
+    }
+
+    #[test]
+    fn chess960() {
+        // from the Chess960 perft suite, castling rights in Shredder-FEN
+        let positions = [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471],
+            ),
+        ];
+        for (fen, expected) in positions {
+            assert_eq!(counts(fen, "standard", 3), expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn atomic() {
+        // by ply 4 captures blow up more than the piece taken
+        assert_eq!(counts(START_FEN, "atomic", 4), [20, 400, 8902, 197326]);
+        let fen = "rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq - 0 1";
+        assert_eq!(counts(fen, "atomic", 3), [40, 1238, 45237]);
+    }
+
+    // the next three only end games differently, no king reaches the centre or gets checked
+    // three times this early, so the counts are the usual ones
+
+    #[test]
+    fn king_of_the_hill() {
+        assert_eq!(
+            counts(START_FEN, "king_of_the_hill", 4),
+            [20, 400, 8902, 197281]
+        );
+    }
+
+    #[test]
+    fn three_check() {
+        assert_eq!(counts(START_FEN, "three_check", 4), [20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn crazyhouse() {
+        // the first capture comes at ply 3, and a drop with what it took at ply 5
+        assert_eq!(counts(START_FEN, "crazyhouse", 4), [20, 400, 8902, 197281]);
+        // every kind of piece in both pockets: 62 squares for each, 48 for pawns
+        let fen = "2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1";
+        assert_eq!(counts(fen, "crazyhouse", 2), [301, 75353]);
+        // nothing in hand yet, but captures two plies in drop on the third
+        let fen = "r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPP/R1BQK2R[] b KQkq - 0 1";
+        assert_eq!(counts(fen, "crazyhouse", 3), [42, 1347, 58057]);
+    }
+}
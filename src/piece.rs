@@ -8,4 +8,59 @@ pub struct Piece {
     pub ways_to_move: Vec<(i32, i32, bool)>, //  columns, rows, allows multiple moves
 }
 impl Piece {
+    /*
+    Builds a piece with the moves it's allowed to make, None if the name isn't a piece
+    */
+    pub fn new(name: &str, white: bool, position: Position) -> Option<Piece> {
+        let ways_to_move: Vec<(i32, i32, bool)> = match name {
+            "pawn" if white => vec![(0, 1, false), (1, 1, false), (-1, 1, false), (0, 2, false)],
+            "pawn" => vec![
+                (0, -1, false),
+                (-1, -1, false),
+                (1, -1, false),
+                (0, -2, false),
+            ],
+            "rook" => vec![(-1, 0, true), (0, -1, true), (1, 0, true), (0, 1, true)],
+            "knight" => vec![
+                (2, 1, false),
+                (2, -1, false),
+                (-2, 1, false),
+                (-2, -1, false),
+                (1, 2, false),
+                (-1, 2, false),
+                (1, -2, false),
+                (-1, -2, false),
+            ],
+            "bishop" => vec![(1, 1, true), (1, -1, true), (-1, 1, true), (-1, -1, true)],
+            "queen" => vec![
+                (1, 1, true),
+                (1, -1, true),
+                (-1, 1, true),
+                (-1, -1, true),
+                (1, 0, true),
+                (-1, 0, true),
+                (0, 1, true),
+                (0, -1, true),
+            ],
+            "king" => vec![
+                (1, 1, false),
+                (1, -1, false),
+                (-1, 1, false),
+                (-1, -1, false),
+                (1, 0, true),
+                (-1, 0, true),
+                (0, 1, false),
+                (0, -1, false),
+            ],
+            _ => return None,
+        };
+        Some(Piece {
+            name: name.to_string(),
+            white,
+            position,
+            ways_to_move,
+        })
+    }
 }
+
+pub const PROMOTIONS: [&str; 4] = ["queen", "rook", "bishop", "knight"];
@@ -1,4 +1,5 @@
 use crate::aux_func::{int_to_letter, letter_to_int};
+use std::fmt;
 
 #[derive(Clone)]
 pub struct Position {
@@ -24,4 +25,23 @@ impl Position {
             row: next_row,
         }
     }
+    /*
+    Parses a square like "E2" (case insensitive), None if it isn't on the board
+    */
+    pub fn parse(square: &str) -> Option<Position> {
+        let mut chars = square.trim().chars();
+        let column = chars.next()?.to_ascii_uppercase();
+        let row = chars.as_str().parse::<i32>().ok()?;
+        let pos = Position { column, row };
+        if pos.is_within_bounds() {
+            Some(pos)
+        } else {
+            None
+        }
+    }
+}
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.column, self.row)
+    }
 }
@@ -0,0 +1,285 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::io;
+
+use chess::clock::Clock;
+
+use crate::storage::{GameStore, StoredGame};
+
+// Glicko-2 works on its own scale, this converts to and from the usual 1500 based one
+const SCALE: f64 = 173.7178;
+// how much the volatility is allowed to change, 0.3 to 1.2 per Glickman
+const TAU: f64 = 0.5;
+const EPSILON: f64 = 0.000001;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+impl Default for Rating {
+    fn default() -> Rating {
+        Rating {
+            rating: 1500.0,
+            deviation: 350.0,
+            volatility: 0.06,
+        }
+    }
+}
+impl Rating {
+    /*
+    Rating after one game against opponent, score is 1 for a win, 0.5 for a draw and 0 for a loss.
+    Every game is its own rating period, like most online servers do
+    */
+    pub fn after_game(&self, opponent: &Rating, score: f64) -> Rating {
+        let mu = (self.rating - 1500.0) / SCALE;
+        let phi = self.deviation / SCALE;
+        let mu_j = (opponent.rating - 1500.0) / SCALE;
+        let phi_j = opponent.deviation / SCALE;
+
+        let g = 1.0 / (1.0 + 3.0 * phi_j * phi_j / (PI * PI)).sqrt();
+        let expected = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+        let v = 1.0 / (g * g * expected * (1.0 - expected));
+        let delta = v * g * (score - expected);
+
+        let volatility = new_volatility(phi, v, delta, self.volatility);
+        let phi_star = (phi * phi + volatility * volatility).sqrt();
+        let new_phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+        let new_mu = mu + new_phi * new_phi * g * (score - expected);
+
+        Rating {
+            rating: new_mu * SCALE + 1500.0,
+            deviation: new_phi * SCALE,
+            volatility,
+        }
+    }
+    /*
+    Few games played yet, the number isn't worth much
+    */
+    pub fn is_provisional(&self) -> bool {
+        self.deviation > 110.0
+    }
+}
+
+/*
+Step 5 of the Glicko-2 paper, solved with the Illinois algorithm
+*/
+fn new_volatility(phi: f64, v: f64, delta: f64, sigma: f64) -> f64 {
+    let a = (sigma * sigma).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        ex * (delta * delta - phi * phi - v - ex) / (2.0 * (phi * phi + v + ex).powi(2))
+            - (x - a) / (TAU * TAU)
+    };
+    let mut big_a = a;
+    let mut big_b = if delta * delta > phi * phi + v {
+        (delta * delta - phi * phi - v).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * TAU) < 0.0 {
+            k += 1.0;
+        }
+        a - k * TAU
+    };
+    let mut f_a = f(big_a);
+    let mut f_b = f(big_b);
+    while (big_b - big_a).abs() > EPSILON {
+        let big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+        let f_c = f(big_c);
+        if f_c * f_b <= 0.0 {
+            big_a = big_b;
+            f_a = f_b;
+        } else {
+            f_a /= 2.0;
+        }
+        big_b = big_c;
+        f_b = f_c;
+    }
+    (big_a / 2.0).exp()
+}
+
+/*
+Ratings are kept apart per kind of game, from how long a game is expected to take
+*/
+pub fn category(clock: Option<&Clock>) -> &'static str {
+    match clock {
+        None => "untimed",
+        Some(clock) => {
+            // the usual estimate of 40 moves per game
+            let seconds = (clock.base_ms() + 40 * clock.increment_ms) / 1000;
+            if seconds < 180 {
+                "bullet"
+            } else if seconds < 480 {
+                "blitz"
+            } else if seconds < 1500 {
+                "rapid"
+            } else {
+                "classical"
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RatingChange {
+    pub game_id: u64,
+    pub category: String,
+    pub rating: f64,
+    pub deviation: f64,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Account {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub ratings: BTreeMap<String, Rating>, // category -> rating
+    pub history: Vec<RatingChange>,
+}
+impl Account {
+    pub fn new(name: &str) -> Account {
+        Account {
+            name: name.to_string(),
+            created_at: Utc::now(),
+            ratings: BTreeMap::new(),
+            history: Vec::new(),
+        }
+    }
+    pub fn rating(&self, category: &str) -> Rating {
+        self.ratings.get(category).copied().unwrap_or_default()
+    }
+    pub fn set_rating(&mut self, game_id: u64, category: &str, rating: Rating) {
+        self.ratings.insert(category.to_string(), rating);
+        self.history.push(RatingChange {
+            game_id,
+            category: category.to_string(),
+            rating: rating.rating,
+            deviation: rating.deviation,
+            at: Utc::now(),
+        });
+    }
+}
+
+/*
+Account names double as file names, so keep them simple
+*/
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name != "none"
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/*
+Moves both players' ratings after a rated game, in the category the game was played in
+*/
+pub fn rate_game(store: &mut dyn GameStore, record: &StoredGame) -> io::Result<()> {
+    let score: f64 = match record.result.as_deref() {
+        Some("1-0") => 1.0,
+        Some("0-1") => 0.0,
+        Some("1/2-1/2") => 0.5,
+        _ => return Ok(()),
+    };
+    if !record.rated || !is_valid_name(&record.white) || !is_valid_name(&record.black) {
+        return Ok(());
+    }
+    let category = category(record.clock.as_ref());
+    let mut white: Account = store
+        .account(&record.white)?
+        .unwrap_or_else(|| Account::new(&record.white));
+    let mut black: Account = store
+        .account(&record.black)?
+        .unwrap_or_else(|| Account::new(&record.black));
+    let white_rating = white.rating(category);
+    let black_rating = black.rating(category);
+    white.set_rating(
+        record.id,
+        category,
+        white_rating.after_game(&black_rating, score),
+    );
+    black.set_rating(
+        record.id,
+        category,
+        black_rating.after_game(&white_rating, 1.0 - score),
+    );
+    store.save_account(&white)?;
+    store.save_account(&black)
+}
+
+#[derive(Serialize, Debug)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub rating: f64,
+    pub deviation: f64,
+    pub games: usize,
+}
+
+/*
+Best rated players of a category, provisional ratings left out
+*/
+pub fn leaderboard(accounts: &[Account], category: &str, limit: usize) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = accounts
+        .iter()
+        .filter_map(|account| {
+            let rating = account.ratings.get(category)?;
+            if rating.is_provisional() {
+                return None;
+            }
+            Some(LeaderboardEntry {
+                name: account.name.clone(),
+                rating: rating.rating,
+                deviation: rating.deviation,
+                games: account
+                    .history
+                    .iter()
+                    .filter(|change| change.category == category)
+                    .count(),
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+    entries.truncate(limit);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+    use chess::game::Termination;
+
+    #[test]
+    fn rapid_game_ending_short_of_time_is_rated_as_rapid() {
+        let mut store = MemoryStore::default();
+        let mut record = StoredGame::new(1);
+        record.white = "alice".to_string();
+        record.black = "bob".to_string();
+        record.rated = true;
+        let mut clock = Clock::new(10 * 60_000, 0);
+        // two minutes left for one, seconds for the other
+        clock.white_ms = 120_000;
+        clock.black_ms = 4_000;
+        record.clock = Some(clock);
+        record.finish("1-0", Termination::Checkmate);
+
+        rate_game(&mut store, &record).unwrap();
+        let alice = store.account("alice").unwrap().unwrap();
+        assert!(alice.ratings.contains_key("rapid"));
+        assert_eq!(alice.ratings.len(), 1);
+        assert_eq!(alice.history[0].category, "rapid");
+    }
+
+    #[test]
+    fn category_follows_the_time_control() {
+        assert_eq!(category(None), "untimed");
+        assert_eq!(category(Some(&Clock::new(60_000, 0))), "bullet");
+        assert_eq!(category(Some(&Clock::new(3 * 60_000, 2000))), "blitz");
+        assert_eq!(category(Some(&Clock::new(10 * 60_000, 0))), "rapid");
+        assert_eq!(category(Some(&Clock::new(30 * 60_000, 0))), "classical");
+    }
+}
@@ -0,0 +1,276 @@
+use serde::Serialize;
+
+use crate::ai::{same_move, search, Limits, Score, SearchResult};
+use crate::game::Game;
+use crate::moves::Move;
+use crate::notation::{numbered, san, san_moves_from, tag_section, wrap};
+
+// evaluations are capped here, so a mate weighs about as much as a rook and a half
+const EVAL_CAP: i32 = 1000;
+
+/*
+How a move compares to the engine's choice, by the centipawns it gave away
+*/
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Judgement {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+impl Judgement {
+    fn from_loss(loss: i32) -> Judgement {
+        match loss {
+            ..=49 => Judgement::Good,
+            50..=99 => Judgement::Inaccuracy,
+            100..=299 => Judgement::Mistake,
+            _ => Judgement::Blunder,
+        }
+    }
+    /*
+    The NAG that goes after the move in PGN: ?!, ? and ??
+    */
+    pub fn nag(&self) -> Option<&'static str> {
+        match self {
+            Judgement::Inaccuracy => Some("$6"),
+            Judgement::Mistake => Some("$2"),
+            Judgement::Blunder => Some("$4"),
+            _ => None,
+        }
+    }
+    fn describe(&self) -> &'static str {
+        match self {
+            Judgement::Best => "Best move",
+            Judgement::Good => "Good move",
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        }
+    }
+}
+
+pub struct ReviewedMove {
+    pub mov: Move,
+    pub san: String,
+    pub white: bool,         // played by white
+    pub eval: Option<Score>, // after the move from white's point of view, None once it's over
+    pub loss: i32,
+    pub judgement: Judgement,
+    pub best: Option<SearchResult>, // what the engine had in mind before the move
+}
+impl ReviewedMove {
+    /*
+    The engine's line instead of this move in SAN, when the move wasn't the best
+    */
+    pub fn better_line(&self, before: &Game) -> Vec<String> {
+        match &self.best {
+            Some(best) if self.judgement != Judgement::Best => {
+                let pv: Vec<String> = best.pv.iter().map(|m| m.to_string()).collect();
+                san_moves_from(before, &pv)
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct PlayerReview {
+    pub accuracy: f64, // percent, 100 for only playing the engine's moves
+    pub acpl: u32,     // average centipawn loss
+    pub inaccuracies: u32,
+    pub mistakes: u32,
+    pub blunders: u32,
+}
+
+pub struct Review {
+    pub start: Game,
+    pub positions: Vec<Game>, // before each move
+    pub moves: Vec<ReviewedMove>,
+    pub white: PlayerReview,
+    pub black: PlayerReview,
+}
+
+/*
+Value of a position for the side to move in capped centipawns, with the engine's search
+*/
+fn evaluate(game: &Game, limits: &Limits) -> (i32, Option<SearchResult>) {
+    match search(game, limits) {
+        Some(result) => (
+            result.score.centipawns().clamp(-EVAL_CAP, EVAL_CAP),
+            Some(result),
+        ),
+        None if game.is_in_check(game.white_to_move) => (-EVAL_CAP, None),
+        None => (0, None),
+    }
+}
+
+/*
+Chances of winning in percent for the side ahead by cp, the curve lichess fitted to its games
+*/
+fn win_chance(cp: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cp as f64).exp()) - 1.0)
+}
+
+/*
+Accuracy of a move in percent, from the winning chances before and after it
+*/
+fn move_accuracy(before: i32, after: i32) -> f64 {
+    let lost = (win_chance(before) - win_chance(after)).max(0.0);
+    (103.1668 * (-0.04354 * lost).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+/*
+Runs the engine over every position of a game given in "E2E4" form from start and judges each
+move by how much worse it left the position than the engine's choice would have
+*/
+pub fn review(start: &Game, moves: &[String], limits: &Limits) -> Review {
+    let mut game: Game = start.clone();
+    let mut positions: Vec<Game> = Vec::new();
+    let mut reviewed: Vec<ReviewedMove> = Vec::new();
+    // accuracy and centipawns lost added up, and moves counted, for white and black
+    let mut totals = [(0.0, 0, 0); 2];
+    let (mut before, mut best) = evaluate(&game, limits);
+    for text in moves {
+        let Some(mov) = Move::parse(text) else {
+            break;
+        };
+        let Some(san) = san(&game, &mov) else {
+            break;
+        };
+        let white = game.white_to_move;
+        let mut next: Game = game.clone();
+        let _ = next.play(&mov);
+        let (next_value, next_best) = evaluate(&next, limits);
+        let after = -next_value;
+        let is_best = best.as_ref().is_some_and(|b| same_move(&b.best, &mov));
+        let loss = if is_best { 0 } else { (before - after).max(0) };
+        let judgement = if is_best {
+            Judgement::Best
+        } else {
+            Judgement::from_loss(loss)
+        };
+        let eval = next_best.as_ref().map(|b| {
+            // the score is for whoever moves next
+            if white {
+                b.score.negate()
+            } else {
+                b.score
+            }
+        });
+        let total = &mut totals[if white { 0 } else { 1 }];
+        total.0 += move_accuracy(before, after);
+        total.1 += loss;
+        total.2 += 1;
+        reviewed.push(ReviewedMove {
+            mov,
+            san,
+            white,
+            eval,
+            loss,
+            judgement,
+            best,
+        });
+        positions.push(game);
+        game = next;
+        before = next_value;
+        best = next_best;
+    }
+    let player = |white: bool| {
+        let (accuracy, loss, count) = totals[if white { 0 } else { 1 }];
+        let count_of = |judgement: Judgement| {
+            reviewed
+                .iter()
+                .filter(|m| m.white == white && m.judgement == judgement)
+                .count() as u32
+        };
+        PlayerReview {
+            accuracy: if count > 0 {
+                (accuracy / count as f64 * 10.0).round() / 10.0
+            } else {
+                100.0
+            },
+            acpl: if count > 0 { (loss / count) as u32 } else { 0 },
+            inaccuracies: count_of(Judgement::Inaccuracy),
+            mistakes: count_of(Judgement::Mistake),
+            blunders: count_of(Judgement::Blunder),
+        }
+    };
+    Review {
+        white: player(true),
+        black: player(false),
+        start: start.clone(),
+        positions,
+        moves: reviewed,
+    }
+}
+
+/*
+[%eval] style score from white's point of view: pawns with two decimals or #n for a mate
+*/
+fn eval_text(score: &Score) -> String {
+    match score {
+        Score::Cp(cp) => format!("{:.2}", *cp as f64 / 100.0),
+        Score::Mate(moves) => format!("#{}", moves),
+    }
+}
+
+/*
+The reviewed game in PGN: every move with its evaluation, NAGs on the bad ones and the
+engine's line instead of them as a variation
+*/
+pub fn annotated_pgn(review: &Review, tags: &[(&str, String)], result: &str) -> String {
+    let mut text = tag_section(tags, &review.start, result);
+    let mut tokens: Vec<String> = vec![format!(
+        "{{ White: {}% accuracy, {} ACPL. Black: {}% accuracy, {} ACPL. }}",
+        review.white.accuracy, review.white.acpl, review.black.accuracy, review.black.acpl
+    )];
+    let mut number = review.start.fullmove_number;
+    for (reviewed, before) in review.moves.iter().zip(&review.positions) {
+        // every move is followed by a comment, so black's moves always need their number
+        tokens.push(if reviewed.white {
+            format!("{}.", number)
+        } else {
+            format!("{}...", number)
+        });
+        tokens.push(reviewed.san.clone());
+        if let Some(nag) = reviewed.judgement.nag() {
+            tokens.push(nag.to_string());
+        }
+        let line = reviewed.better_line(before);
+        let mut comment: Vec<String> = Vec::new();
+        if let Some(eval) = &reviewed.eval {
+            comment.push(format!("[%eval {}]", eval_text(eval)));
+        }
+        if reviewed.judgement.nag().is_some() {
+            if let Some(first) = line.first() {
+                comment.push(format!(
+                    "{}. {} was best.",
+                    reviewed.judgement.describe(),
+                    first
+                ));
+            }
+        }
+        if !comment.is_empty() {
+            tokens.push(format!("{{ {} }}", comment.join(" ")));
+        }
+        if reviewed.judgement.nag().is_some() && !line.is_empty() {
+            let mut variation = numbered(number, reviewed.white, &line);
+            if let Some(first) = variation.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = variation.last_mut() {
+                last.push(')');
+            }
+            tokens.extend(variation);
+        }
+        if !reviewed.white {
+            number += 1;
+        }
+    }
+    tokens.push(result.to_string());
+    text.push_str(&wrap(&tokens));
+    text.push_str("\n\n");
+    text
+}
@@ -0,0 +1,201 @@
+use crate::error::Error;
+use crate::game::Game;
+use crate::position::Position;
+
+// handicaps, white giving them. Names as the API takes them and the position they start from
+pub const ODDS: [(&str, &str); 6] = [
+    (
+        "pawn",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPP1PP/RNBQKBNR w KQkq - 0 1",
+    ),
+    (
+        "knight",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1",
+    ),
+    (
+        "bishop",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RN1QKBNR w KQkq - 0 1",
+    ),
+    (
+        "rook",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/1NBQKBNR w Kkq - 0 1",
+    ),
+    (
+        "queen",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1",
+    ),
+    (
+        "two_knights",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKB1R w KQkq - 0 1",
+    ),
+];
+
+/*
+The starting position of an odds game, like "queen" for queen odds
+*/
+pub fn odds(name: &str) -> Result<Game, Error> {
+    match ODDS.iter().find(|(odds, _)| *odds == name) {
+        Some((_, fen)) => Game::from_fen(fen),
+        None => {
+            let names: Vec<&str> = ODDS.iter().map(|(odds, _)| *odds).collect();
+            Err(Error::Setup(format!(
+                "{:?} is not an odds game, there's {}",
+                name,
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+/*
+Reads a position someone wants to play from. Unlike Game::from_fen, which drops castling rights
+that don't fit the board, those are an error here, and so is anything validate doesn't like
+*/
+pub fn from_fen(fen: &str) -> Result<Game, Error> {
+    let game = Game::from_fen(fen)?;
+    let mut letters: Vec<char> = fen
+        .split_whitespace()
+        .nth(2)
+        .unwrap_or("-")
+        .chars()
+        .filter(|c| *c != '-')
+        .collect();
+    letters.sort();
+    letters.dedup();
+    if letters.len() != castling_rights(&game) {
+        return Err(Error::Position(format!(
+            "the castling rights {:?} don't fit the board, every one needs its king and rook on \
+             their starting squares",
+            letters.iter().collect::<String>()
+        )));
+    }
+    validate(&game)?;
+    Ok(game)
+}
+
+/*
+How many ways to castle are left in game
+*/
+fn castling_rights(game: &Game) -> usize {
+    let (white_rooks, black_rooks) = game.has_rook_moved;
+    [
+        (game.has_king_moved.0, white_rooks),
+        (game.has_king_moved.1, black_rooks),
+    ]
+    .iter()
+    .filter(|(king_moved, _)| !king_moved)
+    .map(|(_, (long, short))| !long as usize + !short as usize)
+    .sum()
+}
+
+/*
+Whether a position can be played from: one king a side where kings says, no more pieces than a
+side starts with, no pawns on the first or last rank, the side that just moved not in check,
+castling rights and the en passant square that fit the board
+*/
+pub fn validate(game: &Game) -> Result<(), Error> {
+    check(game).map_err(Error::Position)
+}
+
+fn check(game: &Game) -> Result<(), String> {
+    for white in [true, false] {
+        let color = if white { "white" } else { "black" };
+        let count = |name: &str| {
+            game.pieces
+                .values()
+                .filter(|p| p.white == white && p.name == name)
+                .count()
+        };
+        if count("king") != 1 {
+            return Err(format!("{} needs exactly one king", color));
+        }
+        if !game.has_king(white) {
+            return Err(format!("{}'s king isn't where the game has it", color));
+        }
+        if count("pawn") > 8 {
+            return Err(format!("{} has more than 8 pawns", color));
+        }
+        if game.pieces.values().filter(|p| p.white == white).count() > 16 {
+            return Err(format!("{} has more than 16 pieces", color));
+        }
+        let row = if white { 1 } else { 8 };
+        let (king_moved, (long_moved, short_moved)) = if white {
+            (game.has_king_moved.0, game.has_rook_moved.0)
+        } else {
+            (game.has_king_moved.1, game.has_rook_moved.1)
+        };
+        if king_moved {
+            continue;
+        }
+        let king = if white { &game.kings.0 } else { &game.kings.1 };
+        if king.row != row || (!game.chess960 && king.column != 'E') {
+            return Err(format!(
+                "{} can't castle, the king isn't on its starting square",
+                color
+            ));
+        }
+        for (moved, file) in [
+            (long_moved, game.castling_files.0),
+            (short_moved, game.castling_files.1),
+        ] {
+            let at_home = game
+                .pieces
+                .get(&(file, row))
+                .is_some_and(|p| p.name == "rook" && p.white == white);
+            if !moved && !at_home {
+                return Err(format!(
+                    "{} can't castle with the rook on {}{}, there's none",
+                    color,
+                    file.to_ascii_lowercase(),
+                    row
+                ));
+            }
+        }
+    }
+    if let Some(pawn) = game
+        .pieces
+        .values()
+        .find(|p| p.name == "pawn" && (p.position.row == 1 || p.position.row == 8))
+    {
+        return Err(format!(
+            "there's a pawn on {}, pawns can't be on the first or last rank",
+            pawn.position.to_string().to_lowercase()
+        ));
+    }
+    if game.is_in_check(!game.white_to_move) {
+        let (to_move, checked) = if game.white_to_move {
+            ("white", "black")
+        } else {
+            ("black", "white")
+        };
+        return Err(format!(
+            "{} is to move while {}'s king is in check",
+            to_move, checked
+        ));
+    }
+    if let Some(square) = &game.en_passant {
+        validate_en_passant(game, square)?;
+    }
+    Ok(())
+}
+
+/*
+The en passant square has to be one a pawn of the side that just moved skipped over
+*/
+fn validate_en_passant(game: &Game, square: &Position) -> Result<(), String> {
+    let mover_white = !game.white_to_move;
+    let (skipped_row, pawn_row, start_row) = if mover_white { (3, 4, 2) } else { (6, 5, 7) };
+    let occupied = |row: i32| game.pieces.get(&(square.column, row));
+    let pawn_there = occupied(pawn_row).is_some_and(|p| p.name == "pawn" && p.white == mover_white);
+    if square.row != skipped_row
+        || !pawn_there
+        || occupied(skipped_row).is_some()
+        || occupied(start_row).is_some()
+    {
+        return Err(format!(
+            "{} can't be the en passant square, no pawn just moved two squares past it",
+            square.to_string().to_lowercase()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::ai::{search_lines, Limits, Noise, SearchResult};
+use crate::error::Error;
+use crate::game::Game;
+
+pub const MAX_LEVEL: u32 = 20;
+// rough strength of the lowest and highest levels, what the Elo mode goes by
+pub const MIN_ELO: u32 = 600;
+pub const MAX_ELO: u32 = 2000;
+
+/*
+How strong the built-in engine should play: a level from 1 to 20 (20 being full strength) or
+about how many Elo points. The same seed makes it pick the same moves again
+*/
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Strength {
+    pub level: Option<u32>,
+    pub elo: Option<u32>,
+    pub seed: Option<u64>,
+}
+impl Strength {
+    pub fn is_set(&self) -> bool {
+        self.level.is_some() || self.elo.is_some() || self.seed.is_some()
+    }
+    pub fn check(&self) -> Result<(), Error> {
+        match (self.level, self.elo) {
+            (Some(_), Some(_)) => Err(Error::Engine(
+                "give either a level or an elo, not both".to_string(),
+            )),
+            (Some(level), None) if !(1..=MAX_LEVEL).contains(&level) => Err(Error::Engine(
+                format!("level {} is not between 1 and {}", level, MAX_LEVEL),
+            )),
+            (None, Some(elo)) if !(MIN_ELO..=MAX_ELO).contains(&elo) => Err(Error::Engine(
+                format!("elo {} is not between {} and {}", elo, MIN_ELO, MAX_ELO),
+            )),
+            _ => Ok(()),
+        }
+    }
+    /*
+    Level on the 1 to 20 scale, in between levels for an Elo
+    */
+    fn level(&self) -> f64 {
+        match (self.level, self.elo) {
+            (Some(level), _) => level as f64,
+            (None, Some(elo)) => {
+                let elo = elo.clamp(MIN_ELO, MAX_ELO) as f64;
+                1.0 + (elo - MIN_ELO as f64) / (MAX_ELO - MIN_ELO) as f64 * (MAX_LEVEL - 1) as f64
+            }
+            (None, None) => MAX_LEVEL as f64,
+        }
+    }
+}
+
+/*
+What a level means for the search. Weaker levels look less deep and at fewer nodes, misjudge
+positions by up to noise_cp and every so often play one of their other lines instead of the
+best one, as long as it's no more than max_loss_cp worse. Full strength has no caps
+*/
+#[derive(Clone, Debug)]
+pub struct Skill {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub noise_cp: i32,
+    pub lines: usize,
+    pub blunder_chance: f64,
+    pub max_loss_cp: i32,
+}
+impl Skill {
+    pub fn new(strength: &Strength) -> Skill {
+        let level = strength.level().clamp(1.0, MAX_LEVEL as f64);
+        // 0 at level 1, 1 at full strength
+        let t = (level - 1.0) / (MAX_LEVEL - 1) as f64;
+        let capped = level < MAX_LEVEL as f64;
+        Skill {
+            depth: capped.then(|| 1 + (3.0 * t).round() as u32),
+            nodes: capped.then(|| (300.0 * 200f64.powf(t)) as u64),
+            noise_cp: (150.0 * (1.0 - t)).round() as i32,
+            lines: if capped { 4 } else { 1 },
+            blunder_chance: 0.4 * (1.0 - t),
+            max_loss_cp: (40.0 + 400.0 * (1.0 - t)).round() as i32,
+        }
+    }
+    /*
+    limits with the level's caps on top
+    */
+    pub fn limits(&self, limits: &Limits) -> Limits {
+        // the lower of the two, whichever are there
+        fn cap<T: Ord>(limit: Option<T>, cap: Option<T>) -> Option<T> {
+            match (limit, cap) {
+                (Some(limit), Some(cap)) => Some(limit.min(cap)),
+                (limit, cap) => limit.or(cap),
+            }
+        }
+        Limits {
+            depth: cap(limits.depth, self.depth),
+            nodes: cap(limits.nodes, self.nodes),
+            ..limits.clone()
+        }
+    }
+}
+
+/*
+The built-in engine held back to a level, with the dice it rolls
+*/
+pub struct Weakened {
+    pub skill: Skill,
+    seed: Option<u64>,
+    rng: StdRng,
+}
+impl Weakened {
+    pub fn new(strength: &Strength) -> Weakened {
+        Weakened {
+            skill: Skill::new(strength),
+            seed: strength.seed,
+            rng: new_rng(strength.seed),
+        }
+    }
+    /*
+    Back to the seed, so a seeded game plays out the same every time
+    */
+    pub fn restart(&mut self) {
+        self.rng = new_rng(self.seed);
+    }
+    /*
+    Searches game with the skill's caps and noise and picks a move the way the level would
+    */
+    pub fn search(&mut self, game: &Game, limits: &Limits) -> Option<SearchResult> {
+        let skill = &self.skill;
+        let noise = Noise {
+            amplitude: skill.noise_cp,
+            rng: &mut self.rng,
+        };
+        let lines = search_lines(game, &skill.limits(limits), skill.lines, Some(noise));
+        let best = lines.first()?;
+        if lines.len() > 1 && self.rng.gen_bool(skill.blunder_chance) {
+            let best_cp = best.score.centipawns();
+            let others: Vec<&SearchResult> = lines[1..]
+                .iter()
+                .filter(|line| best_cp - line.score.centipawns() <= skill.max_loss_cp)
+                .collect();
+            if let Some(line) = others.choose(&mut self.rng) {
+                return Some((*line).clone());
+            }
+        }
+        Some(best.clone())
+    }
+}
+
+fn new_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::init_pieces;
+    use crate::uci::uci_move;
+
+    fn strength(level: u32, seed: u64) -> Strength {
+        Strength {
+            level: Some(level),
+            elo: None,
+            seed: Some(seed),
+        }
+    }
+
+    /*
+    The moves the level picks playing both sides for plies plies from the start
+    */
+    fn self_play(strength: &Strength, plies: usize) -> Vec<String> {
+        let mut engine = Weakened::new(strength);
+        let limits = Limits {
+            depth: Some(2),
+            ..Default::default()
+        };
+        let mut game = init_pieces();
+        let mut moves = Vec::new();
+        for _ in 0..plies {
+            let found = engine.search(&game, &limits).unwrap();
+            game.play(&found.best).unwrap();
+            moves.push(uci_move(&found.best));
+        }
+        moves
+    }
+
+    #[test]
+    fn full_strength_has_no_caps() {
+        let skill = Skill::new(&strength(MAX_LEVEL, 1));
+        let limits = Limits {
+            depth: Some(12),
+            nodes: Some(5_000_000),
+            ..Default::default()
+        };
+        let capped = skill.limits(&limits);
+        assert_eq!((capped.depth, capped.nodes), (Some(12), Some(5_000_000)));
+        // the lower levels still are
+        let weak = Skill::new(&strength(1, 1)).limits(&limits);
+        assert_eq!((weak.depth, weak.nodes), (Some(1), Some(300)));
+    }
+
+    #[test]
+    fn same_seed_same_moves() {
+        for level in [1, 10, MAX_LEVEL] {
+            let first = self_play(&strength(level, 7), 6);
+            assert_eq!(first, self_play(&strength(level, 7), 6), "level {}", level);
+        }
+    }
+
+    #[test]
+    fn weakest_level_plays_differently() {
+        let weakest = self_play(&strength(1, 7), 6);
+        assert_ne!(weakest, self_play(&strength(MAX_LEVEL, 7), 6));
+    }
+}
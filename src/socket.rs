@@ -0,0 +1,196 @@
+use actix::{Actor, ActorContext, AsyncContext, Handler, Message, StreamHandler};
+use actix_web_actors::ws;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use chess::ai::{analyse, Limits, SearchResult};
+use chess::game::Game;
+
+use crate::api::AnalysisView;
+use crate::lobby::{Event, ServerEvent, SharedLobby, Watcher};
+
+/*
+One browser connected to a game, or to the lobby when there's no game_id. Read only: it gets
+every new state pushed to it, moves still go through the http api so players are checked there
+*/
+pub struct GameSocket {
+    pub id: usize,
+    pub game_id: Option<u64>,
+    pub name: Option<String>,
+    pub lobby: SharedLobby,
+}
+
+impl Actor for GameSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let mut lobby = self.lobby.lock().unwrap();
+        let watcher = Watcher {
+            name: self.name.clone(),
+            recipient: ctx.address().recipient(),
+        };
+        let Some(game_id) = self.game_id else {
+            if let Some(text) = (ServerEvent::Seeks {
+                seeks: &lobby.seeks,
+            })
+            .encode()
+            {
+                ctx.text(text);
+            }
+            lobby.listeners.insert(self.id, watcher);
+            return;
+        };
+        match lobby.tables.get_mut(&game_id) {
+            Some(table) => {
+                table.watchers.insert(self.id, watcher);
+                table.broadcast();
+            }
+            None => ctx.stop(),
+        }
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        let mut lobby = self.lobby.lock().unwrap();
+        lobby.listeners.remove(&self.id);
+        // the table may have been replaced by a reset, so look everywhere
+        for table in lobby.tables.values_mut() {
+            if table.watchers.remove(&self.id).is_some() {
+                table.broadcast();
+            }
+        }
+    }
+}
+
+impl Handler<Event> for GameSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: Event, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for GameSocket {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(bytes)) => ctx.pong(&bytes),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(_) => ctx.stop(),
+        }
+    }
+}
+
+/*
+A connection running one analysis, pushing the lines every time the search gets a ply deeper.
+It closes when the search is over, sending {"type": "stop"} (or closing it first) stops it
+*/
+pub struct AnalysisSocket {
+    game: Game,
+    limits: Limits,
+    lines: usize,
+    stop: Arc<AtomicBool>,
+}
+impl AnalysisSocket {
+    pub fn new(game: Game, mut limits: Limits, lines: usize) -> AnalysisSocket {
+        let stop = Arc::new(AtomicBool::new(false));
+        limits.stop = Some(stop.clone());
+        AnalysisSocket {
+            game,
+            limits,
+            lines,
+            stop,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnalysisCommand {
+    Stop,
+}
+
+// last frame of an analysis, the socket closes after it
+#[derive(Message)]
+#[rtype(result = "()")]
+struct AnalysisOver(String);
+
+impl Actor for AnalysisSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let address = ctx.address();
+        let game = self.game.clone();
+        let limits = self.limits.clone();
+        let lines = self.lines;
+        let stop = self.stop.clone();
+        // the search blocks, so it gets a thread of its own
+        thread::spawn(move || {
+            let send = |results: &[SearchResult]| {
+                if let Some(text) =
+                    ServerEvent::Analysis(&AnalysisView::new(&game, results)).encode()
+                {
+                    address.do_send(Event(text));
+                }
+            };
+            let mut sent = false;
+            analyse(&game, &limits, lines, &mut |results| {
+                sent = true;
+                send(results);
+            });
+            if !sent {
+                send(&[]);
+            }
+            let done = ServerEvent::AnalysisDone {
+                stopped: stop.load(Ordering::Relaxed),
+            };
+            if let Some(text) = done.encode() {
+                address.do_send(AnalysisOver(text));
+            }
+        });
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Handler<Event> for AnalysisSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: Event, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+    }
+}
+
+impl Handler<AnalysisOver> for AnalysisSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: AnalysisOver, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+        ctx.close(None);
+        ctx.stop();
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for AnalysisSocket {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(bytes)) => ctx.pong(&bytes),
+            Ok(ws::Message::Text(text)) => match serde_json::from_str(&text) {
+                Ok(AnalysisCommand::Stop) => self.stop.store(true, Ordering::Relaxed),
+                Err(e) => println!("analysis socket got {:?}: {}", text, e),
+            },
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(_) => ctx.stop(),
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use chess::clock::Clock;
+use chess::fen::START_FEN;
+use chess::game::{init_pieces, Game, Termination};
+use chess::notation::write_pgn;
+use chess::skill::Strength;
+use chess::variant;
+
+use crate::rating::Account;
+use crate::tournament::Tournament;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct StoredGame {
+    pub id: u64,
+    pub white: String,
+    pub black: String,
+    pub moves: Vec<String>, // "E2E4" style, in the order they were played
+    #[serde(default)]
+    pub start_fen: Option<String>, // None for the usual starting position
+    #[serde(default)]
+    pub variant: Option<String>, // None for standard rules
+    pub clock: Option<Clock>,
+    pub result: Option<String>, // "1-0", "0-1", "1/2-1/2" or "*" when abandoned
+    #[serde(default)]
+    pub termination: Option<Termination>,
+    #[serde(default)]
+    pub rated: bool,
+    // how strong the built-in engine plays when it sits in that seat
+    #[serde(default)]
+    pub white_strength: Option<Strength>,
+    #[serde(default)]
+    pub black_strength: Option<Strength>,
+    // what the people in the seats prove it's them with, never shown to anyone else
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub white_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub black_token: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+impl StoredGame {
+    pub fn new(id: u64) -> StoredGame {
+        StoredGame {
+            id,
+            white: "none".to_string(),
+            black: "none".to_string(),
+            moves: Vec::new(),
+            start_fen: None,
+            variant: None,
+            clock: None,
+            result: None,
+            termination: None,
+            rated: false,
+            white_strength: None,
+            black_strength: None,
+            white_token: None,
+            black_token: None,
+            started_at: Utc::now(),
+            finished_at: None,
+        }
+    }
+    /*
+    The position the game started from
+    */
+    pub fn start(&self) -> Result<Game, String> {
+        let mut game = match &self.start_fen {
+            Some(fen) => Game::from_fen(fen)?,
+            None => init_pieces(),
+        };
+        if let Some(name) = &self.variant {
+            game.variant =
+                variant::by_name(name).ok_or_else(|| format!("{:?} is not a variant", name))?;
+        }
+        Ok(game)
+    }
+    pub fn start_fen(&self) -> &str {
+        self.start_fen.as_deref().unwrap_or(START_FEN)
+    }
+    pub fn finish(&mut self, result: &str, termination: Termination) {
+        self.result = Some(result.to_string());
+        self.termination = Some(termination);
+        self.finished_at = Some(Utc::now());
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ArchiveQuery {
+    pub player: Option<String>,
+    pub date: Option<NaiveDate>, // day the game started, "YYYY-MM-DD"
+}
+impl ArchiveQuery {
+    pub fn matches(&self, game: &StoredGame) -> bool {
+        if let Some(player) = &self.player {
+            if &game.white != player && &game.black != player {
+                return false;
+            }
+        }
+        if let Some(date) = self.date {
+            if game.started_at.date_naive() != date {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/*
+Where games, the accounts of the people playing them and tournaments are kept between server restarts.
+Every change is saved right away, so save() overwrites the previous copy
+*/
+pub trait GameStore: Send {
+    fn save(&mut self, game: &StoredGame) -> io::Result<()>;
+    fn load(&self, id: u64) -> io::Result<Option<StoredGame>>;
+    fn all(&self) -> io::Result<Vec<StoredGame>>;
+    fn save_account(&mut self, account: &Account) -> io::Result<()>;
+    fn account(&self, name: &str) -> io::Result<Option<Account>>;
+    fn accounts(&self) -> io::Result<Vec<Account>>;
+    fn save_tournament(&mut self, tournament: &Tournament) -> io::Result<()>;
+    fn tournaments(&self) -> io::Result<Vec<Tournament>>;
+    fn next_id(&self) -> io::Result<u64> {
+        Ok(self.all()?.iter().map(|g| g.id).max().unwrap_or(0) + 1)
+    }
+    fn unfinished(&self) -> io::Result<Vec<StoredGame>> {
+        Ok(self
+            .all()?
+            .into_iter()
+            .filter(|g| g.result.is_none())
+            .collect())
+    }
+    fn archive(&self, query: &ArchiveQuery) -> io::Result<Vec<StoredGame>> {
+        Ok(self
+            .all()?
+            .into_iter()
+            .filter(|g| g.result.is_some() && query.matches(g))
+            .collect())
+    }
+}
+
+/*
+Keeps every game as its own JSON file, games/<id>.json, accounts in games/players/<name>.json
+and tournaments in games/tournaments/<id>.json
+*/
+pub struct FileStore {
+    dir: PathBuf,
+}
+impl FileStore {
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<FileStore> {
+        let dir: PathBuf = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileStore { dir })
+    }
+    fn path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+    fn players_dir(&self) -> PathBuf {
+        self.dir.join("players")
+    }
+    fn tournaments_dir(&self) -> PathBuf {
+        self.dir.join("tournaments")
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: PathBuf) -> io::Result<Option<T>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/*
+Everything in dir that reads as a T, a missing dir is just empty
+*/
+fn read_all_json<T: DeserializeOwned>(dir: PathBuf) -> io::Result<Vec<T>> {
+    let mut values: Vec<T> = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(values),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        match serde_json::from_slice::<T>(&fs::read(&path)?) {
+            Ok(value) => values.push(value),
+            Err(e) => println!("skipping {}: {}", path.display(), e),
+        }
+    }
+    Ok(values)
+}
+
+/*
+Writes to a temp file first so a crash mid-write can't corrupt what was there
+*/
+fn write_json<T: Serialize>(path: PathBuf, value: &T) -> io::Result<()> {
+    let data = serde_json::to_vec_pretty(value)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, data)?;
+    fs::rename(tmp, path)
+}
+impl GameStore for FileStore {
+    fn save(&mut self, game: &StoredGame) -> io::Result<()> {
+        write_json(self.path(game.id), game)
+    }
+    fn load(&self, id: u64) -> io::Result<Option<StoredGame>> {
+        read_json(self.path(id))
+    }
+    fn all(&self) -> io::Result<Vec<StoredGame>> {
+        let mut games: Vec<StoredGame> = read_all_json(self.dir.clone())?;
+        games.sort_by_key(|g| g.id);
+        Ok(games)
+    }
+    fn save_account(&mut self, account: &Account) -> io::Result<()> {
+        fs::create_dir_all(self.players_dir())?;
+        write_json(
+            self.players_dir().join(format!("{}.json", account.name)),
+            account,
+        )
+    }
+    fn account(&self, name: &str) -> io::Result<Option<Account>> {
+        read_json(self.players_dir().join(format!("{}.json", name)))
+    }
+    fn accounts(&self) -> io::Result<Vec<Account>> {
+        read_all_json(self.players_dir())
+    }
+    fn save_tournament(&mut self, tournament: &Tournament) -> io::Result<()> {
+        fs::create_dir_all(self.tournaments_dir())?;
+        write_json(
+            self.tournaments_dir()
+                .join(format!("{}.json", tournament.id)),
+            tournament,
+        )
+    }
+    fn tournaments(&self) -> io::Result<Vec<Tournament>> {
+        let mut tournaments: Vec<Tournament> = read_all_json(self.tournaments_dir())?;
+        tournaments.sort_by_key(|t| t.id);
+        Ok(tournaments)
+    }
+}
+
+/*
+Keeps everything in memory, for trying the server out or tests. It's all gone on restart
+*/
+#[derive(Default)]
+pub struct MemoryStore {
+    games: BTreeMap<u64, StoredGame>,
+    accounts: BTreeMap<String, Account>,
+    tournaments: BTreeMap<u64, Tournament>,
+}
+impl GameStore for MemoryStore {
+    fn save(&mut self, game: &StoredGame) -> io::Result<()> {
+        self.games.insert(game.id, game.clone());
+        Ok(())
+    }
+    fn load(&self, id: u64) -> io::Result<Option<StoredGame>> {
+        Ok(self.games.get(&id).cloned())
+    }
+    fn all(&self) -> io::Result<Vec<StoredGame>> {
+        Ok(self.games.values().cloned().collect())
+    }
+    fn save_account(&mut self, account: &Account) -> io::Result<()> {
+        self.accounts.insert(account.name.clone(), account.clone());
+        Ok(())
+    }
+    fn account(&self, name: &str) -> io::Result<Option<Account>> {
+        Ok(self.accounts.get(name).cloned())
+    }
+    fn accounts(&self) -> io::Result<Vec<Account>> {
+        Ok(self.accounts.values().cloned().collect())
+    }
+    fn save_tournament(&mut self, tournament: &Tournament) -> io::Result<()> {
+        self.tournaments.insert(tournament.id, tournament.clone());
+        Ok(())
+    }
+    fn tournaments(&self) -> io::Result<Vec<Tournament>> {
+        Ok(self.tournaments.values().cloned().collect())
+    }
+}
+
+/*
+PGN of a stored game, event and round go in the tags of the same name
+*/
+pub fn pgn(record: &StoredGame, event: &str, round: &str) -> String {
+    write_pgn(
+        &record_tags(record, event, round),
+        &record.start().unwrap_or_else(|_| init_pieces()),
+        &record.moves,
+        record.result.as_deref().unwrap_or("*"),
+    )
+}
+
+/*
+The seven tags but Result for a stored game
+*/
+pub fn record_tags(record: &StoredGame, event: &str, round: &str) -> Vec<(&'static str, String)> {
+    let player = |name: &str| {
+        if name == "none" {
+            "?".to_string()
+        } else {
+            name.to_string()
+        }
+    };
+    vec![
+        ("Event", event.to_string()),
+        ("Site", "?".to_string()),
+        ("Date", record.started_at.format("%Y.%m.%d").to_string()),
+        ("Round", round.to_string()),
+        ("White", player(&record.white)),
+        ("Black", player(&record.black)),
+    ]
+}
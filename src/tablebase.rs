@@ -0,0 +1,1285 @@
+use memmap2::Mmap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::aux_func::letter_to_int;
+use crate::book::castling_rights;
+use crate::error::ProbeError;
+use crate::game::Game;
+use crate::moves::Move;
+use crate::variant::{Variant, STANDARD};
+
+/*
+Reader for Syzygy endgame tablebases: .rtbw files say whether a position is won, drawn or lost
+and .rtbz files how far it is from the next capture or pawn move (DTZ) with best play.
+The layout follows the format of the generator by Ronald de Man, as read by Stockfish and Fathom
+*/
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// flags of each sub-table
+const FLAG_STM: u8 = 1; // side to move a DTZ table is stored for (0 white)
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/*
+Result with perfect play for the side to move. Cursed wins and blessed losses are wins and
+losses that the fifty move rule turns into draws
+*/
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+    fn value(self) -> i32 {
+        self as i32 - 2
+    }
+    pub fn negate(self) -> Wdl {
+        Wdl::from_value(-self.value())
+    }
+    /*
+    What a DTZ says about the result, the cursed ones being past 100 plies
+    */
+    fn from_dtz(dtz: i32) -> Wdl {
+        match dtz {
+            101.. => Wdl::CursedWin,
+            1..=100 => Wdl::Win,
+            0 => Wdl::Draw,
+            -100..=-1 => Wdl::Loss,
+            _ => Wdl::BlessedLoss,
+        }
+    }
+}
+
+/*
+DTZ tables don't have the positions where the best move captures or pushes a pawn, their DTZ
+only depends on the result
+*/
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+/*
+Lookup tables for turning a position into an index, the same for every file
+*/
+struct Indexes {
+    map_pawns: [usize; 64],    // a2-h7 to 0..47, the leading pawn has the highest
+    map_b1h1h7: [usize; 64],   // squares under the a1-h8 diagonal to 0..27
+    map_a1d1d4: [usize; 64],   // the a1-d1-d4 triangle to 0..9, diagonal last
+    map_kk: [[usize; 64]; 10], // the 462 ways to place both kings
+    binomial: [[u64; 64]; 6],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+fn file_of(square: usize) -> usize {
+    square & 7
+}
+
+fn rank_of(square: usize) -> usize {
+    square >> 3
+}
+
+fn off_diagonal(square: usize) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+fn indexes() -> &'static Indexes {
+    static INDEXES: OnceLock<Indexes> = OnceLock::new();
+    INDEXES.get_or_init(|| {
+        let mut ix = Indexes {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                ix.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+        let mut diagonal: Vec<usize> = Vec::new();
+        code = 0;
+        for square in 0..28 {
+            if off_diagonal(square) < 0 && file_of(square) <= 3 {
+                ix.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 && file_of(square) <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            ix.map_a1d1d4[square] = code;
+            code += 1;
+        }
+        // kings next to each other can't be, and with the first king on the diagonal the
+        // second one is mirrored under it
+        let mut both_on_diagonal: Vec<(usize, usize)> = Vec::new();
+        code = 0;
+        for idx in 0..10 {
+            for first in 0..28 {
+                // b1 is the one mapped to 0, squares outside the triangle are left at 0
+                if ix.map_a1d1d4[first] != idx || (idx == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64 {
+                    let touching = file_of(first).abs_diff(file_of(second)) <= 1
+                        && rank_of(first).abs_diff(rank_of(second)) <= 1;
+                    if touching || (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((idx, second));
+                    } else {
+                        ix.map_kk[idx][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal {
+            ix.map_kk[idx][second] = code;
+            code += 1;
+        }
+        ix.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                ix.binomial[k][n] = if k > 0 { ix.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { ix.binomial[k][n - 1] } else { 0 };
+            }
+        }
+        // pawns can't be on the first or last rank, and every rank the leading pawn goes up
+        // leaves two squares (that rank on its file and the mirrored one) out for the others
+        let mut available = 47;
+        for lead in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let square = 8 * rank + file;
+                    if lead == 1 {
+                        ix.map_pawns[square] = available;
+                        ix.map_pawns[square ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    ix.lead_pawn_idx[lead][square] = idx;
+                    idx += ix.binomial[lead - 1][ix.map_pawns[square]];
+                }
+                ix.lead_pawns_size[lead][file] = idx;
+            }
+        }
+        ix
+    })
+}
+
+fn read_u8(data: &[u8], at: usize) -> Option<u8> {
+    data.get(at).copied()
+}
+
+fn read_u16_le(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32_le(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u32_be(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64_be(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/*
+One of the compressed sub-tables of a file, for one side to move and (with pawns) one file of
+the leading pawn. Offsets point into the file
+*/
+#[derive(Default)]
+struct Pairs {
+    flags: u8,
+    pieces: Vec<u8>, // order the pieces are encoded in, which also makes the groups
+    group_len: Vec<usize>, // pieces in each group: KRvKN is (3, 1)
+    group_idx: Vec<u64>, // factor of each group in the index, the last one is the table size
+    block_size: usize,
+    span: usize, // about every span values there's an entry in the sparse index
+    num_blocks: usize,
+    min_sym_len: u8, // or the value itself, when all positions have the same one
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>, // values (less one) behind each symbol
+    btree: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    block_lengths: usize,
+    block_length_size: usize,
+    data: usize,
+    map_idx: [usize; 4], // where the DTZ values of each result start in the map
+}
+impl Pairs {
+    /*
+    Left and right halves of symbol sym, 12 bits each. A leaf keeps its value on the left
+    */
+    fn btree_pair(&self, file: &[u8], sym: usize) -> Option<(usize, usize)> {
+        let at = self.btree + 3 * sym;
+        let bytes = file.get(at..at + 3)?;
+        let left = ((bytes[1] as usize & 0xf) << 8) | bytes[0] as usize;
+        let right = ((bytes[2] as usize) << 4) | (bytes[1] as usize >> 4);
+        Some((left, right))
+    }
+    fn set_symlen(&mut self, file: &[u8], sym: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[sym] = true;
+        let (left, right) = self.btree_pair(file, sym)?;
+        if right == 0xfff {
+            return Some(0);
+        }
+        if left >= visited.len() || right >= visited.len() {
+            return None;
+        }
+        if !visited[left] {
+            self.symlen[left] = self.set_symlen(file, left, visited)?;
+        }
+        if !visited[right] {
+            self.symlen[right] = self.set_symlen(file, right, visited)?;
+        }
+        Some(
+            self.symlen[left]
+                .wrapping_add(self.symlen[right])
+                .wrapping_add(1),
+        )
+    }
+    /*
+    Reads the Huffman code and block sizes starting at at, returns where they end
+    */
+    fn set_sizes(&mut self, file: &[u8], mut at: usize) -> Option<usize> {
+        self.flags = read_u8(file, at)?;
+        at += 1;
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            self.min_sym_len = read_u8(file, at)?;
+            return Some(at + 1);
+        }
+        let table_size = *self.group_idx.last()?;
+        self.block_size = 1 << read_u8(file, at)?;
+        self.span = 1 << read_u8(file, at + 1)?;
+        self.sparse_index_size = table_size.div_ceil(self.span as u64) as usize;
+        let padding = read_u8(file, at + 2)? as usize;
+        self.num_blocks = read_u32_le(file, at + 3)? as usize;
+        // padded so the sparse index never points past the end
+        self.block_length_size = self.num_blocks + padding;
+        let max_sym_len = read_u8(file, at + 7)?;
+        self.min_sym_len = read_u8(file, at + 8)?;
+        at += 9;
+        if max_sym_len < self.min_sym_len || max_sym_len > 64 {
+            return None;
+        }
+        self.lowest_sym = at;
+        // longer codes have lower values, base64[l] is the lowest code of length
+        // min_sym_len + l padded to 64 bits
+        let lengths = (max_sym_len - self.min_sym_len) as usize + 1;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16_le(file, at + 2 * i)? as u64;
+            let next_lowest = read_u16_le(file, at + 2 * i + 2)? as u64;
+            self.base64[i] = self.base64[i + 1]
+                .wrapping_add(lowest)
+                .wrapping_sub(next_lowest)
+                / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base
+                .checked_shl(64 - i as u32 - self.min_sym_len as u32)
+                .unwrap_or(0);
+        }
+        at += 2 * lengths;
+        let symbols = read_u16_le(file, at)? as usize;
+        at += 2;
+        self.btree = at;
+        // every symbol stands for a pair of others, down to the values themselves
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.set_symlen(file, sym, &mut visited)?;
+            }
+        }
+        Some(at + 3 * symbols + (symbols & 1))
+    }
+    /*
+    The value stored for position idx
+    */
+    fn decompress(&self, file: &[u8], idx: u64) -> Option<i32> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(self.min_sym_len as i32);
+        }
+        // the sparse index gives the block and offset of every span-th value, from there
+        // walk the block lengths to the block holding idx
+        let k = (idx / self.span as u64) as usize;
+        let entry = self.sparse_index + 6 * k;
+        let mut block = read_u32_le(file, entry)? as usize;
+        let mut offset = read_u16_le(file, entry + 4)? as i64;
+        offset += (idx % self.span as u64) as i64 - (self.span / 2) as i64;
+        let block_length = |block: usize| -> Option<i64> {
+            Some(read_u16_le(file, self.block_lengths + 2 * block)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+        // then read symbols off the block until the one covering offset
+        let mut at = self.data + block * self.block_size;
+        let mut buffer = read_u64_be(file, at)?;
+        at += 8;
+        let mut buffer_bits = 64;
+        let min_sym_len = self.min_sym_len as u32;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buffer < self.base64[len] {
+                len += 1;
+                if len == self.base64.len() {
+                    return None;
+                }
+            }
+            let code = (buffer - self.base64[len])
+                .checked_shr(64 - len as u32 - min_sym_len)
+                .unwrap_or(0);
+            sym =
+                (code as u16).wrapping_add(read_u16_le(file, self.lowest_sym + 2 * len)?) as usize;
+            let values = *self.symlen.get(sym)? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            let len = len as u32 + min_sym_len;
+            buffer = buffer.checked_shl(len).unwrap_or(0);
+            buffer_bits -= len as i32;
+            if buffer_bits <= 32 {
+                buffer_bits += 32;
+                buffer |= (read_u32_be(file, at)? as u64) << (64 - buffer_bits);
+                at += 4;
+            }
+        }
+        // and down the pairs to the value itself
+        while *self.symlen.get(sym)? != 0 {
+            let (left, right) = self.btree_pair(file, sym)?;
+            let values = *self.symlen.get(left)? as i64 + 1;
+            if offset < values {
+                sym = left;
+            } else {
+                offset -= values;
+                sym = right;
+            }
+        }
+        Some(self.btree_pair(file, sym)?.0 as i32)
+    }
+}
+
+/*
+Piece codes the way the tables have them: pawn 1 up to king 6, plus 8 for black
+*/
+fn piece_code(name: &str, white: bool) -> u8 {
+    let role = match name {
+        "pawn" => 1,
+        "knight" => 2,
+        "bishop" => 3,
+        "rook" => 4,
+        "queen" => 5,
+        _ => 6,
+    };
+    if white {
+        role
+    } else {
+        role + 8
+    }
+}
+
+const PAWN: u8 = 1;
+
+/*
+"KRP": the king and then a side's pieces from the queen down to the pawns, as files are named
+*/
+fn side_name(game: &Game, white: bool) -> String {
+    let mut name = String::from("K");
+    for (letter, piece) in [
+        ('Q', "queen"),
+        ('R', "rook"),
+        ('B', "bishop"),
+        ('N', "knight"),
+        ('P', "pawn"),
+    ] {
+        for p in game.pieces.values() {
+            if p.white == white && p.name == piece {
+                name.push(letter);
+            }
+        }
+    }
+    name
+}
+
+/*
+An opened .rtbw or .rtbz file. "White" is the first side in its name
+*/
+struct Table {
+    file: Mmap,
+    dtz: bool,
+    symmetric: bool, // same pieces on both sides: only white to move is stored
+    has_pawns: bool,
+    unique_pieces: bool, // some side has exactly one of a kind besides the king
+    pawns: [usize; 2],   // of the leading side (the one with fewer pawns) and the other one
+    piece_count: usize,
+    parts: Vec<Vec<Pairs>>, // [side to move][file of the leading pawn]
+    map: usize,             // start of the DTZ value maps
+}
+impl Table {
+    fn open(path: &PathBuf, material: &str, dtz: bool) -> io::Result<Table> {
+        let corrupt = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a valid table", path.display()),
+            )
+        };
+        // SAFETY: tables are only ever read, nobody is expected to change them while we run
+        let file = unsafe { Mmap::map(&File::open(path)?)? };
+        let magic = if dtz { DTZ_MAGIC } else { WDL_MAGIC };
+        if file.get(..4) != Some(&magic[..]) {
+            return Err(corrupt());
+        }
+        let (white, black) = material.split_once('v').ok_or_else(corrupt)?;
+        let count = |side: &str, letter: char| side.chars().filter(|c| *c == letter).count();
+        let unique_pieces = [white, black]
+            .iter()
+            .any(|side| "QRBNP".chars().any(|letter| count(side, letter) == 1));
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let mut table = Table {
+            file,
+            dtz,
+            symmetric: white == black,
+            has_pawns: white_pawns + black_pawns > 0,
+            unique_pieces,
+            pawns: if white_leads {
+                [white_pawns, black_pawns]
+            } else {
+                [black_pawns, white_pawns]
+            },
+            piece_count: white.len() + black.len(),
+            parts: Vec::new(),
+            map: 0,
+        };
+        table.read_header().ok_or_else(corrupt)?;
+        Ok(table)
+    }
+    fn read_header(&mut self) -> Option<()> {
+        let file: &[u8] = &self.file;
+        let flags = read_u8(file, 4)?;
+        // split tables have both sides to move, files with pawns a part per file a to d
+        if (flags & 2 != 0) != self.has_pawns || (flags & 1 != 0) == self.symmetric {
+            return None;
+        }
+        let sides = if !self.dtz && !self.symmetric { 2 } else { 1 };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let both_pawns = self.has_pawns && self.pawns[1] > 0;
+        let mut parts: Vec<Vec<Pairs>> = (0..sides)
+            .map(|_| (0..files).map(|_| Pairs::default()).collect())
+            .collect();
+        let mut at = 5;
+        for f in 0..files {
+            let first = read_u8(file, at)?;
+            let second = if both_pawns {
+                read_u8(file, at + 1)?
+            } else {
+                0xff
+            };
+            let order = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            at += 1 + both_pawns as usize;
+            for _ in 0..self.piece_count {
+                let byte = read_u8(file, at)?;
+                for (side, part) in parts.iter_mut().enumerate() {
+                    part[f]
+                        .pieces
+                        .push(if side == 1 { byte >> 4 } else { byte & 0xf });
+                }
+                at += 1;
+            }
+            for (side, part) in parts.iter_mut().enumerate() {
+                self.set_groups(&mut part[f], order[side], f);
+            }
+        }
+        at += at & 1;
+        for f in 0..files {
+            for part in parts.iter_mut() {
+                at = part[f].set_sizes(file, at)?;
+            }
+        }
+        if self.dtz {
+            self.map = at;
+            for pairs in parts[0].iter_mut() {
+                if pairs.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if pairs.flags & FLAG_WIDE != 0 {
+                    at += at & 1;
+                    for i in 0..4 {
+                        pairs.map_idx[i] = (at - self.map) / 2 + 1;
+                        at += 2 * read_u16_le(file, at)? as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        pairs.map_idx[i] = at - self.map + 1;
+                        at += read_u8(file, at)? as usize + 1;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+        for f in 0..files {
+            for part in parts.iter_mut() {
+                part[f].sparse_index = at;
+                at += 6 * part[f].sparse_index_size;
+            }
+        }
+        for f in 0..files {
+            for part in parts.iter_mut() {
+                part[f].block_lengths = at;
+                at += 2 * part[f].block_length_size;
+            }
+        }
+        for f in 0..files {
+            for part in parts.iter_mut() {
+                // blocks start on 64 byte boundaries
+                at = (at + 0x3f) & !0x3f;
+                part[f].data = at;
+                at += part[f].num_blocks * part[f].block_size;
+            }
+        }
+        if at > file.len() {
+            return None;
+        }
+        self.parts = parts;
+        Some(())
+    }
+    /*
+    Splits the pieces into the groups they're encoded in and works out what each group is worth
+    in the index. Without pawns the first group is three unique pieces or the two kings
+    */
+    fn set_groups(&self, pairs: &mut Pairs, order: [u8; 2], file: usize) {
+        let ix = indexes();
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.unique_pieces {
+            3
+        } else {
+            2
+        };
+        pairs.group_len = vec![1];
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || pairs.pieces[i] == pairs.pieces[i - 1] {
+                *pairs.group_len.last_mut().unwrap() += 1;
+            } else {
+                pairs.group_len.push(1);
+            }
+        }
+        let groups = pairs.group_len.len();
+        let both_pawns = self.has_pawns && self.pawns[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free = 64 - pairs.group_len[0] - if both_pawns { pairs.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+        pairs.group_idx = vec![0; groups + 1];
+        let mut k = 0;
+        while next < groups || k == order[0] || k == order[1] {
+            if k == order[0] {
+                pairs.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    ix.lead_pawns_size[pairs.group_len[0]][file]
+                } else if self.unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                pairs.group_idx[1] = idx;
+                idx *= ix.binomial[pairs.group_len[1]][48 - pairs.group_len[0]];
+            } else {
+                pairs.group_idx[next] = idx;
+                idx *= ix.binomial[pairs.group_len[next]][free];
+                free -= pairs.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        pairs.group_idx[groups] = idx;
+    }
+    /*
+    DTZ tables keep their values sorted by how often they come up, per result, and count
+    in moves rather than plies where that's exact
+    */
+    fn map_score(&self, file: usize, value: i32, wdl: Wdl) -> Option<i32> {
+        if !self.dtz {
+            return Some(value - 2);
+        }
+        let pairs = &self.parts[0][file];
+        let mut value = value;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let start = pairs.map_idx[[1, 3, 0, 2, 0][(wdl.value() + 2) as usize]];
+            let at = start + value as usize;
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                read_u16_le(&self.file, self.map + 2 * at)? as i32
+            } else {
+                read_u8(&self.file, self.map + at)? as i32
+            };
+        }
+        let plies = match wdl {
+            Wdl::Win => pairs.flags & FLAG_WIN_PLIES != 0,
+            Wdl::Loss => pairs.flags & FLAG_LOSS_PLIES != 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => false,
+            Wdl::Draw => true,
+        };
+        if !plies {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+}
+
+/*
+A table lookup either finds the value or, for DTZ tables that only keep the other side to
+move, says so
+*/
+enum Lookup {
+    Value(i32),
+    OtherSide,
+}
+
+/*
+The tables found in some directories, opened the first time they're needed
+*/
+pub struct Tablebase {
+    files: HashMap<String, PathBuf>, // "KRvK.rtbw" -> where it is
+    max_pieces: usize,
+    tables: Mutex<HashMap<String, Result<Arc<Table>, ProbeError>>>,
+}
+
+/*
+How good a move is for the side making it, with the DTZ after it from that side
+*/
+#[derive(Clone)]
+pub struct RatedMove {
+    pub mov: Move,
+    pub wdl: Wdl,
+    pub dtz: i32,
+}
+
+fn is_capture(game: &Game, mov: &Move) -> bool {
+    game.pieces.contains_key(&(mov.to.column, mov.to.row))
+        || (mov.from.column != mov.to.column && is_pawn_move(game, mov))
+}
+
+fn is_pawn_move(game: &Game, mov: &Move) -> bool {
+    game.pieces
+        .get(&(mov.from.column, mov.from.row))
+        .is_some_and(|p| p.name == "pawn")
+}
+
+fn after(game: &Game, mov: &Move) -> Game {
+    let mut child: Game = game.clone();
+    let _ = child.play(mov);
+    child
+}
+
+/*
+"KQvKR" with the white pieces first, None if that's not a name tables have
+*/
+fn material_of_file(name: &str) -> Option<(&str, usize)> {
+    let material = name
+        .strip_suffix(".rtbw")
+        .or_else(|| name.strip_suffix(".rtbz"))?;
+    let (white, black) = material.split_once('v')?;
+    let side_ok =
+        |side: &str| side.starts_with('K') && side[1..].chars().all(|c| "QRBNP".contains(c));
+    if !side_ok(white) || !side_ok(black) {
+        return None;
+    }
+    Some((material, white.len() + black.len()))
+}
+
+impl Tablebase {
+    /*
+    Looks for tables in the directories listed in paths, separated like in PATH
+    */
+    pub fn open(paths: &str) -> io::Result<Tablebase> {
+        let mut files: HashMap<String, PathBuf> = HashMap::new();
+        let mut max_pieces = 0;
+        for dir in env::split_paths(paths) {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if let Some((_, pieces)) = material_of_file(name) {
+                    max_pieces = max_pieces.max(pieces);
+                    files.entry(name.to_string()).or_insert(path.clone());
+                }
+            }
+        }
+        Ok(Tablebase {
+            files,
+            max_pieces,
+            tables: Mutex::new(HashMap::new()),
+        })
+    }
+    pub fn table_count(&self) -> usize {
+        self.files.len()
+    }
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+    fn table(&self, name: &str) -> Result<Arc<Table>, ProbeError> {
+        let mut tables = self.tables.lock().unwrap();
+        if let Some(table) = tables.get(name) {
+            return table.clone();
+        }
+        let missing = || ProbeError::Missing(name.to_string());
+        let path = self.files.get(name).ok_or_else(missing)?;
+        let (material, _) = material_of_file(name).ok_or_else(missing)?;
+        // kept either way, a table that can't be used isn't opened again
+        let table = Table::open(path, material, name.ends_with(".rtbz"))
+            .map(Arc::new)
+            .map_err(|e| ProbeError::Broken(format!("can't use tablebase {}: {}", name, e)));
+        tables.insert(name.to_string(), table.clone());
+        table
+    }
+    /*
+    Whether game is small enough and has no castling rights, tables have neither. They're
+    for normal chess only
+    */
+    fn covers(&self, game: &Game) -> bool {
+        game.pieces.len() <= self.max_pieces
+            && !castling_rights(game).contains(&true)
+            && game.variant.name() == STANDARD.name()
+    }
+    /*
+    The stored value for game, which has to be in the tables. wdl is the result, for DTZ tables
+    */
+    fn probe_table(&self, game: &Game, dtz: bool, wdl: Wdl) -> Result<Lookup, ProbeError> {
+        if game.pieces.len() == 2 {
+            return Ok(Lookup::Value(0));
+        }
+        let white = side_name(game, true);
+        let black = side_name(game, false);
+        let extension = if dtz { "rtbz" } else { "rtbw" };
+        let straight = format!("{}v{}.{}", white, black, extension);
+        let swapped = format!("{}v{}.{}", black, white, extension);
+        let (name, black_stronger) = if self.files.contains_key(&straight) {
+            (straight, false)
+        } else if self.files.contains_key(&swapped) {
+            (swapped, true)
+        } else {
+            // named the way the tables are, the side with more pieces first
+            return Err(ProbeError::Missing(if black.len() > white.len() {
+                swapped
+            } else {
+                straight
+            }));
+        };
+        let table = self.table(&name)?;
+        Self::lookup(&table, game, black_stronger, wdl)
+            .ok_or_else(|| ProbeError::Broken(format!("{} doesn't have this position", name)))
+    }
+    /*
+    Where game is in table and what's kept there, None if it doesn't fit
+    */
+    fn lookup(table: &Table, game: &Game, black_stronger: bool, wdl: Wdl) -> Option<Lookup> {
+        let ix = indexes();
+        // tables are stored with their first side as white, so when black is that side (or
+        // both are the same and black is to move) colors and ranks get swapped
+        let flip = black_stronger || (table.symmetric && !game.white_to_move);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ !game.white_to_move as usize;
+        let mut placed: Vec<(usize, u8)> = game
+            .pieces
+            .values()
+            .map(|p| {
+                let square = 8 * (p.position.row - 1) + letter_to_int(p.position.column) - 1;
+                (square as usize, piece_code(&p.name, p.white))
+            })
+            .collect();
+        placed.sort();
+        let mut squares: Vec<usize> = Vec::new();
+        let mut codes: Vec<u8> = Vec::new();
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        let mut lead_code = 0;
+        if table.has_pawns {
+            // the pawns of the leading side come first, the one most toward the edge and
+            // lowest up the board leading them
+            lead_code = table.parts[0][0].pieces[0] ^ flip_color;
+            if lead_code & 7 != PAWN {
+                return None;
+            }
+            for &(square, code) in placed.iter().filter(|(_, c)| *c == lead_code) {
+                squares.push(square ^ flip_squares);
+                codes.push(code ^ flip_color);
+            }
+            lead_pawns = squares.len();
+            let leader = (0..lead_pawns).max_by_key(|&i| ix.map_pawns[squares[i]])?;
+            squares.swap(0, leader);
+            file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+        let one_side_stored = table.dtz && (table.has_pawns || !table.symmetric);
+        if one_side_stored && table.parts[0][file].flags & FLAG_STM != stm as u8 {
+            return Some(Lookup::OtherSide);
+        }
+        for &(square, code) in placed.iter() {
+            if !(table.has_pawns && code == lead_code) {
+                squares.push(square ^ flip_squares);
+                codes.push(code ^ flip_color);
+            }
+        }
+        let size = squares.len();
+        if size != table.piece_count {
+            return None;
+        }
+        let side = if table.parts.len() == 1 { 0 } else { stm };
+        let pairs = &table.parts[side][file];
+        // line the pieces up the way the table encodes them
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if pairs.pieces[i] == codes[j] {
+                    codes.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+        // the leading piece goes to files a to d
+        if file_of(squares[0]) > 3 {
+            for square in squares.iter_mut() {
+                *square ^= 7;
+            }
+        }
+        let mut idx: u64;
+        if table.has_pawns {
+            idx = ix.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&s| ix.map_pawns[s]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += ix.binomial[i][ix.map_pawns[square]];
+            }
+        } else {
+            // without pawns also to ranks 1 to 4, and under the a1-h8 diagonal
+            if rank_of(squares[0]) > 3 {
+                for square in squares.iter_mut() {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..pairs.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for square in squares[i..].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+            if table.unique_pieces {
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as usize;
+                let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+                let (r0, r1, r2) = (rank_of(s0), rank_of(s1), rank_of(s2));
+                idx = if off_diagonal(s0) != 0 {
+                    (ix.map_a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+                } else if off_diagonal(s1) != 0 {
+                    (6 * 63 + r0 * 28 + ix.map_b1h1h7[s1]) * 62 + s2 - adjust2
+                } else if off_diagonal(s2) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + r0 * 7 * 28
+                        + (r1 - adjust1) * 28
+                        + ix.map_b1h1h7[s2]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + r0 * 7 * 6
+                        + (r1 - adjust1) * 6
+                        + (r2 - adjust2)
+                } as u64;
+            } else {
+                idx = ix.map_kk[ix.map_a1d1d4[squares[0]]][squares[1]] as u64;
+            }
+        }
+        // then each group in turn, its squares counted past the ones already taken
+        idx *= pairs.group_idx[0];
+        let mut start = pairs.group_len[0];
+        let mut remaining_pawns = table.has_pawns && table.pawns[1] > 0;
+        for next in 1..pairs.group_len.len() {
+            let len = pairs.group_len[next];
+            squares[start..start + len].sort();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let taken = squares[..start].iter().filter(|&&s| square > s).count();
+                let pawn_rank = if remaining_pawns { 8 } else { 0 };
+                n += ix.binomial[i + 1][square.checked_sub(taken + pawn_rank)?];
+            }
+            remaining_pawns = false;
+            idx += n * pairs.group_idx[next];
+            start += len;
+        }
+        let value = pairs.decompress(&table.file, idx)?;
+        Some(Lookup::Value(table.map_score(file, value, wdl)?))
+    }
+    /*
+    Tables treat positions where a capture wins as "don't care" and may store a loss where
+    a capture draws, so captures (and for DTZ pawn moves) are tried along with the table.
+    Also says whether the best move is one of those
+    */
+    fn search(&self, game: &Game, pawn_moves: bool) -> Result<(Wdl, bool), ProbeError> {
+        let moves = game.legal_moves();
+        let mut best = Wdl::Loss;
+        let mut tried = 0;
+        for mov in &moves {
+            let zeroing = is_capture(game, mov) || (pawn_moves && is_pawn_move(game, mov));
+            if !zeroing {
+                continue;
+            }
+            tried += 1;
+            let value = self.search(&after(game, mov), false)?.0.negate();
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+        // with every move tried the table isn't needed, and could be wrong (en passant)
+        let no_more_moves = tried > 0 && tried == moves.len();
+        let value = if no_more_moves {
+            best
+        } else {
+            match self.probe_table(game, false, Wdl::Draw)? {
+                Lookup::Value(value) => Wdl::from_value(value),
+                Lookup::OtherSide => {
+                    return Err(ProbeError::Broken(
+                        "a WDL table with one side to move".to_string(),
+                    ))
+                }
+            }
+        };
+        if best >= value {
+            return Ok((best, best > Wdl::Draw || no_more_moves));
+        }
+        Ok((value, false))
+    }
+    fn dtz(&self, game: &Game) -> Result<i32, ProbeError> {
+        let (wdl, zeroing_best) = self.search(game, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing_best {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+        let sign = wdl.value().signum();
+        match self.probe_table(game, true, wdl)? {
+            Lookup::Value(dtz) => {
+                let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+                Ok((dtz + if cursed { 100 } else { 0 }) * sign)
+            }
+            // only the other side to move is stored, so look one move further
+            Lookup::OtherSide => {
+                let mut min_dtz = 0xffff;
+                for mov in game.legal_moves() {
+                    let zeroing = is_capture(game, &mov) || is_pawn_move(game, &mov);
+                    let child = after(game, &mov);
+                    let mut dtz = if zeroing {
+                        -dtz_before_zeroing(self.search(&child, false)?.0)
+                    } else {
+                        -self.dtz(&child)?
+                    };
+                    if dtz == 1 && child.is_mate() == 1 {
+                        min_dtz = 1;
+                    }
+                    if !zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz < min_dtz && dtz.signum() == sign {
+                        min_dtz = dtz;
+                    }
+                }
+                Ok(if min_dtz == 0xffff { -1 } else { min_dtz })
+            }
+        }
+    }
+    /*
+    Result of game with perfect play, or why the tables can't tell
+    */
+    pub fn probe_wdl(&self, game: &Game) -> Result<Wdl, ProbeError> {
+        if !self.covers(game) {
+            return Err(ProbeError::NotCovered);
+        }
+        Ok(self.search(game, false)?.0)
+    }
+    /*
+    Plies to the next capture or pawn move with perfect play, positive when the side to move
+    wins. Past 100 the win or loss is cursed
+    */
+    pub fn probe_dtz(&self, game: &Game) -> Result<i32, ProbeError> {
+        if !self.covers(game) {
+            return Err(ProbeError::NotCovered);
+        }
+        self.dtz(game)
+    }
+    /*
+    Every legal move with what it leads to, the best first: the fastest wins, then draws, then
+    the slowest losses
+    */
+    pub fn rate_moves(&self, game: &Game) -> Result<Vec<RatedMove>, ProbeError> {
+        if !self.covers(game) {
+            return Err(ProbeError::NotCovered);
+        }
+        let mut moves: Vec<RatedMove> = Vec::new();
+        for mov in game.legal_moves() {
+            let child = after(game, &mov);
+            let mut dtz = if is_capture(game, &mov) || is_pawn_move(game, &mov) {
+                dtz_before_zeroing(self.search(&child, false)?.0.negate())
+            } else {
+                let dtz = -self.dtz(&child)?;
+                dtz + dtz.signum()
+            };
+            // a mate is as close to zeroing as it gets
+            if dtz == 2 && child.is_mate() == 1 {
+                dtz = 1;
+            }
+            moves.push(RatedMove {
+                mov,
+                wdl: Wdl::from_dtz(dtz),
+                dtz,
+            });
+        }
+        let rank = |dtz: i32| match dtz {
+            1.. => 1000 - dtz,
+            0 => 0,
+            _ => -1000 - dtz,
+        };
+        moves.sort_by_key(|m| -rank(m.dtz));
+        Ok(moves)
+    }
+}
+
+static TABLEBASE: OnceLock<Tablebase> = OnceLock::new();
+
+/*
+Makes the tables in paths the ones everything uses, the engine searching included. Only the
+first call counts
+*/
+pub fn load(paths: &str) -> io::Result<&'static Tablebase> {
+    if TABLEBASE.get().is_none() {
+        let _ = TABLEBASE.set(Tablebase::open(paths)?);
+    }
+    Ok(TABLEBASE.get().unwrap())
+}
+
+pub fn tablebase() -> Option<&'static Tablebase> {
+    TABLEBASE.get()
+}
+
+/*
+"1-0", "0-1" or "1/2-1/2" once the tables know how game ends, the fifty move rule counting
+*/
+pub fn adjudicate(game: &Game) -> Option<&'static str> {
+    let wdl = tablebase()?.probe_wdl(game).ok()?;
+    Some(match (wdl, game.white_to_move) {
+        (Wdl::Win, true) | (Wdl::Loss, false) => "1-0",
+        (Wdl::Win, false) | (Wdl::Loss, true) => "0-1",
+        _ => "1/2-1/2",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{search_lines, Limits, Score};
+    use crate::uci::uci_move;
+
+    // KQvK, KRvK and KPvK (with KBvK and KNvK for underpromotions), see the README there
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+
+    fn game(fen: &str) -> Game {
+        Game::from_fen(fen).unwrap()
+    }
+
+    /*
+    A directory of its own under the temp dir with files made up by the test
+    */
+    fn scratch(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chess-tablebase-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn missing_table_is_an_error() {
+        let dir = scratch("missing", &[("KRvK.rtbw", &WDL_MAGIC)]);
+        let tables = Tablebase::open(dir.to_str().unwrap()).unwrap();
+        let kqk = game("4k3/8/8/8/8/8/8/4K2Q w - - 0 1");
+        assert_eq!(
+            tables.probe_wdl(&kqk),
+            Err(ProbeError::Missing("KQvK.rtbw".to_string()))
+        );
+        // named by the stronger side even when it's black's
+        let kkq = game("4k2q/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(
+            tables.probe_dtz(&kkq).unwrap_err(),
+            ProbeError::Missing("KQvK.rtbw".to_string())
+        );
+        assert!(tables.rate_moves(&kqk).is_err());
+    }
+
+    #[test]
+    fn broken_table_is_an_error() {
+        let mut truncated = WDL_MAGIC.to_vec();
+        truncated.extend([1, 0, 0x66, 0x55]);
+        let dir = scratch(
+            "broken",
+            &[("KRvK.rtbw", &truncated), ("KQvK.rtbw", b"nonsense")],
+        );
+        let tables = Tablebase::open(dir.to_str().unwrap()).unwrap();
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K2R w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K2Q b - - 0 1",
+        ] {
+            assert!(matches!(
+                tables.probe_wdl(&game(fen)),
+                Err(ProbeError::Broken(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn only_small_standard_positions_are_covered() {
+        let dir = scratch("covered", &[("KRvK.rtbw", &WDL_MAGIC)]);
+        let tables = Tablebase::open(dir.to_str().unwrap()).unwrap();
+        assert_eq!(tables.max_pieces(), 3);
+        let start = game(crate::fen::START_FEN);
+        assert_eq!(tables.probe_wdl(&start), Err(ProbeError::NotCovered));
+        // bare kings need no table at all
+        let kings = game("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(tables.probe_wdl(&kings), Ok(Wdl::Draw));
+        assert_eq!(tables.probe_dtz(&kings), Ok(0));
+    }
+
+    fn fixtures() -> Tablebase {
+        Tablebase::open(FIXTURES).unwrap()
+    }
+
+    #[test]
+    fn known_wdl() {
+        let tables = fixtures();
+        let cases = [
+            ("4k3/8/4K3/8/8/8/8/7Q w - - 0 1", Wdl::Win),
+            ("4k3/8/4K3/8/8/8/8/7Q b - - 0 1", Wdl::Loss),
+            // stalemate
+            ("k7/8/1K6/8/8/8/7Q/8 b - - 0 1", Wdl::Draw),
+            // the rook hangs
+            ("8/8/8/8/8/8/1k6/R6K b - - 0 1", Wdl::Draw),
+            ("8/8/8/8/8/8/1k6/R6K w - - 0 1", Wdl::Win),
+            // king in front of its pawn on the sixth
+            ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", Wdl::Win),
+            ("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", Wdl::Loss),
+            // king behind it on the sixth, the black king holds either way
+            ("4k3/8/4P3/4K3/8/8/8/8 w - - 0 1", Wdl::Draw),
+            ("4k3/8/4P3/4K3/8/8/8/8 b - - 0 1", Wdl::Draw),
+            // further back, whoever has to move gives up the opposition
+            ("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1", Wdl::Draw),
+            ("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1", Wdl::Loss),
+        ];
+        for (fen, wdl) in cases {
+            assert_eq!(tables.probe_wdl(&game(fen)), Ok(wdl), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn known_dtz() {
+        let tables = fixtures();
+        // Qh8 is the only mate
+        let mate_in_one = game("k7/8/1K6/8/8/8/7Q/8 w - - 0 1");
+        assert_eq!(tables.probe_dtz(&mate_in_one), Ok(1));
+        let rated = tables.rate_moves(&mate_in_one).unwrap();
+        assert_eq!((rated[0].mov.to.column, rated[0].mov.to.row), ('H', 8));
+        assert_eq!((rated[0].wdl, rated[0].dtz), (Wdl::Win, 1));
+        assert!(rated[1].dtz > 1);
+        // the longest mates: ten moves with the queen, sixteen with the rook
+        let queen = game("8/8/4k3/8/8/8/1Q6/K7 w - - 0 1");
+        assert_eq!(tables.probe_dtz(&queen), Ok(19));
+        let colors_swapped = game("k7/1q6/8/8/8/4K3/8/8 b - - 0 1");
+        assert_eq!(tables.probe_dtz(&colors_swapped), Ok(19));
+        let rook = game("8/8/8/8/8/2k5/1R6/K7 w - - 0 1");
+        assert_eq!(tables.probe_dtz(&rook), Ok(31));
+        let hanging_rook = game("8/8/8/8/8/8/1k6/R6K b - - 0 1");
+        assert_eq!(tables.probe_dtz(&hanging_rook), Ok(0));
+        // the king is in the pawn's way, it steps aside and the pawn goes on the move after
+        let blocked = game("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1");
+        assert_eq!(tables.probe_dtz(&blocked), Ok(3));
+    }
+
+    #[test]
+    fn engine_lines_come_from_the_tables() {
+        load(FIXTURES).unwrap();
+        let mate_in_one = game("k7/8/1K6/8/8/8/7Q/8 w - - 0 1");
+        let lines = search_lines(&mate_in_one, &Limits::default(), 3, None);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(uci_move(&lines[0].best), "h2h8");
+        // the others win too, in two
+        let scores: Vec<Score> = lines.iter().map(|line| line.score).collect();
+        assert_eq!(
+            scores,
+            [Score::Cp(19_999), Score::Cp(19_997), Score::Cp(19_997)]
+        );
+    }
+}
@@ -0,0 +1,426 @@
+use std::io::{self, BufRead, Write};
+
+use chess::ai::{search, Limits, Score};
+use chess::aux_func::int_to_letter;
+use chess::game::{init_pieces, Game, Termination};
+use chess::moves::Move;
+use chess::notation::{parse_san, san, write_pgn};
+use chess::position::Position;
+
+// how deep "hint" looks
+const HINT_DEPTH: u32 = 4;
+
+// 256 colour backgrounds for the squares
+const LIGHT: u8 = 180;
+const DARK: u8 = 137;
+const LAST_MOVE_LIGHT: u8 = 186;
+const LAST_MOVE_DARK: u8 = 143;
+const CHECK: u8 = 160;
+
+const HELP: &str = "Moves in SAN (e4, Nf3, O-O, e8=Q) or UCI (e2e4, e7e8q). Commands:
+  undo     take back the last move
+  flip     turn the board around
+  fen      the position in FEN
+  pgn      the game so far in PGN
+  hint     what the engine would play
+  resign   give up the game
+  new      start over
+  quit     leave";
+
+/*
+The built-in engine playing one side of a terminal game
+*/
+#[derive(Clone, Copy)]
+pub struct Opponent {
+    pub white: bool,
+    pub depth: u32,
+}
+
+/*
+A game of chess between two people sharing the terminal, or one of them and the engine
+*/
+struct Terminal {
+    start: Game,
+    game: Game,
+    sans: Vec<String>, // the moves played, for the PGN and the last move line
+    keys: Vec<String>, // position keys since start, for repetitions
+    over: Option<(String, String)>, // result and how it ended
+    flipped: bool,     // turned around on top of following the side to move
+    color: bool,       // ANSI colours, off when output isn't a terminal
+    engine: Option<Opponent>,
+}
+
+/*
+Unicode symbol of a piece, the solid ones when colours tell the sides apart
+*/
+fn symbol(name: &str, white: bool, color: bool) -> char {
+    let index = match name {
+        "king" => 0,
+        "queen" => 1,
+        "rook" => 2,
+        "bishop" => 3,
+        "knight" => 4,
+        _ => 5,
+    };
+    if white && !color {
+        ['♔', '♕', '♖', '♗', '♘', '♙'][index]
+    } else {
+        ['♚', '♛', '♜', '♝', '♞', '♟'][index]
+    }
+}
+
+fn termination_text(termination: Termination) -> &'static str {
+    match termination {
+        Termination::Checkmate => "checkmate",
+        Termination::Stalemate => "stalemate",
+        Termination::InsufficientMaterial => "insufficient material",
+        Termination::Resignation => "resignation",
+        Termination::KingOfTheHill => "king reached the hill",
+        Termination::ThreeChecks => "third check",
+        Termination::Explosion => "king exploded",
+        Termination::Timeout => "time",
+        Termination::Abandoned => "abandoned",
+        Termination::Tablebase => "tablebase",
+    }
+}
+
+fn score_text(score: &Score) -> String {
+    match score {
+        Score::Cp(cp) => format!("{:+.2}", *cp as f64 / 100.0),
+        Score::Mate(moves) => format!("mate in {}", moves),
+    }
+}
+
+impl Terminal {
+    fn new(color: bool, engine: Option<Opponent>) -> Terminal {
+        let game = init_pieces();
+        Terminal {
+            keys: vec![game.position_key()],
+            start: game.clone(),
+            game,
+            sans: Vec::new(),
+            over: None,
+            flipped: false,
+            color,
+            engine,
+        }
+    }
+    fn black_at_bottom(&self) -> bool {
+        // against the engine the board stays on the player's side
+        let white_at_bottom = match self.engine {
+            Some(engine) => !engine.white,
+            None => self.game.white_to_move,
+        };
+        white_at_bottom == self.flipped
+    }
+    fn engine_to_move(&self) -> bool {
+        self.over.is_none()
+            && self
+                .engine
+                .is_some_and(|engine| engine.white == self.game.white_to_move)
+    }
+    /*
+    The board from the side to move's point of view, with the last move and a king in check
+    lit up
+    */
+    fn draw(&self, output: &mut impl Write) -> io::Result<()> {
+        let last: Option<&Move> = self.game.history.last();
+        let checked: Option<&Position> = if self.game.is_in_check(self.game.white_to_move) {
+            Some(if self.game.white_to_move {
+                &self.game.kings.0
+            } else {
+                &self.game.kings.1
+            })
+        } else {
+            None
+        };
+        let (rows, columns): (Vec<i32>, Vec<i32>) = if self.black_at_bottom() {
+            ((1..=8).collect(), (1..=8).rev().collect())
+        } else {
+            ((1..=8).rev().collect(), (1..=8).collect())
+        };
+        writeln!(output)?;
+        for row in &rows {
+            write!(output, " {} ", row)?;
+            for column in &columns {
+                let square = Position {
+                    column: int_to_letter(*column),
+                    row: *row,
+                };
+                let piece = self.game.pieces.get(&(square.column, square.row));
+                if !self.color {
+                    let shown = piece.map_or('·', |p| symbol(&p.name, p.white, false));
+                    write!(output, "{} ", shown)?;
+                    continue;
+                }
+                let light = (column + row) % 2 == 1;
+                let lit = last.is_some_and(|m| m.from.equals(&square) || m.to.equals(&square));
+                let background = match (checked.is_some_and(|k| k.equals(&square)), lit, light) {
+                    (true, _, _) => CHECK,
+                    (_, true, true) => LAST_MOVE_LIGHT,
+                    (_, true, false) => LAST_MOVE_DARK,
+                    (_, false, true) => LIGHT,
+                    (_, false, false) => DARK,
+                };
+                match piece {
+                    // bright white or black letters
+                    Some(p) => write!(
+                        output,
+                        "\x1b[48;5;{}m\x1b[{}m {} \x1b[0m",
+                        background,
+                        if p.white { 97 } else { 30 },
+                        symbol(&p.name, p.white, true)
+                    )?,
+                    None => write!(output, "\x1b[48;5;{}m   \x1b[0m", background)?,
+                }
+            }
+            writeln!(output)?;
+        }
+        let files: Vec<String> = columns
+            .iter()
+            .map(|c| int_to_letter(*c).to_ascii_lowercase().to_string())
+            .collect();
+        let gap = if self.color { "  " } else { " " };
+        let lead = if self.color { "    " } else { "   " };
+        writeln!(output, "{}{}\n", lead, files.join(gap))?;
+        if let Some(san) = self.sans.last() {
+            // the move number goes up after black's move
+            let (number, dots) = if self.game.white_to_move {
+                (self.game.fullmove_number - 1, "...")
+            } else {
+                (self.game.fullmove_number, ".")
+            };
+            writeln!(output, "Last move: {}{} {}", number, dots, san)?;
+        }
+        Ok(())
+    }
+    /*
+    Plays mov, then tells how the game stands
+    */
+    fn play(&mut self, mov: &Move, output: &mut impl Write) -> io::Result<()> {
+        let san = san(&self.game, mov).unwrap_or(mov.to_string());
+        let _ = self.game.play(mov);
+        self.keys.push(self.game.position_key());
+        self.sans.push(san);
+        self.draw(output)?;
+        if let Some((result, reason)) = self.result() {
+            writeln!(output, "Game over, {} by {}", result, reason)?;
+            self.over = Some((result, reason));
+        } else if self.game.is_in_check(self.game.white_to_move) {
+            writeln!(output, "Check!")?;
+        }
+        Ok(())
+    }
+    /*
+    Lets the engine move while it's its turn
+    */
+    fn reply(&mut self, output: &mut impl Write) -> io::Result<()> {
+        let Some(engine) = self.engine else {
+            return Ok(());
+        };
+        if !self.engine_to_move() {
+            return Ok(());
+        }
+        let limits = Limits {
+            depth: Some(engine.depth),
+            ..Limits::default()
+        };
+        if let Some(found) = search(&self.game, &limits) {
+            self.play(&found.best, output)?;
+        }
+        Ok(())
+    }
+    /*
+    Replays the game from the start without its last move
+    */
+    fn undo(&mut self) -> bool {
+        let mut history: Vec<Move> = self.game.history.clone();
+        if history.pop().is_none() {
+            return false;
+        }
+        self.game = self.start.clone();
+        self.keys.truncate(1);
+        for mov in &history {
+            let _ = self.game.play(mov);
+            self.keys.push(self.game.position_key());
+        }
+        self.sans.pop();
+        self.over = None;
+        true
+    }
+    /*
+    The game's result once it's over, with the draw rules a board alone can't tell
+    */
+    fn result(&self) -> Option<(String, String)> {
+        if let Some((result, termination)) = self.game.result() {
+            return Some((result, termination_text(termination).to_string()));
+        }
+        if self.game.halfmove_clock >= 100 {
+            return Some(("1/2-1/2".to_string(), "50 move rule".to_string()));
+        }
+        let current = self.keys.last()?;
+        if self.keys.iter().filter(|k| *k == current).count() >= 3 {
+            return Some(("1/2-1/2".to_string(), "threefold repetition".to_string()));
+        }
+        None
+    }
+    /*
+    The move text stands for, SAN first and then the UCI form, or what to tell the player
+    */
+    fn read_move(&self, text: &str) -> Result<Move, String> {
+        if let Some(mov) = parse_san(&self.game, text) {
+            return Ok(mov);
+        }
+        let Some(mov) = Move::parse(text) else {
+            return Err(format!(
+                "{:?} isn't a legal move here, type help for help",
+                text
+            ));
+        };
+        match self.game.clone().play(&mov) {
+            Ok(()) => Ok(mov),
+            Err(e) => Err(format!("{} isn't a legal move, {}", text, e)),
+        }
+    }
+    fn pgn(&self) -> String {
+        let result = self
+            .over
+            .as_ref()
+            .map_or("*", |(result, _)| result.as_str());
+        let mut tags: Vec<(&str, String)> = vec![
+            ("Event", "Casual game".to_string()),
+            ("Site", "?".to_string()),
+            ("Date", chrono::Utc::now().format("%Y.%m.%d").to_string()),
+            ("Round", "-".to_string()),
+            ("White", "?".to_string()),
+            ("Black", "?".to_string()),
+        ];
+        if let Some((_, reason)) = &self.over {
+            tags.push(("Termination", reason.clone()));
+        }
+        let moves: Vec<String> = self.game.history.iter().map(Move::to_string).collect();
+        write_pgn(&tags, &self.start, &moves, result)
+    }
+    fn side(&self) -> &'static str {
+        if self.game.white_to_move {
+            "White"
+        } else {
+            "Black"
+        }
+    }
+    /*
+    Handles one line typed in, false once the player wants out
+    */
+    fn command(&mut self, line: &str, output: &mut impl Write) -> io::Result<bool> {
+        match line.trim() {
+            "" => return Ok(true),
+            "quit" | "exit" => return Ok(false),
+            "help" => writeln!(output, "{}", HELP)?,
+            "fen" => writeln!(output, "{}", self.game.to_fen())?,
+            "pgn" => write!(output, "{}", self.pgn())?,
+            "flip" => {
+                self.flipped = !self.flipped;
+                self.draw(output)?;
+            }
+            "new" => {
+                *self = Terminal::new(self.color, self.engine);
+                self.draw(output)?;
+                self.reply(output)?;
+            }
+            "undo" => {
+                // a resignation is taken back on its own, the moves stay
+                if self
+                    .over
+                    .as_ref()
+                    .is_some_and(|(_, reason)| reason == "resignation")
+                {
+                    self.over = None;
+                    writeln!(output, "Resignation taken back")?;
+                } else if self.undo() {
+                    // the engine's move goes too, so it's the player's turn again
+                    if self.engine_to_move() && !self.undo() {
+                        self.reply(output)?;
+                    }
+                    self.draw(output)?;
+                } else {
+                    writeln!(output, "Nothing to take back")?;
+                }
+            }
+            _ if self.over.is_some() => {
+                writeln!(output, "The game is over, try undo, pgn or new")?;
+            }
+            "resign" => {
+                let result = if self.game.white_to_move {
+                    "0-1"
+                } else {
+                    "1-0"
+                };
+                writeln!(output, "{} resigns, {}", self.side(), result)?;
+                self.over = Some((result.to_string(), "resignation".to_string()));
+            }
+            "hint" => {
+                let limits = Limits {
+                    depth: Some(HINT_DEPTH),
+                    ..Limits::default()
+                };
+                match search(&self.game, &limits) {
+                    Some(found) => writeln!(
+                        output,
+                        "Try {} ({})",
+                        san(&self.game, &found.best).unwrap_or(found.best.to_string()),
+                        score_text(&found.score)
+                    )?,
+                    None => writeln!(output, "There's no move to play")?,
+                }
+            }
+            _ if self.engine_to_move() => {
+                writeln!(output, "It's the engine's move")?;
+            }
+            text => {
+                let mov = match self.read_move(text) {
+                    Ok(mov) => mov,
+                    Err(problem) => {
+                        writeln!(output, "{}", problem)?;
+                        return Ok(true);
+                    }
+                };
+                self.play(&mov, output)?;
+                self.reply(output)?;
+            }
+        }
+        Ok(true)
+    }
+    fn prompt(&self, output: &mut impl Write) -> io::Result<()> {
+        if self.over.is_none() {
+            write!(output, "{} to move> ", self.side())?;
+        } else {
+            write!(output, "> ")?;
+        }
+        output.flush()
+    }
+}
+
+/*
+Plays a game on the terminal, reading moves and commands from input until it runs out or the
+player quits. With an engine it plays its side by itself
+*/
+pub fn run(
+    input: impl BufRead,
+    mut output: impl Write,
+    color: bool,
+    engine: Option<Opponent>,
+) -> io::Result<()> {
+    let mut terminal = Terminal::new(color, engine);
+    writeln!(output, "Type help for the commands")?;
+    terminal.draw(&mut output)?;
+    terminal.reply(&mut output)?;
+    terminal.prompt(&mut output)?;
+    for line in input.lines() {
+        if !terminal.command(&line?, &mut output)? {
+            break;
+        }
+        terminal.prompt(&mut output)?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
@@ -0,0 +1,1049 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+use chess::clock::Clock;
+
+use crate::api::ApiError;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    RoundRobin,
+    Swiss,
+    Knockout,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntrantKind {
+    Player,
+    Engine, // no game is opened for these, the organizer reports how it went
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Entrant {
+    pub name: String,
+    pub kind: EntrantKind,
+    pub rating: f64, // seeds the pairings
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Pairing {
+    pub white: usize,         // index in entrants
+    pub black: Option<usize>, // None is a bye
+    pub game_id: Option<u64>,
+    pub result: Option<String>, // "1-0", "0-1" or "1/2-1/2"
+}
+impl Pairing {
+    /*
+    What entrant scored in this pairing, None if they weren't in it or it isn't over yet
+    */
+    pub fn points(&self, entrant: usize) -> Option<f64> {
+        let white_points: f64 = match self.result.as_deref()? {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            _ => 0.5,
+        };
+        if self.white == entrant {
+            Some(white_points)
+        } else if self.black == Some(entrant) {
+            Some(1.0 - white_points)
+        } else {
+            None
+        }
+    }
+    pub fn opponent(&self, entrant: usize) -> Option<usize> {
+        if self.white == entrant {
+            self.black
+        } else if self.black == Some(entrant) {
+            Some(self.white)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Round {
+    pub number: u32,
+    pub stage: u32, // knockout rounds replaying drawn matches share the stage they replay
+    pub pairings: Vec<Pairing>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Tournament {
+    pub id: u64,
+    pub name: String,
+    pub organizer: String,
+    pub format: Format,
+    pub swiss_rounds: u32,
+    pub minutes: Option<u64>,
+    pub increment: u64,
+    pub rated: bool,
+    pub entrants: Vec<Entrant>, // best seed first once the first round is out
+    pub rounds: Vec<Round>,
+    pub created_at: DateTime<Utc>,
+    pub finished: bool,
+}
+
+pub fn is_valid_result(result: &str) -> bool {
+    matches!(result, "1-0" | "0-1" | "1/2-1/2")
+}
+
+impl Tournament {
+    pub fn new(id: u64, name: &str, organizer: &str, format: Format) -> Tournament {
+        Tournament {
+            id,
+            name: name.to_string(),
+            organizer: organizer.to_string(),
+            format,
+            swiss_rounds: 0,
+            minutes: None,
+            increment: 0,
+            rated: false,
+            entrants: Vec::new(),
+            rounds: Vec::new(),
+            created_at: Utc::now(),
+            finished: false,
+        }
+    }
+    pub fn clock(&self) -> Option<Clock> {
+        self.minutes
+            .map(|minutes| Clock::new(minutes * 60_000, self.increment * 1000))
+    }
+    pub fn register(&mut self, name: &str, kind: EntrantKind, rating: f64) -> Result<(), ApiError> {
+        if !self.rounds.is_empty() {
+            return Err(ApiError::conflict(
+                "registration_closed",
+                "the tournament has already started",
+            ));
+        }
+        if self.entrants.iter().any(|e| e.name == name) {
+            return Err(ApiError::conflict(
+                "already_registered",
+                format!("{} is already registered", name),
+            ));
+        }
+        self.entrants.push(Entrant {
+            name: name.to_string(),
+            kind,
+            rating,
+        });
+        Ok(())
+    }
+    fn round_complete(&self) -> bool {
+        self.rounds
+            .last()
+            .is_none_or(|round| round.pairings.iter().all(|p| p.result.is_some()))
+    }
+    /*
+    Pairs the next round, once every game of the current one has a result. Returns its number
+    */
+    pub fn next_round(&mut self) -> Result<u32, ApiError> {
+        if self.finished {
+            return Err(ApiError::conflict(
+                "tournament_over",
+                "the tournament is over",
+            ));
+        }
+        if self.entrants.len() < 2 {
+            return Err(ApiError::conflict(
+                "not_enough_players",
+                "a tournament needs at least two entrants",
+            ));
+        }
+        if !self.round_complete() {
+            return Err(ApiError::conflict(
+                "round_in_progress",
+                "the current round still has games going",
+            ));
+        }
+        if self.rounds.is_empty() {
+            // pairing numbers follow the ratings
+            self.entrants.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        }
+        let (stage, pairings): (u32, Vec<Pairing>) = match self.upcoming() {
+            Some(next) => next,
+            None => {
+                self.finished = true;
+                return Err(ApiError::conflict(
+                    "tournament_over",
+                    "the tournament is over",
+                ));
+            }
+        };
+        if pairings.is_empty() {
+            return Err(ApiError::conflict(
+                "no_pairings",
+                "nobody can be paired without meeting someone twice",
+            ));
+        }
+        self.rounds.push(Round {
+            number: self.rounds.len() as u32 + 1,
+            stage,
+            pairings,
+        });
+        Ok(self.rounds.len() as u32)
+    }
+    /*
+    Stage and pairings of the round after the current one, None when there's nothing left to play
+    */
+    fn upcoming(&self) -> Option<(u32, Vec<Pairing>)> {
+        let number = self.rounds.len() as u32 + 1;
+        match self.format {
+            Format::RoundRobin => {
+                let players = self.entrants.len() + self.entrants.len() % 2;
+                if number as usize >= players {
+                    return None;
+                }
+                let pairings = berger_round(players, number as usize)
+                    .into_iter()
+                    // with an odd number of entrants the last number is a rest
+                    .filter(|&(white, black)| {
+                        white < self.entrants.len() && black < self.entrants.len()
+                    })
+                    .map(|(white, black)| Pairing {
+                        white,
+                        black: Some(black),
+                        game_id: None,
+                        result: None,
+                    })
+                    .collect();
+                Some((number, pairings))
+            }
+            Format::Swiss => {
+                if number > self.swiss_rounds {
+                    return None;
+                }
+                Some((number, self.swiss_pairings()))
+            }
+            Format::Knockout => self.knockout_pairings(),
+        }
+    }
+    /*
+    Fills in results of games that ended, result_of gives the result of a server game by id
+    */
+    pub fn collect_results(&mut self, result_of: impl Fn(u64) -> Option<String>) {
+        for round in self.rounds.iter_mut() {
+            for pairing in round.pairings.iter_mut() {
+                if pairing.result.is_some() {
+                    continue;
+                }
+                if let Some(result) = pairing.game_id.and_then(&result_of) {
+                    if is_valid_result(&result) {
+                        pairing.result = Some(result);
+                    }
+                }
+            }
+        }
+        self.check_finished();
+    }
+    /*
+    Result reported by hand, for engines, forfeits and games played elsewhere
+    */
+    pub fn set_result(&mut self, round: u32, board: usize, result: &str) -> Result<(), ApiError> {
+        if !is_valid_result(result) {
+            return Err(ApiError::bad_request(
+                "invalid_result",
+                format!("{:?} is not 1-0, 0-1 or 1/2-1/2", result),
+            ));
+        }
+        let pairing: &mut Pairing = self
+            .rounds
+            .get_mut((round as usize).wrapping_sub(1))
+            .and_then(|r| r.pairings.get_mut(board.wrapping_sub(1)))
+            .ok_or_else(|| {
+                ApiError::not_found(
+                    "unknown_pairing",
+                    format!("there's no board {} in round {}", board, round),
+                )
+            })?;
+        if pairing.black.is_none() {
+            return Err(ApiError::conflict("bye", "a bye has no game to report"));
+        }
+        pairing.result = Some(result.to_string());
+        self.check_finished();
+        Ok(())
+    }
+    fn check_finished(&mut self) {
+        let last_round: bool = match self.format {
+            Format::RoundRobin => {
+                self.rounds.len() + 1 >= self.entrants.len() + self.entrants.len() % 2
+            }
+            Format::Swiss => self.rounds.len() as u32 >= self.swiss_rounds,
+            Format::Knockout => self.knockout_pairings().is_none(),
+        };
+        self.finished = !self.rounds.is_empty() && self.round_complete() && last_round;
+    }
+
+    fn pairings_of(&self, entrant: usize) -> impl Iterator<Item = (&Round, &Pairing)> {
+        self.rounds.iter().flat_map(move |round| {
+            round
+                .pairings
+                .iter()
+                .filter(move |p| p.white == entrant || p.black == Some(entrant))
+                .map(move |p| (round, p))
+        })
+    }
+    pub fn score(&self, entrant: usize) -> f64 {
+        self.pairings_of(entrant)
+            .filter_map(|(_, p)| p.points(entrant))
+            .sum()
+    }
+    fn has_met(&self, a: usize, b: usize) -> bool {
+        self.pairings_of(a).any(|(_, p)| p.opponent(a) == Some(b))
+    }
+    fn had_bye(&self, entrant: usize) -> bool {
+        self.pairings_of(entrant).any(|(_, p)| p.black.is_none())
+    }
+    /*
+    Sum of the scores of everyone entrant played
+    */
+    pub fn buchholz(&self, entrant: usize) -> f64 {
+        self.pairings_of(entrant)
+            .filter(|(_, p)| p.result.is_some())
+            .filter_map(|(_, p)| p.opponent(entrant))
+            .map(|opponent| self.score(opponent))
+            .sum()
+    }
+    /*
+    Scores of the opponents entrant beat, plus half of those they drew
+    */
+    pub fn sonneborn_berger(&self, entrant: usize) -> f64 {
+        self.pairings_of(entrant)
+            .filter_map(|(_, p)| Some(p.points(entrant)? * self.score(p.opponent(entrant)?)))
+            .sum()
+    }
+
+    /*
+    Colors entrant played so far, true for white. Byes don't count
+    */
+    fn colors(&self, entrant: usize) -> Vec<bool> {
+        self.pairings_of(entrant)
+            .filter(|(_, p)| p.black.is_some())
+            .map(|(_, p)| p.white == entrant)
+            .collect()
+    }
+    /*
+    Color entrant is due and how badly: 3 absolute, 2 strong, 1 mild. None before the first game
+    */
+    fn color_preference(&self, entrant: usize) -> Option<(bool, u8)> {
+        let colors = self.colors(entrant);
+        let last: bool = *colors.last()?;
+        let difference: i32 = colors.iter().map(|&white| if white { 1 } else { -1 }).sum();
+        let twice_in_a_row = colors.len() >= 2 && colors[colors.len() - 2] == last;
+        if twice_in_a_row || difference.abs() >= 2 {
+            Some((!last, 3))
+        } else if difference != 0 {
+            Some((difference < 0, 2))
+        } else {
+            Some((!last, 1))
+        }
+    }
+    /*
+    Two entrants who both absolutely need the same color can't be paired
+    */
+    fn colors_fit(&self, a: usize, b: usize) -> bool {
+        match (self.color_preference(a), self.color_preference(b)) {
+            (Some((a_white, 3)), Some((b_white, 3))) => a_white != b_white,
+            _ => true,
+        }
+    }
+    /*
+    Who is white between higher, the better ranked, and lower, on the given board
+    */
+    fn allocate_colors(&self, higher: usize, lower: usize, board: usize) -> (usize, usize) {
+        let higher_white: bool = match (self.color_preference(higher), self.color_preference(lower))
+        {
+            (None, None) => board % 2 == 1,
+            (Some((white, _)), None) => white,
+            (None, Some((white, _))) => !white,
+            (Some((a, _)), Some((b, _))) if a != b => a,
+            (Some((a, a_strength)), Some((_, b_strength))) => {
+                if a_strength >= b_strength {
+                    a
+                } else {
+                    !a
+                }
+            }
+        };
+        if higher_white {
+            (higher, lower)
+        } else {
+            (lower, higher)
+        }
+    }
+
+    /*
+    Dutch system: players are ranked by score then pairing number, each score group is split in
+    halves and the top half meets the bottom half in order. When that would repeat a game or break
+    the color rules, the following players in the group are tried, then the next groups down
+    */
+    fn swiss_pairings(&self) -> Vec<Pairing> {
+        let mut ranking: Vec<usize> = (0..self.entrants.len()).collect();
+        ranking.sort_by(|&a, &b| self.score(b).total_cmp(&self.score(a)).then(a.cmp(&b)));
+        let mut pairings: Vec<Pairing> = Vec::new();
+        // the bye goes to the lowest ranked player who hasn't had one yet
+        if ranking.len() % 2 == 1 {
+            let index = ranking
+                .iter()
+                .rposition(|&e| !self.had_bye(e))
+                .unwrap_or(ranking.len() - 1);
+            let entrant = ranking.remove(index);
+            pairings.push(Pairing {
+                white: entrant,
+                black: None,
+                game_id: None,
+                result: Some("1-0".to_string()),
+            });
+        }
+        let pairs = self
+            .pair_bracket(&ranking, true)
+            .or_else(|| self.pair_bracket(&ranking, false))
+            .unwrap_or_default();
+        if pairs.is_empty() && !ranking.is_empty() {
+            return Vec::new();
+        }
+        let mut boards: Vec<Pairing> = pairs
+            .into_iter()
+            .enumerate()
+            .map(|(board, (higher, lower))| {
+                let (white, black) = self.allocate_colors(higher, lower, board + 1);
+                Pairing {
+                    white,
+                    black: Some(black),
+                    game_id: None,
+                    result: None,
+                }
+            })
+            .collect();
+        boards.append(&mut pairings);
+        boards
+    }
+    fn pair_bracket(
+        &self,
+        remaining: &[usize],
+        strict_colors: bool,
+    ) -> Option<Vec<(usize, usize)>> {
+        let Some((&top, rest)) = remaining.split_first() else {
+            return Some(Vec::new());
+        };
+        let score = self.score(top);
+        let group: Vec<usize> = remaining
+            .iter()
+            .copied()
+            .filter(|&e| self.score(e) == score)
+            .collect();
+        let half = group.len() / 2;
+        let mut candidates: Vec<usize> = group[half.max(1)..].to_vec();
+        candidates.extend_from_slice(&group[1..half.max(1)]);
+        candidates.extend(rest.iter().copied().filter(|e| !group.contains(e)));
+        for opponent in candidates {
+            if self.has_met(top, opponent) || (strict_colors && !self.colors_fit(top, opponent)) {
+                continue;
+            }
+            let others: Vec<usize> = rest.iter().copied().filter(|&e| e != opponent).collect();
+            if let Some(mut pairs) = self.pair_bracket(&others, strict_colors) {
+                pairs.insert(0, (top, opponent));
+                return Some(pairs);
+            }
+        }
+        None
+    }
+
+    /*
+    Next knockout round: replays of drawn matches with colors swapped while there are any,
+    otherwise the winners meet in bracket order
+    */
+    fn knockout_pairings(&self) -> Option<(u32, Vec<Pairing>)> {
+        let Some(last) = self.rounds.last() else {
+            let seeds = bracket(self.entrants.len().next_power_of_two());
+            let pairings = seeds
+                .chunks(2)
+                .map(|pair| {
+                    if pair[1] < self.entrants.len() {
+                        Pairing {
+                            white: pair[0],
+                            black: Some(pair[1]),
+                            game_id: None,
+                            result: None,
+                        }
+                    } else {
+                        Pairing {
+                            white: pair[0],
+                            black: None,
+                            game_id: None,
+                            result: Some("1-0".to_string()),
+                        }
+                    }
+                })
+                .collect();
+            return Some((1, pairings));
+        };
+        let stage = last.stage;
+        let first: &Round = self.rounds.iter().find(|r| r.stage == stage).unwrap();
+        let mut winners: Vec<usize> = Vec::new();
+        let mut replays: Vec<Pairing> = Vec::new();
+        for pairing in &first.pairings {
+            let Some(black) = pairing.black else {
+                winners.push(pairing.white);
+                continue;
+            };
+            let games: Vec<&Pairing> = self
+                .rounds
+                .iter()
+                .filter(|r| r.stage == stage)
+                .flat_map(|r| r.pairings.iter())
+                .filter(|p| p.opponent(pairing.white) == Some(black))
+                .collect();
+            let decided = games
+                .iter()
+                .find(|p| matches!(p.result.as_deref(), Some("1-0" | "0-1")));
+            match decided {
+                Some(game) if game.result.as_deref() == Some("1-0") => winners.push(game.white),
+                Some(game) => winners.push(game.black.unwrap()),
+                None => {
+                    let last_game = games.last().unwrap();
+                    replays.push(Pairing {
+                        white: last_game.black.unwrap(),
+                        black: Some(last_game.white),
+                        game_id: None,
+                        result: None,
+                    });
+                }
+            }
+        }
+        if !replays.is_empty() {
+            return Some((stage, replays));
+        }
+        if winners.len() < 2 {
+            return None;
+        }
+        let pairings = winners
+            .chunks(2)
+            .map(|pair| {
+                // the better seed keeps white
+                let (white, black) = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+                Pairing {
+                    white,
+                    black: Some(black),
+                    game_id: None,
+                    result: None,
+                }
+            })
+            .collect();
+        Some((stage + 1, pairings))
+    }
+    /*
+    Knockout stage an entrant lost in, None while they are still in
+    */
+    fn eliminated_in(&self, entrant: usize) -> Option<u32> {
+        // draws are replayed, so any loss is the end
+        self.rounds
+            .iter()
+            .find(|r| r.pairings.iter().any(|p| p.points(entrant) == Some(0.0)))
+            .map(|r| r.stage)
+    }
+
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = (0..self.entrants.len())
+            .map(|e| Standing {
+                rank: 0,
+                name: self.entrants[e].name.clone(),
+                kind: self.entrants[e].kind,
+                rating: self.entrants[e].rating,
+                points: self.score(e),
+                games: self
+                    .pairings_of(e)
+                    .filter(|(_, p)| p.black.is_some() && p.result.is_some())
+                    .count(),
+                buchholz: self.buchholz(e),
+                sonneborn_berger: self.sonneborn_berger(e),
+                eliminated_in: match self.format {
+                    Format::Knockout => self.eliminated_in(e),
+                    _ => None,
+                },
+                entrant: e,
+            })
+            .collect();
+        let format = self.format;
+        standings.sort_by(|a, b| {
+            let stage = |s: &Standing| s.eliminated_in.unwrap_or(u32::MAX);
+            let tiebreaks = match format {
+                // Buchholz says little when everyone meets everyone
+                Format::RoundRobin => b
+                    .sonneborn_berger
+                    .total_cmp(&a.sonneborn_berger)
+                    .then(b.buchholz.total_cmp(&a.buchholz)),
+                _ => b
+                    .buchholz
+                    .total_cmp(&a.buchholz)
+                    .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger)),
+            };
+            stage(b)
+                .cmp(&stage(a))
+                .then(b.points.total_cmp(&a.points))
+                .then(tiebreaks)
+                .then(a.entrant.cmp(&b.entrant))
+        });
+        for (i, standing) in standings.iter_mut().enumerate() {
+            standing.rank = i + 1;
+        }
+        standings
+    }
+
+    /*
+    Plain text crosstable. Round robins get the usual grid of results against each opponent,
+    Swiss and knockout events a column per round like "+W4" (won with white against number 4)
+    */
+    pub fn crosstable(&self) -> String {
+        let standings = self.standings();
+        let rank_of = |entrant: usize| {
+            standings
+                .iter()
+                .find(|s| s.entrant == entrant)
+                .unwrap()
+                .rank
+        };
+        let name_width = standings
+            .iter()
+            .map(|s| s.name.len())
+            .max()
+            .unwrap_or(4)
+            .max(4);
+        let mut text = String::new();
+        let _ = writeln!(text, "{}", self.name);
+        let _ = write!(text, "{:>3}  {:<name_width$}  {:>4}", "#", "Name", "Rtg");
+        let columns: usize = match self.format {
+            Format::RoundRobin => standings.len(),
+            _ => self.rounds.len(),
+        };
+        for column in 1..=columns {
+            let _ = write!(text, " {:>4}", column);
+        }
+        let _ = writeln!(text, " {:>5} {:>6} {:>6}", "Pts", "BH", "SB");
+        for standing in &standings {
+            let _ = write!(
+                text,
+                "{:>3}  {:<name_width$}  {:>4.0}",
+                standing.rank, standing.name, standing.rating
+            );
+            for column in 1..=columns {
+                let cell: String = match self.format {
+                    Format::RoundRobin => {
+                        let opponent = standings[column - 1].entrant;
+                        if opponent == standing.entrant {
+                            "*".to_string()
+                        } else {
+                            self.pairings_of(standing.entrant)
+                                .filter(|(_, p)| p.opponent(standing.entrant) == Some(opponent))
+                                .map(|(_, p)| match p.points(standing.entrant) {
+                                    Some(points) => points_symbol(points).to_string(),
+                                    None => ".".to_string(),
+                                })
+                                .collect()
+                        }
+                    }
+                    _ => self.rounds[column - 1]
+                        .pairings
+                        .iter()
+                        .find(|p| p.white == standing.entrant || p.black == Some(standing.entrant))
+                        .map(|p| {
+                            let outcome = match p.points(standing.entrant) {
+                                Some(1.0) => "+",
+                                Some(0.0) => "-",
+                                Some(_) => "=",
+                                None => "",
+                            };
+                            match p.opponent(standing.entrant) {
+                                Some(opponent) => format!(
+                                    "{}{}{}",
+                                    outcome,
+                                    if p.white == standing.entrant {
+                                        "W"
+                                    } else {
+                                        "B"
+                                    },
+                                    rank_of(opponent)
+                                ),
+                                None => "BYE".to_string(),
+                            }
+                        })
+                        .unwrap_or_default(),
+                };
+                let _ = write!(text, " {:>4}", cell);
+            }
+            let _ = writeln!(
+                text,
+                " {:>5} {:>6} {:>6}",
+                format_points(standing.points),
+                format_points(standing.buchholz),
+                format_points(standing.sonneborn_berger)
+            );
+        }
+        text
+    }
+}
+
+fn points_symbol(points: f64) -> &'static str {
+    if points == 1.0 {
+        "1"
+    } else if points == 0.0 {
+        "0"
+    } else {
+        "½"
+    }
+}
+
+fn format_points(points: f64) -> String {
+    let mut text = format!("{:.2}", points);
+    while text.ends_with('0') {
+        text.pop();
+    }
+    if text.ends_with('.') {
+        text.pop();
+    }
+    text
+}
+
+/*
+Berger table for round (from 1) of players players, an even number. Pairs are (white, black)
+by pairing number from 0, the last number alternates colors on the first board
+*/
+pub fn berger_round(players: usize, round: usize) -> Vec<(usize, usize)> {
+    let others = players - 1;
+    let half = players / 2;
+    let start = (round - 1) * half;
+    let first = start % others;
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    if round % 2 == 1 {
+        pairs.push((first, players - 1));
+    } else {
+        pairs.push((players - 1, first));
+    }
+    for board in 1..half {
+        // from 1 here, both numbers of a pair add up to round + 1 modulo others
+        let white = (start + board) % others + 1;
+        let black = match (round + 1 + 2 * others - white) % others {
+            0 => others,
+            black => black,
+        };
+        pairs.push((white - 1, black - 1));
+    }
+    pairs
+}
+
+/*
+Seeds of a knockout bracket of size players in bracket order, so that the best seeds meet as
+late as possible: 1 v 8, 4 v 5, 2 v 7, 3 v 6 for eight
+*/
+fn bracket(size: usize) -> Vec<usize> {
+    let mut seeds: Vec<usize> = vec![0];
+    while seeds.len() < size {
+        let next = seeds.len() * 2;
+        seeds = seeds.iter().flat_map(|&s| [s, next - 1 - s]).collect();
+    }
+    seeds
+}
+
+#[derive(Serialize, Debug)]
+pub struct Standing {
+    pub rank: usize,
+    pub name: String,
+    pub kind: EntrantKind,
+    pub rating: f64,
+    pub points: f64,
+    pub games: usize,
+    pub buchholz: f64,
+    pub sonneborn_berger: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eliminated_in: Option<u32>,
+    #[serde(skip)]
+    entrant: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PairingView {
+    pub board: usize,
+    pub white: String,
+    pub black: Option<String>, // none for a bye
+    pub game_id: Option<u64>,
+    pub result: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RoundView {
+    pub number: u32,
+    pub stage: u32,
+    pub pairings: Vec<PairingView>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TournamentView {
+    pub id: u64,
+    pub name: String,
+    pub organizer: String,
+    pub format: Format,
+    pub swiss_rounds: u32,
+    pub minutes: Option<u64>,
+    pub increment: u64,
+    pub rated: bool,
+    pub finished: bool,
+    pub entrants: Vec<Entrant>,
+    pub rounds: Vec<RoundView>,
+    pub standings: Vec<Standing>,
+}
+impl TournamentView {
+    pub fn new(tournament: &Tournament) -> TournamentView {
+        let name = |e: usize| tournament.entrants[e].name.clone();
+        TournamentView {
+            id: tournament.id,
+            name: tournament.name.clone(),
+            organizer: tournament.organizer.clone(),
+            format: tournament.format,
+            swiss_rounds: tournament.swiss_rounds,
+            minutes: tournament.minutes,
+            increment: tournament.increment,
+            rated: tournament.rated,
+            finished: tournament.finished,
+            entrants: tournament.entrants.clone(),
+            rounds: tournament
+                .rounds
+                .iter()
+                .map(|round| RoundView {
+                    number: round.number,
+                    stage: round.stage,
+                    pairings: round
+                        .pairings
+                        .iter()
+                        .enumerate()
+                        .map(|(i, p)| PairingView {
+                            board: i + 1,
+                            white: name(p.white),
+                            black: p.black.map(name),
+                            game_id: p.game_id,
+                            result: p.result.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            standings: tournament.standings(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TournamentSummary {
+    pub id: u64,
+    pub name: String,
+    pub format: Format,
+    pub entrants: usize,
+    pub rounds: usize,
+    pub finished: bool,
+}
+impl TournamentSummary {
+    pub fn new(tournament: &Tournament) -> TournamentSummary {
+        TournamentSummary {
+            id: tournament.id,
+            name: tournament.name.clone(),
+            format: tournament.format,
+            entrants: tournament.entrants.len(),
+            rounds: tournament.rounds.len(),
+            finished: tournament.finished,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /*
+    A tournament of players entrants, the first one rated highest
+    */
+    fn tournament(format: Format, players: usize) -> Tournament {
+        let mut tournament = Tournament::new(1, "Test", "organizer", format);
+        for i in 0..players {
+            let name = format!("player{}", i);
+            tournament
+                .register(&name, EntrantKind::Player, 2000.0 - i as f64)
+                .unwrap();
+        }
+        tournament
+    }
+
+    /*
+    Plays every round there is, result_of saying how each game between (white, black) ends
+    */
+    fn play_out(tournament: &mut Tournament, result_of: impl Fn(usize, usize) -> &'static str) {
+        while tournament.next_round().is_ok() {
+            let round = tournament.rounds.last().unwrap();
+            let (number, pairings) = (round.number, round.pairings.clone());
+            for (board, pairing) in pairings.iter().enumerate() {
+                if let Some(black) = pairing.black {
+                    let result = result_of(pairing.white, black);
+                    tournament.set_result(number, board + 1, result).unwrap();
+                }
+            }
+        }
+    }
+
+    fn games(tournament: &Tournament) -> Vec<(usize, usize)> {
+        tournament
+            .rounds
+            .iter()
+            .flat_map(|r| r.pairings.iter())
+            .filter_map(|p| Some((p.white, p.black?)))
+            .collect()
+    }
+
+    fn byes(tournament: &Tournament) -> Vec<usize> {
+        tournament
+            .rounds
+            .iter()
+            .flat_map(|r| r.pairings.iter())
+            .filter(|p| p.black.is_none())
+            .map(|p| p.white)
+            .collect()
+    }
+
+    #[test]
+    fn round_robin_everyone_meets_once() {
+        for players in [4, 5, 8] {
+            let mut tournament = tournament(Format::RoundRobin, players);
+            play_out(&mut tournament, |_, _| "1/2-1/2");
+            assert!(tournament.finished);
+            // an odd number plays as many rounds as the next even one
+            assert_eq!(tournament.rounds.len(), players + players % 2 - 1);
+            let mut met: HashSet<(usize, usize)> = HashSet::new();
+            for (white, black) in games(&tournament) {
+                assert!(met.insert((white.min(black), white.max(black))));
+            }
+            assert_eq!(met.len(), players * (players - 1) / 2);
+            for round in &tournament.rounds {
+                let seated: Vec<usize> = round
+                    .pairings
+                    .iter()
+                    .flat_map(|p| [p.white].into_iter().chain(p.black))
+                    .collect();
+                let distinct: HashSet<&usize> = seated.iter().collect();
+                assert_eq!(
+                    seated.len(),
+                    distinct.len(),
+                    "someone plays twice in a round"
+                );
+                assert_eq!(seated.len(), players - players % 2);
+            }
+        }
+    }
+
+    #[test]
+    fn berger_rounds_balance_colors() {
+        for players in [4, 6, 8] {
+            let mut whites = vec![0; players];
+            for round in 1..players {
+                for (white, _) in berger_round(players, round) {
+                    whites[white] += 1;
+                }
+            }
+            // of players - 1 games, half give or take one with white
+            for count in whites {
+                assert!(count == players / 2 || count == players / 2 - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn swiss_has_no_repeats_and_keeps_colors_fair() {
+        // seven players make a bye every round
+        for (players, rounds) in [(8, 5), (7, 5)] {
+            let mut tournament = tournament(Format::Swiss, players);
+            tournament.swiss_rounds = rounds;
+            // the better seed wins, but every third game is drawn
+            play_out(&mut tournament, |white, black| match (white + black) % 3 {
+                0 => "1/2-1/2",
+                _ if white < black => "1-0",
+                _ => "0-1",
+            });
+            assert_eq!(tournament.rounds.len(), rounds as usize);
+            let mut met: HashSet<(usize, usize)> = HashSet::new();
+            for (white, black) in games(&tournament) {
+                assert!(
+                    met.insert((white.min(black), white.max(black))),
+                    "{} and {} met twice",
+                    white,
+                    black
+                );
+            }
+            let byes = byes(&tournament);
+            assert_eq!(byes.len(), players % 2 * rounds as usize);
+            assert_eq!(byes.iter().collect::<HashSet<_>>().len(), byes.len());
+            for entrant in 0..players {
+                let colors = tournament.colors(entrant);
+                let difference: i32 = colors.iter().map(|&w| if w { 1 } else { -1 }).sum();
+                assert!(difference.abs() <= 2, "{} has {:?}", entrant, colors);
+                assert!(
+                    !colors.windows(3).any(|w| w[0] == w[1] && w[1] == w[2]),
+                    "{} has {:?}",
+                    entrant,
+                    colors
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn swiss_first_round_is_top_half_against_bottom_half() {
+        let mut tournament = tournament(Format::Swiss, 8);
+        tournament.swiss_rounds = 1;
+        tournament.next_round().unwrap();
+        let pairs: Vec<(usize, usize)> = games(&tournament)
+            .into_iter()
+            .map(|(white, black)| (white.min(black), white.max(black)))
+            .collect();
+        assert_eq!(pairs, [(0, 4), (1, 5), (2, 6), (3, 7)]);
+        // colors alternate down the boards
+        assert_eq!(games(&tournament)[0], (0, 4));
+        assert_eq!(games(&tournament)[1], (5, 1));
+    }
+
+    #[test]
+    fn bracket_meets_the_best_seeds_last() {
+        assert_eq!(bracket(2), [0, 1]);
+        assert_eq!(bracket(4), [0, 3, 1, 2]);
+        // 1 v 8, 4 v 5, 2 v 7, 3 v 6
+        assert_eq!(bracket(8), [0, 7, 3, 4, 1, 6, 2, 5]);
+    }
+
+    #[test]
+    fn knockout_gives_byes_to_the_top_seeds() {
+        let mut tournament = tournament(Format::Knockout, 6);
+        // higher seeds win, so the top two meet in the final
+        play_out(
+            &mut tournament,
+            |white, black| {
+                if white < black {
+                    "1-0"
+                } else {
+                    "0-1"
+                }
+            },
+        );
+        let boards: Vec<(usize, Option<usize>)> = tournament.rounds[0]
+            .pairings
+            .iter()
+            .map(|p| (p.white, p.black))
+            .collect();
+        assert_eq!(boards, [(0, None), (3, Some(4)), (1, None), (2, Some(5))]);
+        assert!(tournament.finished);
+        assert_eq!(tournament.rounds.len(), 3);
+        let last = tournament.rounds.last().unwrap();
+        assert_eq!(last.pairings.len(), 1);
+        assert_eq!(
+            (last.pairings[0].white, last.pairings[0].black),
+            (0, Some(1))
+        );
+    }
+}
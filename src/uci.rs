@@ -0,0 +1,411 @@
+use serde::Serialize;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ai::{Limits, Score};
+use crate::fen::START_FEN;
+use crate::moves::Move;
+
+// how long an engine gets to answer anything that isn't a search
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// how long a search may run past its time before we ask it to stop
+const SEARCH_SLACK: Duration = Duration::from_secs(5);
+
+/*
+Something the engine lets us tune, as announced in its "option name ..." lines
+*/
+#[derive(Serialize, Clone, Debug)]
+pub struct UciOption {
+    pub name: String,
+    pub kind: String, // check, spin, combo, button or string
+    pub default: Option<String>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub choices: Vec<String>, // the vars of a combo
+}
+
+/*
+"option name Hash type spin default 16 min 1 max 1024", names can have spaces in them
+*/
+pub fn parse_option(line: &str) -> Option<UciOption> {
+    let mut option = UciOption {
+        name: String::new(),
+        kind: String::new(),
+        default: None,
+        min: None,
+        max: None,
+        choices: Vec::new(),
+    };
+    let mut key = "";
+    let mut value: Vec<&str> = Vec::new();
+    let mut words = line.split_whitespace();
+    if words.next() != Some("option") {
+        return None;
+    }
+    // each keyword collects the words up to the next one
+    let mut store = |key: &str, value: &mut Vec<&str>| {
+        let text = value.join(" ");
+        value.clear();
+        match key {
+            "name" => option.name = text,
+            "type" => option.kind = text,
+            "default" => {
+                option.default = Some(if text == "<empty>" {
+                    String::new()
+                } else {
+                    text
+                })
+            }
+            "min" => option.min = text.parse().ok(),
+            "max" => option.max = text.parse().ok(),
+            "var" => option.choices.push(text),
+            _ => {}
+        }
+    };
+    for word in words {
+        if matches!(word, "name" | "type" | "default" | "min" | "max" | "var") && !key.is_empty() {
+            store(key, &mut value);
+            key = word;
+        } else if key.is_empty() {
+            key = word;
+        } else {
+            value.push(word);
+        }
+    }
+    store(key, &mut value);
+    if option.name.is_empty() {
+        return None;
+    }
+    Some(option)
+}
+
+/*
+What an "info" line says about the search, None where it didn't say
+*/
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Info {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time_ms: Option<u64>,
+    pub pv: Vec<String>, // "e2e4" form
+}
+
+const INFO_KEYWORDS: [&str; 17] = [
+    "depth",
+    "seldepth",
+    "time",
+    "nodes",
+    "pv",
+    "multipv",
+    "score",
+    "currmove",
+    "currmovenumber",
+    "hashfull",
+    "nps",
+    "tbhits",
+    "sbhits",
+    "cpuload",
+    "string",
+    "refutation",
+    "currline",
+];
+
+pub fn parse_info(line: &str) -> Info {
+    let mut info = Info::default();
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let mut i = 1;
+    let number = |i: usize| words.get(i).and_then(|w| w.parse::<u64>().ok());
+    while i < words.len() {
+        match words[i] {
+            "depth" => info.depth = number(i + 1).map(|n| n as u32),
+            "seldepth" => info.seldepth = number(i + 1).map(|n| n as u32),
+            "multipv" => info.multipv = number(i + 1).map(|n| n as u32),
+            "nodes" => info.nodes = number(i + 1),
+            "nps" => info.nps = number(i + 1),
+            "time" => info.time_ms = number(i + 1),
+            "score" => {
+                let value: Option<i32> = words.get(i + 2).and_then(|w| w.parse().ok());
+                info.score = match (words.get(i + 1), value) {
+                    (Some(&"cp"), Some(cp)) => Some(Score::Cp(cp)),
+                    (Some(&"mate"), Some(moves)) => Some(Score::Mate(moves)),
+                    _ => None,
+                };
+                i += 2;
+            }
+            "pv" => {
+                while i + 1 < words.len() && !INFO_KEYWORDS.contains(&words[i + 1]) {
+                    info.pv.push(words[i + 1].to_string());
+                    i += 1;
+                }
+            }
+            // the rest of the line is free text
+            "string" => break,
+            _ => {}
+        }
+        i += 1;
+    }
+    info
+}
+
+/*
+"e2e4", "e7e8q": UCI wants squares and promotions in lower case, and drops like "N@f3"
+*/
+pub fn uci_move(mov: &Move) -> String {
+    let text = mov.to_string();
+    match text.split_once('@') {
+        Some((piece, square)) => format!("{}@{}", piece, square.to_ascii_lowercase()),
+        None => text.to_ascii_lowercase(),
+    }
+}
+
+/*
+What a search ended with: the move and the last thing the engine said about each line (the
+best one first)
+*/
+#[derive(Clone)]
+pub struct BestMove {
+    pub mov: Move,
+    pub lines: Vec<Info>,
+}
+impl BestMove {
+    pub fn score(&self) -> Option<Score> {
+        self.lines.first().and_then(|info| info.score)
+    }
+}
+
+/*
+An external engine speaking UCI, running as a child process
+*/
+pub struct UciEngine {
+    pub name: String,
+    pub author: Option<String>,
+    pub options: Vec<UciOption>,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+fn timed_out(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("engine didn't answer {}", what),
+    )
+}
+
+fn invalid_option(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+impl UciEngine {
+    /*
+    Starts the engine and waits for it to list its options and say uciok
+    */
+    pub fn start(command: &str, args: &[String]) -> io::Result<UciEngine> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        // reading happens on its own thread so a silent engine can't block us forever
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = UciEngine {
+            name: command.to_string(),
+            author: None,
+            options: Vec::new(),
+            child,
+            stdin,
+            lines,
+        };
+        engine.send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = engine.read_line(deadline, "uci")?;
+            let line = line.trim();
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                engine.author = Some(author.trim().to_string());
+            } else if let Some(option) = parse_option(line) {
+                engine.options.push(option);
+            } else if line == "uciok" {
+                return Ok(engine);
+            }
+        }
+    }
+    pub fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+    fn read_line(&mut self, deadline: Instant, what: &str) -> io::Result<String> {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(left) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(timed_out(what)),
+            Err(RecvTimeoutError::Disconnected) => {
+                let status = match self.child.try_wait() {
+                    Ok(Some(status)) => format!(" ({})", status),
+                    _ => String::new(),
+                };
+                Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    format!("engine exited{}", status),
+                ))
+            }
+        }
+    }
+    /*
+    Sets one of the options the engine announced, checking the value fits it. Names are
+    matched without caring about case, like UCI says
+    */
+    pub fn has_option(&self, name: &str) -> bool {
+        self.options
+            .iter()
+            .any(|o| o.name.eq_ignore_ascii_case(name))
+    }
+    pub fn set_option(&mut self, name: &str, value: &str) -> io::Result<()> {
+        let option = self
+            .options
+            .iter()
+            .find(|o| o.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| invalid_option(format!("{} has no option {:?}", self.name, name)))?;
+        let fits = match option.kind.as_str() {
+            "check" => value == "true" || value == "false",
+            "spin" => value.parse::<i64>().is_ok_and(|v| {
+                option.min.is_none_or(|min| v >= min) && option.max.is_none_or(|max| v <= max)
+            }),
+            "combo" => option.choices.iter().any(|c| c.eq_ignore_ascii_case(value)),
+            _ => true,
+        };
+        if !fits {
+            return Err(invalid_option(format!(
+                "{:?} doesn't fit option {} ({})",
+                value, option.name, option.kind
+            )));
+        }
+        let command = if option.kind == "button" {
+            format!("setoption name {}", option.name)
+        } else {
+            format!("setoption name {} value {}", option.name, value)
+        };
+        self.send(&command)
+    }
+    pub fn is_ready(&mut self) -> io::Result<()> {
+        self.send("isready")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while self.read_line(deadline, "isready")?.trim() != "readyok" {}
+        Ok(())
+    }
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.send("ucinewgame")?;
+        self.is_ready()
+    }
+    /*
+    Sets up the position reached from fen after moves, the way a Game records its history
+    */
+    pub fn position(&mut self, fen: &str, moves: &[Move]) -> io::Result<()> {
+        let mut command = if fen == START_FEN {
+            "position startpos".to_string()
+        } else {
+            format!("position fen {}", fen)
+        };
+        if !moves.is_empty() {
+            command.push_str(" moves");
+            for mov in moves {
+                command.push(' ');
+                command.push_str(&uci_move(mov));
+            }
+        }
+        self.send(&command)
+    }
+    /*
+    Searches the position set up last and waits for its bestmove. An engine that runs too long
+    is told to stop, one that still doesn't answer is given up on
+    */
+    pub fn go(&mut self, limits: &Limits) -> io::Result<BestMove> {
+        let mut go = "go".to_string();
+        if let Some(depth) = limits.depth {
+            go.push_str(&format!(" depth {}", depth));
+        }
+        if let Some(nodes) = limits.nodes {
+            go.push_str(&format!(" nodes {}", nodes));
+        }
+        if let Some(time) = limits.movetime {
+            go.push_str(&format!(" movetime {}", time.as_millis()));
+        }
+        if go == "go" {
+            go.push_str(" depth 4");
+        }
+        self.send(&go)?;
+        let mut deadline =
+            Instant::now() + limits.movetime.unwrap_or(Duration::from_secs(60)) + SEARCH_SLACK;
+        let mut stopped = false;
+        let mut lines: Vec<Info> = Vec::new();
+        loop {
+            let line = match self.read_line(deadline, "go") {
+                Ok(line) => line,
+                Err(e) if e.kind() == io::ErrorKind::TimedOut && !stopped => {
+                    self.send("stop")?;
+                    stopped = true;
+                    deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if line.starts_with("info") {
+                let info = parse_info(&line);
+                if info.score.is_none() && info.pv.is_empty() {
+                    continue;
+                }
+                let index = info.multipv.unwrap_or(1).max(1) as usize - 1;
+                if lines.len() <= index {
+                    lines.resize(index + 1, Info::default());
+                }
+                lines[index] = info;
+            } else if let Some(rest) = line.strip_prefix("bestmove") {
+                let text = rest.split_whitespace().next().unwrap_or("");
+                let mov = Move::parse(text).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("engine played {:?}", text),
+                    )
+                })?;
+                return Ok(BestMove { mov, lines });
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
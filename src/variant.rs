@@ -0,0 +1,362 @@
+use crate::aux_func::{int_to_letter, letter_to_int};
+use crate::game::{Game, Termination};
+use crate::piece::Piece;
+use crate::position::Position;
+
+/*
+Rules a game can play by on top of the usual ones. Every hook defaults to normal chess, a
+variant only overrides what it changes
+*/
+pub trait Variant: Sync {
+    /*
+    How the API and storage call it, like "three_check"
+    */
+    fn name(&self) -> &'static str;
+    /*
+    The value of PGN's Variant tag
+    */
+    fn pgn_name(&self) -> &'static str;
+    /*
+    The value of UCI_Variant for engines that play variants, Fairy-Stockfish's names
+    */
+    fn uci_name(&self) -> &'static str;
+    /*
+    A result the variant's own rules decide right after a move, looked at before mate and the
+    draw rules
+    */
+    fn result(&self, _game: &Game) -> Option<(String, Termination)> {
+        None
+    }
+    /*
+    Whether white's king, or black's, counts as being in check
+    */
+    fn is_in_check(&self, game: &Game, white: bool) -> bool {
+        game.is_king_attacked(white)
+    }
+    /*
+    Whether a move by white, or black, that led to after may be played. The piece already
+    moved and its capture already happened
+    */
+    fn allows(&self, after: &Game, white: bool) -> bool {
+        !after.is_in_check(white)
+    }
+    /*
+    Whether pieces can be dropped from the pockets
+    */
+    fn has_drops(&self) -> bool {
+        false
+    }
+    /*
+    What else happens when a piece takes captured on square, after it got there
+    */
+    fn after_capture(&self, _game: &mut Game, _square: &Position, _captured: &Piece) {}
+    /*
+    Called after every move, with the other side to move
+    */
+    fn after_move(&self, _game: &mut Game) {}
+}
+
+pub struct Standard;
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "standard"
+    }
+    fn pgn_name(&self) -> &'static str {
+        "Standard"
+    }
+    fn uci_name(&self) -> &'static str {
+        "chess"
+    }
+}
+
+/*
+Getting the king to one of the four centre squares wins
+*/
+pub struct KingOfTheHill;
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "king_of_the_hill"
+    }
+    fn pgn_name(&self) -> &'static str {
+        "King of the Hill"
+    }
+    fn uci_name(&self) -> &'static str {
+        "kingofthehill"
+    }
+    fn result(&self, game: &Game) -> Option<(String, Termination)> {
+        let on_hill =
+            |king: &Position| ('D'..='E').contains(&king.column) && (4..=5).contains(&king.row);
+        if on_hill(&game.kings.0) {
+            Some(("1-0".to_string(), Termination::KingOfTheHill))
+        } else if on_hill(&game.kings.1) {
+            Some(("0-1".to_string(), Termination::KingOfTheHill))
+        } else {
+            None
+        }
+    }
+}
+
+// checks it takes to win a three-check game
+pub const CHECKS_TO_WIN: u32 = 3;
+
+/*
+Checking the other king for the third time wins
+*/
+pub struct ThreeCheck;
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "three_check"
+    }
+    fn pgn_name(&self) -> &'static str {
+        "Three-check"
+    }
+    fn uci_name(&self) -> &'static str {
+        "3check"
+    }
+    fn result(&self, game: &Game) -> Option<(String, Termination)> {
+        if game.checks.0 >= CHECKS_TO_WIN {
+            Some(("1-0".to_string(), Termination::ThreeChecks))
+        } else if game.checks.1 >= CHECKS_TO_WIN {
+            Some(("0-1".to_string(), Termination::ThreeChecks))
+        } else {
+            None
+        }
+    }
+    fn after_move(&self, game: &mut Game) {
+        if game.is_in_check(game.white_to_move) {
+            if game.white_to_move {
+                game.checks.1 += 1;
+            } else {
+                game.checks.0 += 1;
+            }
+        }
+    }
+}
+
+/*
+Captures blow up the taking piece and every piece next to the square but pawns. Blowing up the
+other king wins, so kings never take and a king next to the other one can't be checked
+*/
+pub struct Atomic;
+impl Atomic {
+    fn kings_touch(game: &Game) -> bool {
+        let (white, black) = &game.kings;
+        (letter_to_int(white.column) - letter_to_int(black.column)).abs() <= 1
+            && (white.row - black.row).abs() <= 1
+    }
+}
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "atomic"
+    }
+    fn pgn_name(&self) -> &'static str {
+        "Atomic"
+    }
+    fn uci_name(&self) -> &'static str {
+        "atomic"
+    }
+    fn result(&self, game: &Game) -> Option<(String, Termination)> {
+        if !game.has_king(false) {
+            Some(("1-0".to_string(), Termination::Explosion))
+        } else if !game.has_king(true) {
+            Some(("0-1".to_string(), Termination::Explosion))
+        } else {
+            None
+        }
+    }
+    fn is_in_check(&self, game: &Game, white: bool) -> bool {
+        // taking a king next to your own would blow up both
+        game.has_king(white)
+            && game.has_king(!white)
+            && !Atomic::kings_touch(game)
+            && game.is_king_attacked(white)
+    }
+    fn allows(&self, after: &Game, white: bool) -> bool {
+        if !after.has_king(white) {
+            return false;
+        }
+        // blowing up the other king ends the game before any check matters
+        !after.has_king(!white) || !after.is_in_check(white)
+    }
+    fn after_capture(&self, game: &mut Game, square: &Position, _captured: &Piece) {
+        game.pieces.remove(&(square.column, square.row));
+        let column = letter_to_int(square.column);
+        for c in (column - 1).max(1)..=(column + 1).min(8) {
+            for row in (square.row - 1).max(1)..=(square.row + 1).min(8) {
+                let key = (int_to_letter(c), row);
+                let Some(piece) = game.pieces.get(&key) else {
+                    continue;
+                };
+                if piece.name == "pawn" {
+                    continue;
+                }
+                let (white, position) = (piece.white, piece.position.clone());
+                if piece.name == "rook" {
+                    game.mark_rook_moved(white, &position);
+                }
+                game.pieces.remove(&key);
+                game.promoted.remove(&key);
+            }
+        }
+    }
+}
+
+/*
+Captured pieces change sides into the capturer's pocket, and instead of moving a player can
+drop one of them on an empty square
+*/
+pub struct Crazyhouse;
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "crazyhouse"
+    }
+    fn pgn_name(&self) -> &'static str {
+        "Crazyhouse"
+    }
+    fn uci_name(&self) -> &'static str {
+        "crazyhouse"
+    }
+    fn has_drops(&self) -> bool {
+        true
+    }
+    fn after_capture(&self, game: &mut Game, _square: &Position, captured: &Piece) {
+        let pocket = if captured.white {
+            &mut game.pockets.1
+        } else {
+            &mut game.pockets.0
+        };
+        pocket.push(captured.name.clone());
+    }
+}
+
+pub static STANDARD: Standard = Standard;
+pub static VARIANTS: [&(dyn Variant + 'static); 5] =
+    [&Standard, &KingOfTheHill, &ThreeCheck, &Atomic, &Crazyhouse];
+
+/*
+The variant called name, like "atomic"
+*/
+pub fn by_name(name: &str) -> Option<&'static dyn Variant> {
+    VARIANTS.iter().copied().find(|v| v.name() == name)
+}
+
+/*
+The variant a PGN Variant tag stands for, Standard for tags it doesn't know
+*/
+pub fn by_pgn_name(tag: &str) -> &'static dyn Variant {
+    VARIANTS
+        .iter()
+        .copied()
+        .find(|v| v.pgn_name().eq_ignore_ascii_case(tag))
+        .unwrap_or(&STANDARD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IllegalMove;
+    use crate::moves::Move;
+    use crate::notation::parse_san;
+
+    fn game(fen: &str, variant: &str) -> Game {
+        let mut game = Game::from_fen(fen).unwrap();
+        game.variant = by_name(variant).unwrap();
+        game
+    }
+
+    fn play(game: &mut Game, san: &str) {
+        let mov = parse_san(game, san).unwrap();
+        game.play(&mov).unwrap();
+    }
+
+    fn drop(name: &str, square: &str) -> Move {
+        Move::drop(name, Position::parse(square).unwrap())
+    }
+
+    #[test]
+    fn drops_follow_the_rules() {
+        let knight = game("4k3/8/8/8/8/8/8/4K3[Np] w - - 0 1", "crazyhouse");
+        let try_drop = |name: &str, square: &str| knight.clone().play(&drop(name, square));
+        assert_eq!(
+            try_drop("queen", "d4"),
+            Err(IllegalMove::NotInPocket("queen".to_string()))
+        );
+        // the pawn is black's to drop
+        assert_eq!(
+            try_drop("pawn", "d4"),
+            Err(IllegalMove::NotInPocket("pawn".to_string()))
+        );
+        assert_eq!(try_drop("knight", "e8"), Err(IllegalMove::SquareTaken));
+        assert_eq!(try_drop("knight", "d6"), Ok(()));
+        let pawn = game("4k3/8/8/8/8/8/8/4K3[P] w - - 0 1", "crazyhouse");
+        for square in ["a1", "a8"] {
+            assert_eq!(
+                pawn.clone().play(&drop("pawn", square)),
+                Err(IllegalMove::PawnOnBackRank)
+            );
+        }
+        let mut dropped = pawn.clone();
+        dropped.play(&drop("pawn", "a7")).unwrap();
+        assert!(dropped.pockets.0.is_empty());
+        assert_eq!(dropped.to_fen(), "4k3/P7/8/8/8/8/8/4K3 b - - 0 1");
+        // only crazyhouse has pockets at all
+        let mut standard = pawn.clone();
+        standard.variant = &STANDARD;
+        assert_eq!(
+            standard.play(&drop("pawn", "a7")),
+            Err(IllegalMove::NoDrops)
+        );
+    }
+
+    #[test]
+    fn captures_fill_the_pocket() {
+        let mut game = game("4k3/8/8/3p4/4P3/8/8/4K3[] w - - 0 1", "crazyhouse");
+        play(&mut game, "exd5");
+        assert_eq!(game.pockets, (vec!["pawn".to_string()], Vec::new()));
+        play(&mut game, "Ke7");
+        play(&mut game, "P@f6+");
+        assert!(game.pockets.0.is_empty());
+        play(&mut game, "Kxf6");
+        assert_eq!(game.pockets.1, ["pawn"]);
+    }
+
+    #[test]
+    fn promoted_pieces_go_back_as_pawns() {
+        let mut game = game("3rk3/8/8/8/8/8/8/3Q~K3[] b - - 0 1", "crazyhouse");
+        play(&mut game, "Rxd1+");
+        assert_eq!(game.pockets.1, ["pawn"]);
+        play(&mut game, "Kxd1");
+        // the rook never was a pawn
+        assert_eq!(game.pockets.0, ["rook"]);
+        assert!(game.promoted.is_empty());
+    }
+
+    #[test]
+    fn king_on_the_hill_wins() {
+        let mut game = game("4k3/p7/8/8/8/3K4/P7/8 w - - 0 1", "king_of_the_hill");
+        play(&mut game, "Kc4");
+        assert_eq!(game.result(), None);
+        play(&mut game, "Kd7");
+        play(&mut game, "Kd4");
+        assert_eq!(
+            game.result(),
+            Some(("1-0".to_string(), Termination::KingOfTheHill))
+        );
+    }
+
+    #[test]
+    fn third_check_wins() {
+        let mut game = game("4k3/8/8/8/8/8/8/R3K3 w - - 0 1 +1+0", "three_check");
+        play(&mut game, "Ra8+");
+        assert_eq!(game.checks, (2, 0));
+        assert_eq!(game.result(), None);
+        play(&mut game, "Ke7");
+        play(&mut game, "Ra7+");
+        assert_eq!(game.checks, (3, 0));
+        // the king can still get out of it, the check count alone ends the game
+        assert_eq!(
+            game.result(),
+            Some(("1-0".to_string(), Termination::ThreeChecks))
+        );
+    }
+}
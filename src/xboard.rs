@@ -0,0 +1,319 @@
+use std::io::{self, BufRead, Write};
+use std::time::{Duration, Instant};
+
+use chess::ai::{search, Limits, Score};
+use chess::book::{Book, BookPick};
+use chess::computer::time_budget;
+use chess::game::{init_pieces, Game, Termination};
+use chess::moves::Move;
+use chess::tablebase;
+use chess::uci::uci_move;
+use chess::IllegalMove;
+
+/*
+Time control set by "level": moves per control (0 for all of them), base time and increment
+*/
+struct Level {
+    moves: u32,
+    base_ms: u64,
+    increment_ms: u64,
+}
+
+/*
+The built-in engine behind the Chess Engine Communication Protocol (xboard, WinBoard), version 2.
+Moves come and go in coordinate form: "e2e4", "e7e8q"
+*/
+struct Xboard {
+    start: Game, // where the game began, after "new" or "setboard"
+    game: Game,
+    keys: Vec<String>, // position keys since start, for repetitions
+    force: bool,       // just keep track of the moves without playing any
+    engine_white: bool,
+    over: bool,
+    post: bool,
+    depth: Option<u32>,
+    move_time: Option<Duration>,
+    level: Option<Level>,
+    time_left_ms: Option<u64>,
+    book: Option<Book>,
+}
+
+/*
+"5" or "5:30" minutes, as level gives the base time
+*/
+fn parse_minutes(text: &str) -> Option<u64> {
+    match text.split_once(':') {
+        Some((minutes, seconds)) => {
+            Some(minutes.parse::<u64>().ok()? * 60_000 + seconds.parse::<u64>().ok()? * 1000)
+        }
+        None => Some((text.parse::<f64>().ok()? * 60_000.0) as u64),
+    }
+}
+
+/*
+"40 5 0", "0 2:30 1": moves per control, base time and increment in seconds
+*/
+fn parse_level(text: &str) -> Option<Level> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let [moves, base, increment] = fields.as_slice() else {
+        return None;
+    };
+    Some(Level {
+        moves: moves.parse().ok()?,
+        base_ms: parse_minutes(base)?,
+        increment_ms: (increment.parse::<f64>().ok()? * 1000.0) as u64,
+    })
+}
+
+fn termination_comment(white_won: Option<bool>, termination: Termination) -> &'static str {
+    match (termination, white_won) {
+        (Termination::Checkmate, Some(true)) => "White mates",
+        (Termination::Checkmate, _) => "Black mates",
+        (Termination::Stalemate, _) => "Stalemate",
+        (Termination::KingOfTheHill, _) => "King reached the hill",
+        (Termination::ThreeChecks, _) => "Third check",
+        (Termination::Explosion, _) => "King exploded",
+        _ => "Insufficient material",
+    }
+}
+
+impl Xboard {
+    fn new(book: Option<Book>) -> Xboard {
+        let game = init_pieces();
+        Xboard {
+            keys: vec![game.position_key()],
+            start: game.clone(),
+            game,
+            force: false,
+            engine_white: false,
+            over: false,
+            post: false,
+            depth: None,
+            move_time: None,
+            level: None,
+            time_left_ms: None,
+            book,
+        }
+    }
+    fn set_start(&mut self, game: Game) {
+        self.keys = vec![game.position_key()];
+        self.start = game.clone();
+        self.game = game;
+        self.over = false;
+    }
+    /*
+    Takes back the last plies moves by replaying the rest from the start
+    */
+    fn take_back(&mut self, plies: usize) {
+        let history: Vec<Move> = self.game.history.clone();
+        let keep = history.len().saturating_sub(plies);
+        let start = self.start.clone();
+        self.set_start(start);
+        for mov in &history[..keep] {
+            let _ = self.game.play(mov);
+            self.keys.push(self.game.position_key());
+        }
+    }
+    /*
+    "1-0 {White mates}" and the like once the game is over
+    */
+    fn result(&self) -> Option<String> {
+        if let Some((result, termination)) = self.game.result() {
+            let white_won = match result.as_str() {
+                "1-0" => Some(true),
+                "0-1" => Some(false),
+                _ => None,
+            };
+            return Some(format!(
+                "{} {{{}}}",
+                result,
+                termination_comment(white_won, termination)
+            ));
+        }
+        if self.game.halfmove_clock >= 100 {
+            return Some("1/2-1/2 {50 move rule}".to_string());
+        }
+        let current = self.keys.last()?;
+        if self.keys.iter().filter(|k| *k == current).count() >= 3 {
+            return Some("1/2-1/2 {Draw by repetition}".to_string());
+        }
+        None
+    }
+    fn play(&mut self, mov: &Move, output: &mut impl Write) -> io::Result<Result<(), IllegalMove>> {
+        if let Err(e) = self.game.play(mov) {
+            return Ok(Err(e));
+        }
+        self.keys.push(self.game.position_key());
+        if let Some(result) = self.result() {
+            self.over = true;
+            writeln!(output, "{}", result)?;
+        }
+        Ok(Ok(()))
+    }
+    fn limits(&self) -> Limits {
+        let mut limits = Limits {
+            depth: self.depth,
+            ..Limits::default()
+        };
+        if let Some(time) = self.move_time {
+            limits.movetime = Some(time);
+        } else if let (Some(level), Some(left)) = (&self.level, self.time_left_ms) {
+            limits.movetime = Some(if level.moves > 0 {
+                // spread what's left over the moves until the next time control
+                let played = self.game.fullmove_number.saturating_sub(1) % level.moves;
+                let to_go = (level.moves - played) as u64;
+                Duration::from_millis((left / (to_go + 1)).max(50))
+            } else {
+                time_budget(left, level.increment_ms)
+            });
+        }
+        limits
+    }
+    /*
+    Thinks about the position and plays the move it finds, if the engine is to move
+    */
+    fn think(&mut self, output: &mut impl Write) -> io::Result<()> {
+        if self.force || self.over || self.game.white_to_move != self.engine_white {
+            return Ok(());
+        }
+        let book_move = self
+            .book
+            .as_ref()
+            .and_then(|book| book.pick(&self.game, BookPick::Weighted, &mut rand::thread_rng()));
+        if let Some(mov) = book_move {
+            writeln!(output, "move {}", uci_move(&mov))?;
+            let _ = self.play(&mov, output)?;
+            return Ok(());
+        }
+        let started = Instant::now();
+        let Some(result) = search(&self.game, &self.limits()) else {
+            return Ok(());
+        };
+        if self.post {
+            // ply, score, time in centiseconds, nodes and the line
+            let score = match result.score {
+                Score::Cp(cp) => cp,
+                Score::Mate(moves) if moves > 0 => 100_000 + moves,
+                Score::Mate(moves) => -100_000 + moves,
+            };
+            let line: Vec<String> = result.pv.iter().map(uci_move).collect();
+            writeln!(
+                output,
+                "{} {} {} {} {}",
+                result.depth,
+                score,
+                started.elapsed().as_millis() / 10,
+                result.nodes,
+                line.join(" ")
+            )?;
+        }
+        writeln!(output, "move {}", uci_move(&result.best))?;
+        let _ = self.play(&result.best, output)?;
+        Ok(())
+    }
+    /*
+    Handles one line from the GUI, false once it says quit
+    */
+    fn command(&mut self, line: &str, output: &mut impl Write) -> io::Result<bool> {
+        let (command, rest) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let rest = rest.trim();
+        match command {
+            "" | "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer"
+            | "name" | "rating" | "ics" | "otim" | "?" | "white" | "black" => {}
+            "protover" => writeln!(
+                output,
+                "feature myname=\"chess\" ping=1 setboard=1 usermove=1 playother=1 san=0 \
+                 time=1 draw=0 sigint=0 sigterm=0 reuse=1 analyze=0 colors=0 egt=\"syzygy\" done=1"
+            )?,
+            "ping" => writeln!(output, "pong {}", rest)?,
+            "new" => {
+                self.set_start(init_pieces());
+                self.force = false;
+                self.engine_white = false;
+                self.depth = None;
+                self.time_left_ms = self.level.as_ref().map(|level| level.base_ms);
+            }
+            "force" => self.force = true,
+            "go" => {
+                self.force = false;
+                self.engine_white = self.game.white_to_move;
+                self.think(output)?;
+            }
+            "playother" => {
+                self.force = false;
+                self.engine_white = !self.game.white_to_move;
+            }
+            "level" => match parse_level(rest) {
+                Some(level) => {
+                    self.time_left_ms = Some(level.base_ms);
+                    self.level = Some(level);
+                    self.move_time = None;
+                }
+                None => writeln!(output, "Error (bad time control): level {}", rest)?,
+            },
+            "st" => match rest.parse::<f64>() {
+                Ok(seconds) => self.move_time = Some(Duration::from_secs_f64(seconds.max(0.01))),
+                Err(_) => writeln!(output, "Error (bad time): st {}", rest)?,
+            },
+            "sd" => match rest.parse::<u32>() {
+                Ok(depth) => self.depth = Some(depth.max(1)),
+                Err(_) => writeln!(output, "Error (bad depth): sd {}", rest)?,
+            },
+            // our clock, in centiseconds
+            "time" => {
+                if let Ok(centiseconds) = rest.parse::<u64>() {
+                    self.time_left_ms = Some(centiseconds * 10);
+                }
+            }
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "usermove" => match Move::parse(rest) {
+                Some(mov) if !self.over => match self.play(&mov, output)? {
+                    Ok(()) => self.think(output)?,
+                    // the protocol's way of giving a reason
+                    Err(e) => writeln!(output, "Illegal move ({}): {}", e, rest)?,
+                },
+                _ => writeln!(output, "Illegal move: {}", rest)?,
+            },
+            "undo" => {
+                self.take_back(1);
+            }
+            "remove" => {
+                self.take_back(2);
+            }
+            "result" => self.over = true,
+            "setboard" => match Game::from_fen(rest) {
+                Ok(game) => self.set_start(game),
+                Err(e) => writeln!(output, "tellusererror Illegal position: {}", e)?,
+            },
+            // where the GUI keeps its Syzygy tables, only the first path given is used
+            "egtpath" => match rest.split_once(' ') {
+                Some(("syzygy", path)) => {
+                    if let Err(e) = tablebase::load(path.trim()) {
+                        writeln!(output, "tellusererror Can't read tablebases: {}", e)?;
+                    }
+                }
+                _ => writeln!(output, "Error (unsupported tablebases): {}", rest)?,
+            },
+            "quit" => return Ok(false),
+            _ => writeln!(output, "Error (unknown command): {}", command)?,
+        }
+        Ok(true)
+    }
+}
+
+/*
+Plays as an xboard engine over input and output until the GUI quits, taking its moves from
+book while it has some
+*/
+pub fn run(input: impl BufRead, mut output: impl Write, book: Option<Book>) -> io::Result<()> {
+    let mut xboard = Xboard::new(book);
+    for line in input.lines() {
+        let keep_going = xboard.command(&line?, &mut output)?;
+        output.flush()?;
+        if !keep_going {
+            break;
+        }
+    }
+    Ok(())
+}
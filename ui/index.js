@@ -4,10 +4,28 @@ const white_button = document.querySelector('.choose_white');
 const black_button = document.querySelector('.choose_black');
 const white_text = document.querySelector('.wtext');
 const black_text = document.querySelector('.btext');
+const spectator_text = document.querySelector('.spectators');
+const games_list = document.querySelector('.games');
+const seeks_list = document.querySelector('.seeks');
+const seek_button = document.querySelector('#seek-button');
 const rows = 8;
 const cols = 8;
 let username = "johnDoe";
-const dom = 'http://127.0.0.1:8080/'
+const dom = 'http://127.0.0.1:8080/api/v1/'
+let game_id = new URLSearchParams(window.location.search).get('game');
+let socket = null;
+let lobby_socket = null;
+
+// the tokens we got for our seats in each game, they go along with moves and resets
+function keep_token(id, color, token){
+    sessionStorage.setItem('token-' + id + '-' + color, token);
+}
+function seat_token(color){
+    return sessionStorage.getItem('token-' + game_id + '-' + color);
+}
+function color_of(players){
+    return players.white == username ? "white" : "black";
+}
 
 let click_count = 0;
 let current_chosen = 'z1';
@@ -17,6 +35,7 @@ let board_state = {};
 let chosen_color = "none";
 let chosen_players = ["none","none"];
 let player_to_move = "white";
+let legal_moves = {}; // "E2" -> [{to: "E4", promotions: []}, ...]
 
 // Define the initial positions of the pieces
 const initialPositions = {
@@ -67,69 +86,108 @@ function get_piece_symbol(piece){
     }
     return piece_symbol;
 }
-//make function parsing through json looking for pices 'A1':'wpawn' and return a configuration of the board
+//make function parsing through json looking for pices 'A1':{color:'white',name:'pawn'} and return a configuration of the board
 function parse_board_state(data){
     let board_state = {};
-    for (let key in data){
-        if (key.toString().length == 2){
-            board_state[key.toUpperCase()] = data[key];
-        }
+    for (let key in data.board){
+        const piece = data.board[key];
+        board_state[key.toUpperCase()] = (piece.color == "white" ? "w" : "b") + piece.name;
     }
     return board_state;
 }
 
+// the game from the url, or the first one running, or a brand new one
+async function find_game() {
+    if (game_id != null){
+        return;
+    }
+    const games = await (await fetch(dom + 'games')).json();
+    if (games.length > 0){
+        game_id = games[0].id;
+    }else{
+        const data = await (await fetch(dom + 'games', {method: 'POST'})).json();
+        game_id = data.id;
+    }
+}
+
 async function get_board_state() {
-    const url = dom + 'boardstate';
+    const url = dom + 'games/' + game_id;
     const response = await fetch(url);
     console.log("Fetching from:", url);
     const data = await response.json();
+    return apply_state(data);
+}
+
+function apply_state(data) {
     console.log(data);
-    if (data.to_move=="b"){
+    game_id = data.id;
+    legal_moves = data.legal_moves;
+    spectator_text.textContent = "Spectators: " + data.spectators;
+    if (data.to_move=="black"){
         player_to_move="black";
     }else{
         player_to_move="white";
     }
-    if (data.wchosen!="none"){
+    if (data.players.white != null){
         chosen_players[0] = "white";
-        white_text.textContent = "White: "+data.wchosen;
+        white_text.textContent = "White: "+data.players.white;
     }else{
         chosen_players[0] = "none";
         white_text.textContent = "White: Available";
     }
-    if (data.bchosen!="none"){
+    if (data.players.black != null){
         chosen_players[1] = "black";
-        black_text.textContent = "Black: " + data.bchosen;
+        black_text.textContent = "Black: " + data.players.black;
     }else{
         chosen_players[1] = "none";
         black_text.textContent = "Black: Available";
     }
 
-    if (data.wchosen == username){
+    if (data.players.white == username){
         chosen_color = "white";
     }
-    if (data.bchosen == username){
+    if (data.players.black == username){
         chosen_color = "black";
     }
     return data;
 }
 async function reset_board(){
+    const response = await fetch(dom + 'games/' + game_id + '/reset', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json'
+        },
+        body: JSON.stringify({name: username, token: seat_token("white") || seat_token("black")})
+    });
+    const data = await response.json();
+    if (!response.ok){
+        console.log(data.error.code + ": " + data.error.message);
+        return null;
+    }
+    // the seats are empty again, the tokens are no good anymore
+    sessionStorage.removeItem('token-' + game_id + '-white');
+    sessionStorage.removeItem('token-' + game_id + '-black');
     username = "none";
     chosen_color = "none";
     chosen_players = ["none","none"];  
-    const response = await fetch(dom + 'reset');
-    return await response.json();
+    return data;
 } 
 
-async function move_piece(start_pos, end_pos){
-    const response = await fetch(dom + 'movepiece', {
+async function move_piece(start_pos, end_pos, promotion){
+    const response = await fetch(dom + 'games/' + game_id + '/move', {
         method: 'POST',
         headers: {
             'Content-Type': 'application/json'
         },
-        body: JSON.stringify({start_pos: start_pos.toUpperCase(), end_pos: end_pos.toUpperCase()})
+        body: JSON.stringify({name: username, token: seat_token(player_to_move), start_pos: start_pos.toUpperCase(), end_pos: end_pos.toUpperCase(), promotion: promotion})
     });
     const data = await response.json();
     //console.log(data);
+    if (!response.ok){
+        console.log(data.error.code + ": " + data.error.message);
+        return null;
+    }
+    legal_moves = data.legal_moves;
     return data;
 }
 
@@ -155,6 +213,7 @@ function draw_chessboard( board_state ){
                 }else{
                     piece.className = 'piece black';
                 }
+                piece.draggable = true;
                 square.appendChild(piece);
             }
             chessboard.appendChild(square);
@@ -162,45 +221,108 @@ function draw_chessboard( board_state ){
     }
 }
 
+// legal target of the piece on start, or undefined if it can't go there
+function find_target(start, end){
+    const targets = legal_moves[start] || [];
+    return targets.find(target => target.to == end);
+}
+
+function show_targets(start){
+    for (const target of legal_moves[start] || []){
+        document.querySelector(`.square[data-position=${target.to}]`).classList.add('legal');
+    }
+}
+
+function clear_selection(){
+    document.querySelectorAll('.square.selected, .square.legal').forEach(square => {
+        square.classList.remove('selected');
+        square.classList.remove('legal');
+    });
+    click_count = 0;
+}
+
+async function try_move(start, end){
+    const target = find_target(start, end);
+    if (target === undefined){
+        return;
+    }
+    let promotion = null;
+    if (target.promotions.length > 0){
+        promotion = prompt(`Promote to (${target.promotions.join(", ")})`, target.promotions[0]);
+        if (!target.promotions.includes(promotion)){
+            return;
+        }
+    }
+    console.log(`Move from ${start} to ${end}`);
+    const data = await move_piece(start, end, promotion);
+    if (data != null) {
+        console.log("Valid move");
+        const board_state = parse_board_state(data);
+        update_board_state(board_state);
+        if (player_to_move == "white"){
+            player_to_move = "black";
+        }else{
+            player_to_move = "white";
+        }
+    }
+}
+
 // Add the click event listener to the square
 chessboard.addEventListener('click', async (event) => {
 
-    const selectedSquare = event.target;
-    const selectedPiece = selectedSquare.querySelector('.piece');
-    if (player_to_move != chosen_color){
+    const selectedSquare = event.target.closest('.square');
+    if (selectedSquare == null || player_to_move != chosen_color){
         return;
     }
+    const selectedPiece = selectedSquare.querySelector('.piece');
     if (click_count == 0) {
         if (selectedPiece) {
             selectedSquare.classList.add('selected');
             console.log(`Selected piece: ${selectedPiece.textContent}, in ${selectedSquare.dataset.position}`);
             start_pos = selectedSquare.dataset.position;
+            show_targets(start_pos);
             click_count++;
         } else {
             click_count = 0;
         }
     } else if (click_count == 1) {
         end_pos = selectedSquare.dataset.position;
-        console.log(`Move from ${start_pos} to ${end_pos}`);
-        const data = await move_piece(start_pos, end_pos);
-        if (data.is_valid == "true") {
-            console.log("Valid move");
-            const board_state = parse_board_state(data);
-            update_board_state(board_state);
-            if (player_to_move == "white"){
-                player_to_move = "black";
-            }else{
-                player_to_move = "white";
-            }
-            click_count++;
-        }
-        const prev_square = document.querySelector(`.square[data-position=${start_pos}]`);
-        prev_square.classList.remove('selected');
-        click_count = 0;
+        clear_selection();
+        await try_move(start_pos, end_pos);
     }
     
 });
 
+// Drag and drop, only legal squares accept the piece
+chessboard.addEventListener('dragstart', (event) => {
+    const square = event.target.closest('.square');
+    if (square == null || player_to_move != chosen_color || !legal_moves[square.dataset.position]){
+        event.preventDefault();
+        return;
+    }
+    clear_selection();
+    start_pos = square.dataset.position;
+    square.classList.add('selected');
+    show_targets(start_pos);
+});
+chessboard.addEventListener('dragover', (event) => {
+    const square = event.target.closest('.square');
+    if (square != null && find_target(start_pos, square.dataset.position) !== undefined){
+        event.preventDefault();
+    }
+});
+chessboard.addEventListener('drop', async (event) => {
+    event.preventDefault();
+    const square = event.target.closest('.square');
+    clear_selection();
+    if (square != null){
+        await try_move(start_pos, square.dataset.position);
+    }
+});
+chessboard.addEventListener('dragend', () => {
+    clear_selection();
+});
+
 async function update_board_state(board_state) {
     // Remove all existing pieces
     const pieces = document.querySelectorAll('.piece');
@@ -212,16 +334,124 @@ async function update_board_state(board_state) {
         const newPiece = document.createElement('span');
         newPiece.textContent = get_piece_symbol(board_state[position]);
         newPiece.className = `piece ${board_state[position].charAt(0) === 'w' ? 'white' : 'black'}`;
+        newPiece.draggable = true;
         square.appendChild(newPiece);
     }
 }
 
 
+// live updates from the server, the board redraws on every new state
+function watch_game() {
+    if (socket != null){
+        socket.onclose = null;
+        socket.close();
+    }
+    const url = dom.replace('http', 'ws') + 'games/' + game_id + '/watch?name=' + encodeURIComponent(username);
+    socket = new WebSocket(url);
+    socket.onmessage = (event) => {
+        const data = JSON.parse(event.data);
+        if (data.type == "state"){
+            update_board_state(parse_board_state(apply_state(data)));
+        }
+    };
+    socket.onclose = () => setTimeout(watch_game, 1000);
+}
+
+// seeks and pairings, a game_started for this player takes them to the new game
+function watch_lobby() {
+    if (lobby_socket != null){
+        lobby_socket.onclose = null;
+        lobby_socket.close();
+    }
+    const url = dom.replace('http', 'ws') + 'lobby/watch?name=' + encodeURIComponent(username);
+    lobby_socket = new WebSocket(url);
+    lobby_socket.onmessage = (event) => {
+        const data = JSON.parse(event.data);
+        if (data.type == "seeks"){
+            list_seeks(data.seeks);
+        } else if (data.type == "game_started"){
+            keep_token(data.id, color_of(data), data.token);
+            window.location.search = '?game=' + data.id;
+        }
+    };
+    lobby_socket.onclose = () => setTimeout(watch_lobby, 1000);
+}
+
+function list_seeks(seeks) {
+    seeks_list.textContent = "";
+    for (const seek of seeks){
+        const item = document.createElement('li');
+        const time = seek.minutes == null ? "untimed" : `${seek.minutes}+${seek.increment}`;
+        item.textContent = `${seek.name} (${Math.round(seek.rating)}) ${time} ${seek.rated ? "rated" : "casual"} `;
+        if (seek.name != username){
+            const button = document.createElement('button');
+            button.textContent = "Accept";
+            button.addEventListener('click', () => accept_seek(seek.id));
+            item.appendChild(button);
+        }
+        seeks_list.appendChild(item);
+    }
+}
+
+async function post_seek() {
+    const response = await fetch(dom + 'seeks', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json'
+        },
+        body: JSON.stringify({
+            name: username,
+            minutes: parseInt(document.getElementById('seek-minutes').value),
+            increment: parseInt(document.getElementById('seek-increment').value),
+            rated: document.getElementById('seek-rated').checked
+        })
+    });
+    const data = await response.json();
+    if (!response.ok){
+        console.log(data.error.message);
+    } else if (data.status == "matched"){
+        keep_token(data.game.id, color_of(data.game.players), data.game.token);
+        window.location.search = '?game=' + data.game.id;
+    }
+}
+
+async function accept_seek(id) {
+    const response = await fetch(dom + 'seeks/' + id + '/accept', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json'
+        },
+        body: JSON.stringify({name: username})
+    });
+    const data = await response.json();
+    if (!response.ok){
+        console.log(data.error.message);
+        return;
+    }
+    keep_token(data.id, color_of(data.players), data.token);
+    window.location.search = '?game=' + data.id;
+}
+
+async function list_games() {
+    const games = await (await fetch(dom + 'games')).json();
+    games_list.textContent = "";
+    for (const game of games){
+        const link = document.createElement('a');
+        link.href = '?game=' + game.id;
+        link.textContent = `#${game.id} ${game.players.white || "?"} vs ${game.players.black || "?"} (${game.spectators} watching)`;
+        const item = document.createElement('li');
+        item.appendChild(link);
+        games_list.appendChild(item);
+    }
+}
+
 async function initializeBoard() {
     try {
+        await find_game();
         const board_state_data = await get_board_state();
         const board_state = parse_board_state(board_state_data);
         draw_chessboard(board_state);
+        watch_game();
     } catch (e) {
         console.log(e);
         draw_chessboard(initialPositions);
@@ -231,9 +461,13 @@ async function initializeBoard() {
 reset_button.addEventListener('click', handle_reset);
 white_button.addEventListener('click', handle_white);
 black_button.addEventListener('click', handle_black);
+seek_button.addEventListener('click', post_seek);
 async function handle_reset() {
     const data = await reset_board();
-    const board_state = parse_board_state(data);
+    if (data == null){
+        return;
+    }
+    const board_state = parse_board_state(apply_state(data));
     update_board_state(board_state);
     player_to_move = "white";
     const name_div = document.querySelector('.name-chooser');
@@ -252,19 +486,19 @@ async function handle_white() {
     if (chosen_players[0] != "none"){
         return;
     }
-    const response = await fetch(dom + 'white', {
+    const response = await fetch(dom + 'games/' + game_id + '/seats/white', {
         method: 'POST',
         headers: {
             'Content-Type': 'application/json'
         },
-        body: JSON.stringify({name: username, color: "white"})
+        body: JSON.stringify({name: username})
     });
-    const data = await response.json();
-    if (data.status == "taken"){
+    if (response.status == 409){
         console.log("White is taken");
-    }else{
+    }else if (response.ok){
         console.log("White is available");
         chosen_color = "white";
+        keep_token(game_id, "white", (await response.json()).token);
     }
     const board_state_data = await get_board_state();
     const board_state = parse_board_state(board_state_data);
@@ -274,33 +508,26 @@ async function handle_black() {
     if (chosen_players[1] != "none"){
         return;
     }
-    const response = await fetch(dom + 'black', {
+    const response = await fetch(dom + 'games/' + game_id + '/seats/black', {
         method: 'POST',
         headers: {
             'Content-Type': 'application/json'
         },
-        body: JSON.stringify({name: username, color: "black"})
+        body: JSON.stringify({name: username})
     });
-    const data = await response.json();
-    if (data.status == "taken"){
+    if (response.status == 409){
         console.log("Black is taken");
-    }else{
+    }else if (response.ok){
         console.log("Black is available");
         chosen_color = "black";
+        keep_token(game_id, "black", (await response.json()).token);
     }
     const board_state_data = await get_board_state();
     const board_state = parse_board_state(board_state_data);
     update_board_state(board_state);
 }
-//add timer to execute getBoardstate()
-
-async function timed_update()
-{
-    const board_state_data = await get_board_state();
-    const board_state = parse_board_state(board_state_data);
-    update_board_state(board_state);
-}
-setInterval(timed_update, 1000);
+//the board comes through the websocket, the list of games is still polled
+setInterval(list_games, 5000);
 
 function handle_name(){
     username = document.getElementById('player').value;
@@ -309,8 +536,13 @@ function handle_name(){
     const name_div = document.querySelector('.name-chooser');
     name_div.textContent = "Player: " + username;
     console.log(username);
+    // reconnect so the server knows who this is
+    watch_game();
+    watch_lobby();
 }
 
 console.log("Initializing board");
 initializeBoard();
+list_games();
+watch_lobby();
 